@@ -1,26 +1,113 @@
+use super::fonts::{self, Antialiasing, Font, Fonts, Hinting, PREVIEW_TEXT};
+use super::gsettings;
+use super::icons::{self, IconTheme, PREVIEW_CURSOR, PREVIEW_ICONS, PREVIEW_SIZE};
+use super::theme::{self, Theme, Toolkit};
+use crate::display::wayfire;
+use crate::error::error::{Error, Result};
+use crate::runner::runner::{Cmd, Runner};
 use crate::settings::settings::Settings;
-use eframe::egui::{ComboBox, Grid};
-use std::collections::BTreeMap;
+use eframe::egui::{
+    ComboBox, Context, DragValue, FontData, FontFamily, Grid, RichText, Slider, TextureHandle, Ui,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::path::Path;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+
+/// cursor size where gsettings has none, pixels
+const DEFAULT_CURSOR_SIZE: u32 = 24;
+/// cursor sizes offered, pixels
+const CURSOR_SIZES: [u32; 5] = [24, 32, 48, 64, 96];
+/// font files egui can render, others would make it panic
+const FONT_EXTENSIONS: [&str; 3] = ["ttf", "otf", "ttc"];
+
+/// Icons, cursor and fonts, the looks besides the theme
+#[derive(Debug, Clone, PartialEq)]
+struct Look {
+    icon_theme: String,
+    cursor_theme: String,
+    /// pixels
+    cursor_size: u32,
+    fonts: Fonts,
+}
+
+impl Default for Look {
+    fn default() -> Self {
+        Self {
+            icon_theme: String::new(),
+            cursor_theme: String::new(),
+            cursor_size: DEFAULT_CURSOR_SIZE,
+            fonts: Fonts::default(),
+        }
+    }
+}
+
+impl Look {
+    fn load(runner: &Runner) -> Self {
+        Self {
+            icon_theme: gsettings::get(runner, "icon-theme").unwrap_or_default(),
+            cursor_theme: gsettings::get(runner, "cursor-theme").unwrap_or_default(),
+            cursor_size: gsettings::get(runner, "cursor-size")
+                .and_then(|s| s.parse().ok())
+                .filter(|size| *size > 0)
+                .unwrap_or(DEFAULT_CURSOR_SIZE),
+            fonts: Fonts::load(runner),
+        }
+    }
+}
+
+/// Textures and fonts of the previews, made when first shown
+#[derive(Default)]
+struct Previews {
+    /// icon theme they show, and its icons
+    icons: Option<(String, Vec<TextureHandle>)>,
+    /// cursor theme and size it shows, and the cursor if it has one
+    cursor: Option<((String, u32), Option<TextureHandle>)>,
+    /// families whose file was looked for, loaded into egui or not
+    fonts: BTreeSet<String>,
+}
 
 pub struct Appearance<'a> {
     name: &'a str,
-    now: u8,
-    themes: BTreeMap<u8, String>,
+    now: usize,
+    themes: BTreeMap<usize, Theme>,
+    /// toolkit whose themes are offered, `None` for all
+    toolkit: Option<Toolkit>,
+    /// where to look for themes, see [`theme::dirs`]
+    dirs: Vec<PathBuf>,
+    /// icon and cursor themes, see [`icons::discover`]
+    icon_themes: Vec<IconTheme>,
+    /// where to look for them, see [`icons::dirs`]
+    icon_dirs: Vec<PathBuf>,
+    look: Look,
+    /// look as last read or applied, only what changed is written
+    applied: Look,
+    /// font families to pick from
+    families: Vec<String>,
+    /// files the cursor goes to besides gsettings, see [`icons::write_cursor`]
+    wayfire_ini: PathBuf,
+    environment: PathBuf,
+    previews: Previews,
     init: bool,
+    runner: Runner,
 }
 
 impl Settings for Appearance<'_> {
-    fn init(&mut self) {
-        let appearance = Self::_init();
+    fn init(&mut self) -> Result<()> {
+        let appearance = Self::_init(&self.runner, &self.dirs, &self.icon_dirs)?;
         (self.name, self.now, self.themes) = (appearance.name, appearance.now, appearance.themes);
+        (self.icon_themes, self.look, self.applied, self.families) = (
+            appearance.icon_themes,
+            appearance.look,
+            appearance.applied,
+            appearance.families,
+        );
         self.init = true;
+        Ok(())
     }
 
     fn is_init(&self) -> bool {
-        return self.init;
+        self.init
     }
 
     fn name(&self) -> &str {
@@ -32,104 +119,578 @@ impl Settings for Appearance<'_> {
             .spacing([100.0, 8.0])
             .striped(true)
             .show(ui, |ui| {
-                ui.label("GTK Theme");
-                ComboBox::from_label("")
-                    .selected_text(self.themes.get(&self.now).unwrap_or(&"".to_owned()))
+                ui.label("Toolkit");
+                ComboBox::from_id_source("appearance_toolkit")
+                    .selected_text(self.toolkit.map_or("All", |t| t.as_str()))
                     .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.toolkit, None, "All");
+                        for toolkit in Toolkit::ALL {
+                            ui.selectable_value(&mut self.toolkit, Some(toolkit), toolkit.as_str());
+                        }
+                    });
+                ui.end_row();
+                ui.label("Theme");
+                ComboBox::from_id_source("appearance_theme")
+                    .selected_text(self.theme().unwrap_or(""))
+                    .show_ui(ui, |ui| {
+                        let toolkit = self.toolkit;
                         for (id, theme) in &self.themes {
-                            ui.selectable_value(&mut self.now, *id, theme);
+                            if toolkit.is_none_or(|t| theme.supports(t)) {
+                                ui.selectable_value(&mut self.now, *id, &theme.name)
+                                    .on_hover_text(theme.summary());
+                            }
                         }
                     });
                 ui.end_row();
-                ui.collapsing("Theme", |ui| {
-                    ui.label("sda");
-                    ui.label("sda");
-                });
+                ui.label("Supports");
+                ui.label(
+                    self.themes
+                        .get(&self.now)
+                        .map_or(String::new(), |t| t.summary()),
+                );
+                ui.end_row();
+                self.show_icons(ui);
+                self.show_cursor(ui);
+                self.show_fonts(ui);
             });
     }
-    fn apply(&mut self) {
+    fn apply(&mut self) -> Result<()> {
         println!("Appearance apply");
-        if self.now == 0 {
-            return;
+        // 1. theme, only for the parts it has
+        if let Some(theme) = self.themes.get(&self.now) {
+            if theme.has_gtk() {
+                self.runner
+                    .stdout(&gsettings::set("gtk-theme", &theme.name))?;
+            }
+            if theme.supports(Toolkit::Wm) {
+                self.runner.stdout(
+                    &Cmd::new("gsettings")
+                        .args(["set", "org.gnome.desktop.wm.preferences", "theme"])
+                        .arg(&theme.name),
+                )?;
+            }
+        }
+
+        // 2. icons, cursor and fonts that changed
+        let (look, applied) = (&self.look, &self.applied);
+        if look.icon_theme != applied.icon_theme && !look.icon_theme.is_empty() {
+            self.runner
+                .stdout(&gsettings::set("icon-theme", &look.icon_theme))?;
+        }
+        let cursor = (&look.cursor_theme, look.cursor_size);
+        if cursor != (&applied.cursor_theme, applied.cursor_size) && !look.cursor_theme.is_empty() {
+            self.runner
+                .stdout(&gsettings::set("cursor-theme", &look.cursor_theme))?;
+            self.runner.stdout(&gsettings::set(
+                "cursor-size",
+                &look.cursor_size.to_string(),
+            ))?;
+            // wayfire and X clients don't read gsettings
+            icons::write_cursor(
+                &self.wayfire_ini,
+                wayfire::running(),
+                &self.environment,
+                &look.cursor_theme,
+                look.cursor_size,
+            )?;
         }
-        let theme = self.themes.get(&self.now).unwrap();
-        let _output = Command::new("gsettings")
-            .args(["set", "org.gnome.desktop.interface", "gtk-theme"])
-            .arg(theme.as_str())
-            .output()
-            .unwrap();
-        let _output = Command::new("gsettings")
-            .args(["set", "org.gnome.desktop.wm.preferences", "theme"])
-            .arg(theme.as_str())
-            .output()
-            .unwrap();
+        if look.fonts != applied.fonts {
+            for cmd in look.fonts.cmds(&applied.fonts) {
+                self.runner.stdout(&cmd)?;
+            }
+        }
+        self.applied = self.look.clone();
+        Ok(())
     }
 }
 
+/// Saved appearance configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AppearanceState {
+    pub gtk_theme: Option<String>,
+}
+
 impl Default for Appearance<'_> {
     fn default() -> Self {
         Self {
             now: 0,
             name: "Appearance",
             themes: BTreeMap::new(),
+            toolkit: Some(Toolkit::Gtk3),
+            dirs: theme::dirs(),
+            icon_themes: Vec::new(),
+            icon_dirs: icons::dirs(),
+            look: Look::default(),
+            applied: Look::default(),
+            families: Vec::new(),
+            wayfire_ini: wayfire::config_path(),
+            environment: icons::environment_path(),
+            previews: Previews::default(),
             init: false,
+            runner: Runner::default(),
         }
     }
 }
 
 impl Appearance<'_> {
-    fn _init() -> Self {
+    pub fn new(runner: Runner) -> Self {
+        Self {
+            runner,
+            ..Default::default()
+        }
+    }
+
+    /// look for themes in `dirs` and icon themes in `icon_dirs` instead
+    /// of the XDG ones
+    #[cfg(test)]
+    pub fn with_dirs(mut self, dirs: Vec<PathBuf>, icon_dirs: Vec<PathBuf>) -> Self {
+        (self.dirs, self.icon_dirs) = (dirs, icon_dirs);
+        self
+    }
+
+    /// write the cursor to these instead of the user's files
+    #[cfg(test)]
+    pub fn with_cursor_files(mut self, wayfire_ini: PathBuf, environment: PathBuf) -> Self {
+        (self.wayfire_ini, self.environment) = (wayfire_ini, environment);
+        self
+    }
+
+    /// installed themes and the one currently in use
+    pub fn load(runner: Runner) -> Result<Self> {
+        let mut appearance = Self::new(runner);
+        appearance.init()?;
+        Ok(appearance)
+    }
+
+    /// installed themes for the toolkit of [`Appearance::set_toolkit`]
+    pub fn themes(&self) -> impl Iterator<Item = &Theme> {
+        let toolkit = self.toolkit;
+        self.themes
+            .values()
+            .filter(move |theme| toolkit.is_none_or(|t| theme.supports(t)))
+    }
+
+    /// offer only themes for `toolkit`, all if `None`
+    pub fn set_toolkit(&mut self, toolkit: Option<Toolkit>) {
+        self.toolkit = toolkit;
+    }
+
+    /// theme in use, `None` if it is not an installed one
+    pub fn theme(&self) -> Option<&str> {
+        self.themes.get(&self.now).map(|theme| theme.name.as_str())
+    }
+
+    pub fn select_theme(&mut self, theme: &str) -> Result<()> {
+        self.now = self
+            .themes
+            .iter()
+            .find(|(_, t)| t.name == theme)
+            .map(|(id, _)| *id)
+            .ok_or_else(|| Error::NotFound(format!("theme {}", theme)))?;
+        Ok(())
+    }
+
+    pub fn state(&self) -> AppearanceState {
+        AppearanceState {
+            gtk_theme: self.theme().map(|theme| theme.to_string()),
+        }
+    }
+
+    pub fn restore(&mut self, state: &AppearanceState) -> Result<()> {
+        if let Some(theme) = &state.gtk_theme {
+            self.select_theme(theme)?;
+        }
+        Ok(())
+    }
+
+    /// installed icon themes, cursor ones included
+    pub fn icon_themes(&self) -> &[IconTheme] {
+        &self.icon_themes
+    }
+
+    /// icon theme in use, empty if gsettings has none
+    pub fn icon_theme(&self) -> &str {
+        &self.look.icon_theme
+    }
+
+    /// cursor theme and size in use
+    pub fn cursor(&self) -> (&str, u32) {
+        (&self.look.cursor_theme, self.look.cursor_size)
+    }
+
+    pub fn select_icon_theme(&mut self, theme: &str) -> Result<()> {
+        self.icon_themes
+            .iter()
+            .find(|t| t.name == theme && t.has_icons())
+            .ok_or_else(|| Error::NotFound(format!("icon theme {}", theme)))?;
+        self.look.icon_theme = theme.to_string();
+        Ok(())
+    }
+
+    pub fn select_cursor(&mut self, theme: &str, size: u32) -> Result<()> {
+        self.icon_themes
+            .iter()
+            .find(|t| t.name == theme && t.cursors)
+            .ok_or_else(|| Error::NotFound(format!("cursor theme {}", theme)))?;
+        (self.look.cursor_theme, self.look.cursor_size) = (theme.to_string(), size);
+        Ok(())
+    }
+
+    fn _init(runner: &Runner, dirs: &[PathBuf], icon_dirs: &[PathBuf]) -> Result<Self> {
         let mut appearance = Appearance::default();
 
-        // 1. scan themes
-        let dir = fs::read_dir("/usr/share/themes/").unwrap();
-        let mut id = 1;
-        let sys_theme = Self::get_system_gtk_theme().unwrap_or("".to_string());
-        for entry in dir {
-            let entry = entry.unwrap();
-            let path = entry.path();
-
-            if Self::is_complete_theme_dir(&path) {
-                let theme = path.file_name().unwrap().to_str().unwrap().to_string();
-                if theme == sys_theme {
-                    appearance.now = id;
-                }
-                appearance.themes.insert(id, theme);
-                id += 1;
+        // 1. scan themes, ids from 1
+        let sys_theme = Self::get_system_gtk_theme(runner).unwrap_or_default();
+        for (id, theme) in (1..).zip(theme::discover(dirs)) {
+            if theme.name == sys_theme {
+                appearance.now = id;
             }
+            appearance.themes.insert(id, theme);
         }
 
-        appearance
+        // 2. icon and cursor themes, fonts and what is in use
+        appearance.icon_themes = icons::discover(icon_dirs);
+        appearance.look = Look::load(runner);
+        appearance.applied = appearance.look.clone();
+        appearance.families = fonts::families(runner);
+
+        Ok(appearance)
     }
 
-    fn is_complete_theme_dir(path: &Path) -> bool {
-        if !path.is_dir() {
-            return false;
+    fn get_system_gtk_theme(runner: &Runner) -> Option<String> {
+        gsettings::get(runner, "gtk-theme")
+    }
+
+    fn show_icons(&mut self, ui: &mut Ui) {
+        ui.label("Icons");
+        ComboBox::from_id_source("appearance_icons")
+            .selected_text(&self.look.icon_theme)
+            .show_ui(ui, |ui| {
+                for theme in self.icon_themes.iter().filter(|t| t.has_icons()) {
+                    ui.selectable_value(
+                        &mut self.look.icon_theme,
+                        theme.name.clone(),
+                        &theme.title,
+                    );
+                }
+            });
+        ui.end_row();
+        ui.label("Icon Preview");
+        let size = PREVIEW_SIZE as f32 / ui.ctx().pixels_per_point();
+        let textures = self.icon_previews(ui.ctx());
+        ui.horizontal(|ui| {
+            if textures.is_empty() {
+                // SVG only themes
+                ui.label("no preview");
+            }
+            for texture in textures {
+                ui.image(texture, [size, size]);
+            }
+        });
+        ui.end_row();
+    }
+
+    /// textures of the icon theme picked, remade when it changes
+    fn icon_previews(&mut self, ctx: &Context) -> &[TextureHandle] {
+        let name = &self.look.icon_theme;
+        if self
+            .previews
+            .icons
+            .as_ref()
+            .is_none_or(|(shown, _)| shown != name)
+        {
+            let theme = self.icon_themes.iter().find(|t| &t.name == name);
+            let textures = PREVIEW_ICONS
+                .iter()
+                .filter_map(|icon| {
+                    let image = icons::png(&theme?.icon(icon, PREVIEW_SIZE)?).ok()?;
+                    Some(ctx.load_texture(*icon, image))
+                })
+                .collect();
+            self.previews.icons = Some((name.clone(), textures));
         }
-        let files = vec!["gnome-shell", "xfwm4", "gtk-2.0", "gtk-3.0", "gtk-4.0"];
-        for f in files {
-            let path = path.join(f);
-            if !path.exists() {
-                return false;
+        self.previews.icons.as_ref().map_or(&[], |(_, t)| t)
+    }
+
+    fn show_cursor(&mut self, ui: &mut Ui) {
+        ui.label("Cursor");
+        ui.horizontal(|ui| {
+            ComboBox::from_id_source("appearance_cursor")
+                .selected_text(&self.look.cursor_theme)
+                .show_ui(ui, |ui| {
+                    for theme in self.icon_themes.iter().filter(|t| t.cursors) {
+                        ui.selectable_value(
+                            &mut self.look.cursor_theme,
+                            theme.name.clone(),
+                            &theme.title,
+                        );
+                    }
+                });
+            ComboBox::from_id_source("appearance_cursor_size")
+                .selected_text(format!("{} px", self.look.cursor_size))
+                .show_ui(ui, |ui| {
+                    for size in CURSOR_SIZES {
+                        ui.selectable_value(
+                            &mut self.look.cursor_size,
+                            size,
+                            format!("{} px", size),
+                        );
+                    }
+                });
+            let pixels_per_point = ui.ctx().pixels_per_point();
+            if let Some(texture) = self.cursor_preview(ui.ctx()) {
+                ui.image(texture, texture.size_vec2() / pixels_per_point);
             }
+        });
+        ui.end_row();
+    }
+
+    /// texture of the cursor picked, `None` if its theme has no such image
+    fn cursor_preview(&mut self, ctx: &Context) -> Option<&TextureHandle> {
+        let key = (self.look.cursor_theme.clone(), self.look.cursor_size);
+        if self
+            .previews
+            .cursor
+            .as_ref()
+            .is_none_or(|(shown, _)| *shown != key)
+        {
+            let texture = self
+                .icon_themes
+                .iter()
+                .find(|t| t.name == key.0)
+                .and_then(|t| t.cursor(PREVIEW_CURSOR))
+                .and_then(|path| fs::read(path).ok())
+                .and_then(|data| icons::xcursor(&data, key.1).ok())
+                .map(|image| ctx.load_texture(PREVIEW_CURSOR, image));
+            self.previews.cursor = Some((key, texture));
         }
-        return true;
+        self.previews.cursor.as_ref().and_then(|(_, t)| t.as_ref())
     }
 
-    fn get_system_gtk_theme() -> Option<String> {
-        let output = Command::new("gsettings")
-            .args(["get", "org.gnome.desktop.interface", "gtk-theme"])
-            .output()
-            .unwrap();
-        if !output.status.success() {
-            return None;
+    fn show_fonts(&mut self, ui: &mut Ui) {
+        let fonts = &mut self.look.fonts;
+        // points to pixels, the window is drawn at the scale of its output already
+        let scale = fonts.text_scaling as f32 * 96.0 / 72.0 / ui.ctx().pixels_per_point();
+        for (label, font) in [
+            ("Interface Font", &mut fonts.interface),
+            ("Document Font", &mut fonts.document),
+            ("Monospace Font", &mut fonts.monospace),
+        ] {
+            ui.label(label);
+            ui.horizontal(|ui| {
+                ComboBox::from_id_source(label)
+                    .selected_text(&font.family)
+                    .show_ui(ui, |ui| {
+                        for family in &self.families {
+                            ui.selectable_value(&mut font.family, family.clone(), family);
+                        }
+                    });
+                ui.add(
+                    DragValue::new(&mut font.size)
+                        .clamp_range(4.0..=72.0)
+                        .speed(0.5),
+                );
+            });
+            ui.end_row();
+            ui.label("");
+            let family = preview_family(&mut self.previews.fonts, &self.runner, ui.ctx(), font);
+            ui.label(
+                RichText::new(PREVIEW_TEXT)
+                    .family(family)
+                    .size(font.size * scale),
+            );
+            ui.end_row();
         }
-        match std::str::from_utf8(&output.stdout) {
-            Ok(theme) => Some(theme.trim().replace("'", "").to_string()),
-            Err(e) => {
-                eprintln!("{}", e);
-                None
-            }
+
+        ui.label("Hinting");
+        ComboBox::from_id_source("appearance_hinting")
+            .selected_text(fonts.hinting.as_str())
+            .show_ui(ui, |ui| {
+                for hinting in Hinting::ALL {
+                    ui.selectable_value(&mut fonts.hinting, hinting, hinting.as_str());
+                }
+            });
+        ui.end_row();
+        ui.label("Antialiasing");
+        ComboBox::from_id_source("appearance_antialiasing")
+            .selected_text(fonts.antialiasing.as_str())
+            .show_ui(ui, |ui| {
+                for antialiasing in Antialiasing::ALL {
+                    ui.selectable_value(
+                        &mut fonts.antialiasing,
+                        antialiasing,
+                        antialiasing.as_str(),
+                    );
+                }
+            });
+        ui.end_row();
+        ui.label("Text Scaling");
+        ui.add(Slider::new(&mut fonts.text_scaling, 0.5..=3.0).step_by(0.05));
+        ui.end_row();
+    }
+}
+
+/// egui family to preview `font` in; the file fontconfig picks for it is
+/// loaded once, and egui has it from the next frame on
+fn preview_family(
+    tried: &mut BTreeSet<String>,
+    runner: &Runner,
+    ctx: &Context,
+    font: &Font,
+) -> FontFamily {
+    let family = FontFamily::Name(font.family.as_str().into());
+    if tried.insert(font.family.clone()) {
+        if let Some(path) = font.file(runner) {
+            load_font(ctx, &font.family, &path);
         }
     }
+    // a family egui doesn't know would panic
+    if ctx.fonts().families().contains(&family) {
+        family
+    } else {
+        FontFamily::Proportional
+    }
+}
+
+/// add the font at `path` to egui as family `name`
+fn load_font(ctx: &Context, name: &str, path: &Path) {
+    let renderable = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| FONT_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+    let data = match fs::read(path) {
+        Ok(data) if renderable => data,
+        _ => return,
+    };
+    let mut definitions = ctx.fonts().lock().fonts.definitions().clone();
+    definitions
+        .font_data
+        .insert(name.to_string(), FontData::from_owned(data));
+    // glyphs it lacks come from the usual fonts
+    let mut chain = vec![name.to_string()];
+    chain.extend(
+        definitions.families[&FontFamily::Proportional]
+            .iter()
+            .cloned(),
+    );
+    definitions
+        .families
+        .insert(FontFamily::Name(name.into()), chain);
+    ctx.set_fonts(definitions);
+    ctx.request_repaint();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::runner::FakeRunner;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn system_gtk_theme_from_gsettings() {
+        let runner = Runner::new(FakeRunner::new().with_output(
+            "gsettings get org.gnome.desktop.interface gtk-theme",
+            "'Adwaita-dark'\n",
+        ));
+        assert_eq!(
+            Appearance::get_system_gtk_theme(&runner),
+            Some("Adwaita-dark".to_string())
+        );
+    }
+
+    #[test]
+    fn themes_filter_by_toolkit_and_apply_their_parts() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/themes");
+        let dirs = vec![root.join("home/.themes"), root.join("usr/share/themes")];
+        let fake = Arc::new(FakeRunner::new().with_output(
+            "gsettings get org.gnome.desktop.interface gtk-theme",
+            "'Greybird'\n",
+        ));
+        let mut appearance = Appearance::new(Runner::new(fake.clone())).with_dirs(dirs, Vec::new());
+        appearance.init().unwrap();
+        assert_eq!(appearance.theme(), Some("Greybird"));
+        let names = |a: &Appearance| a.themes().map(|t| t.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&appearance), vec!["Adwaita", "Greybird", "Nordic"]);
+        appearance.set_toolkit(Some(Toolkit::Wm));
+        assert_eq!(names(&appearance), vec!["Bear2", "Greybird"]);
+
+        appearance.apply().unwrap();
+        appearance.select_theme("Nordic").unwrap();
+        appearance.apply().unwrap();
+        let sets: Vec<String> = fake
+            .calls()
+            .into_iter()
+            .filter(|c| c.starts_with("gsettings set"))
+            .collect();
+        assert_eq!(
+            sets,
+            [
+                "gsettings set org.gnome.desktop.interface gtk-theme Greybird",
+                "gsettings set org.gnome.desktop.wm.preferences theme Greybird",
+                "gsettings set org.gnome.desktop.interface gtk-theme Nordic",
+            ]
+        );
+    }
+
+    #[test]
+    fn apply_writes_changed_icons_cursor_and_fonts() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/icons");
+        let icon_dirs = vec![root.join("home/.icons"), root.join("usr/share/icons")];
+        let dir = std::env::temp_dir().join(format!("rsettings-{}-look", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let (ini, env) = (
+            dir.join("wayfire.ini"),
+            dir.join("environment.d/cursor.conf"),
+        );
+        std::fs::write(&ini, "[input]\nxkb_layout = us\n").unwrap();
+        let get = |key: &str| format!("gsettings get org.gnome.desktop.interface {}", key);
+        let fake = Arc::new(
+            FakeRunner::new()
+                .with_output(&get("icon-theme"), "'Adwaita'\n")
+                .with_output(&get("cursor-theme"), "'Adwaita'\n")
+                .with_output(&get("cursor-size"), "24\n"),
+        );
+        let mut appearance = Appearance::new(Runner::new(fake.clone()))
+            .with_dirs(Vec::new(), icon_dirs)
+            .with_cursor_files(ini.clone(), env.clone());
+        appearance.init().unwrap();
+        assert_eq!(appearance.cursor(), ("Adwaita", 24));
+
+        // nothing changed, nothing written
+        appearance.apply().unwrap();
+        assert!(!std::fs::read_to_string(&ini).unwrap().contains("cursor") && !env.exists());
+        // cursor only themes have no icons
+        assert!(appearance.select_icon_theme("Bibata").is_err());
+        appearance.select_icon_theme("Papirus").unwrap();
+        appearance.select_cursor("Bibata", 32).unwrap();
+        appearance.look.fonts.text_scaling = 1.5;
+        appearance.apply().unwrap();
+
+        let sets: Vec<String> = fake
+            .calls()
+            .into_iter()
+            .filter(|c| c.starts_with("gsettings set"))
+            .map(|c| c.replace("org.gnome.desktop.interface ", ""))
+            .collect();
+        assert_eq!(
+            sets[..3],
+            [
+                "gsettings set icon-theme Papirus",
+                "gsettings set cursor-theme Bibata",
+                "gsettings set cursor-size 32",
+            ]
+        );
+        // of the fonts only what changed
+        assert_eq!(sets[3..], ["gsettings set text-scaling-factor 1.5"]);
+        let ini = std::fs::read_to_string(&ini).unwrap();
+        assert!(ini.contains("xkb_layout = us") && ini.contains("cursor_theme = Bibata"));
+        assert!(ini.contains("cursor_size = 32"));
+        assert_eq!(
+            std::fs::read_to_string(&env).unwrap(),
+            "XCURSOR_THEME=Bibata\nXCURSOR_SIZE=32\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,245 @@
+use std::path::PathBuf;
+
+use super::gsettings::{get, set};
+use crate::runner::runner::{Cmd, Runner};
+
+/// text shown by the font previews
+pub const PREVIEW_TEXT: &str = "The quick brown fox jumps over the lazy dog";
+
+/// How much glyph outlines are fitted to the pixel grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hinting {
+    None,
+    Slight,
+    Medium,
+    Full,
+}
+
+impl Hinting {
+    pub const ALL: [Hinting; 4] = [
+        Hinting::None,
+        Hinting::Slight,
+        Hinting::Medium,
+        Hinting::Full,
+    ];
+
+    /// value of the `font-hinting` key
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Hinting::None => "none",
+            Hinting::Slight => "slight",
+            Hinting::Medium => "medium",
+            Hinting::Full => "full",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|h| h.as_str() == s)
+    }
+}
+
+/// How glyph edges are smoothed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Antialiasing {
+    None,
+    Grayscale,
+    /// subpixel, for LCD panels
+    Rgba,
+}
+
+impl Antialiasing {
+    pub const ALL: [Antialiasing; 3] = [
+        Antialiasing::None,
+        Antialiasing::Grayscale,
+        Antialiasing::Rgba,
+    ];
+
+    /// value of the `font-antialiasing` key
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Antialiasing::None => "none",
+            Antialiasing::Grayscale => "grayscale",
+            Antialiasing::Rgba => "rgba",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.as_str() == s)
+    }
+}
+
+/// A font as gsettings writes it, `Cantarell 11`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Font {
+    pub family: String,
+    /// points
+    pub size: f32,
+}
+
+impl Font {
+    /// `FAMILY SIZE`, the family may have spaces and styles
+    pub fn parse(s: &str) -> Option<Self> {
+        let (family, size) = s.trim().rsplit_once(' ')?;
+        Some(Self {
+            family: family.to_string(),
+            size: size.parse().ok().filter(|size: &f32| *size > 0.0)?,
+        })
+    }
+
+    /// file fontconfig renders this font from
+    pub fn file(&self, runner: &Runner) -> Option<PathBuf> {
+        let out = runner
+            .stdout(
+                &Cmd::new("fc-match")
+                    .arg("--format=%{file}")
+                    .arg(&self.family),
+            )
+            .ok()?;
+        let path = PathBuf::from(out.trim());
+        path.is_file().then_some(path)
+    }
+}
+
+impl std::fmt::Display for Font {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.family, self.size)
+    }
+}
+
+/// Fonts and their rendering, the `org.gnome.desktop.interface` keys
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fonts {
+    pub interface: Font,
+    pub document: Font,
+    pub monospace: Font,
+    pub hinting: Hinting,
+    pub antialiasing: Antialiasing,
+    /// text size factor on top of the display scale
+    pub text_scaling: f64,
+}
+
+impl Default for Fonts {
+    /// GNOME defaults
+    fn default() -> Self {
+        let font = |family: &str, size| Font {
+            family: family.to_string(),
+            size,
+        };
+        Self {
+            interface: font("Cantarell", 11.0),
+            document: font("Cantarell", 11.0),
+            monospace: font("Source Code Pro", 10.0),
+            hinting: Hinting::Slight,
+            antialiasing: Antialiasing::Grayscale,
+            text_scaling: 1.0,
+        }
+    }
+}
+
+impl Fonts {
+    /// keys of gsettings, defaults where unset or unreadable
+    pub fn load(runner: &Runner) -> Self {
+        let default = Self::default();
+        let font = |key| get(runner, key).and_then(|s| Font::parse(&s));
+        Self {
+            interface: font("font-name").unwrap_or(default.interface),
+            document: font("document-font-name").unwrap_or(default.document),
+            monospace: font("monospace-font-name").unwrap_or(default.monospace),
+            hinting: get(runner, "font-hinting")
+                .and_then(|s| Hinting::from_str(&s))
+                .unwrap_or(default.hinting),
+            antialiasing: get(runner, "font-antialiasing")
+                .and_then(|s| Antialiasing::from_str(&s))
+                .unwrap_or(default.antialiasing),
+            text_scaling: get(runner, "text-scaling-factor")
+                .and_then(|s| s.parse().ok())
+                .filter(|f| *f > 0.0)
+                .unwrap_or(default.text_scaling),
+        }
+    }
+
+    /// `gsettings set` commands writing what differs from `before`, so
+    /// keys left as loaded keep their value, unreadable ones too
+    pub fn cmds(&self, before: &Fonts) -> Vec<Cmd> {
+        let strings = |fonts: &Fonts| {
+            [
+                fonts.interface.to_string(),
+                fonts.document.to_string(),
+                fonts.monospace.to_string(),
+                fonts.hinting.as_str().to_string(),
+                fonts.antialiasing.as_str().to_string(),
+                fonts.text_scaling.to_string(),
+            ]
+        };
+        let keys = [
+            "font-name",
+            "document-font-name",
+            "monospace-font-name",
+            "font-hinting",
+            "font-antialiasing",
+            "text-scaling-factor",
+        ];
+        keys.into_iter()
+            .zip(strings(self).into_iter().zip(strings(before)))
+            .filter(|(_, (now, was))| now != was)
+            .map(|(key, (now, _))| set(key, &now))
+            .collect()
+    }
+}
+
+/// Font families fontconfig knows, sorted
+pub fn families(runner: &Runner) -> Vec<String> {
+    let out = runner
+        .stdout(&Cmd::new("fc-list").args([":", "family"]))
+        .unwrap_or_default();
+    let mut families: Vec<String> = out
+        .lines()
+        // localized names follow the first, `Noto Sans CJK JP,Noto Sans CJK JP Bold`
+        .filter_map(|l| l.split(',').next())
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty())
+        .collect();
+    families.sort();
+    families.dedup();
+    families
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::appearance::gsettings::INTERFACE;
+    use crate::runner::runner::FakeRunner;
+
+    #[test]
+    fn load_gsettings_and_write_back() {
+        let key = |key: &str| format!("gsettings get {} {}", INTERFACE, key);
+        let runner = Runner::new(
+            FakeRunner::new()
+                .with_output(&key("font-name"), "'Noto Sans Medium 10.5'\n")
+                .with_output(&key("monospace-font-name"), "'Monospace'\n")
+                .with_output(&key("font-hinting"), "'full'\n")
+                .with_output(&key("text-scaling-factor"), "1.25\n"),
+        );
+        let fonts = Fonts::load(&runner);
+        assert_eq!(fonts.interface.family, "Noto Sans Medium");
+        assert_eq!(fonts.interface.size, 10.5);
+        // no size, the default stays
+        assert_eq!(fonts.monospace, Fonts::default().monospace);
+        assert_eq!(fonts.hinting, Hinting::Full);
+        assert_eq!(fonts.antialiasing, Antialiasing::Grayscale);
+        assert!(fonts.cmds(&fonts).is_empty());
+
+        // only what changed is written, the unreadable monospace stays
+        let mut changed = fonts.clone();
+        changed.interface.size = 12.0;
+        changed.text_scaling = 1.5;
+        let lines: Vec<String> = changed.cmds(&fonts).iter().map(Cmd::line).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "gsettings set org.gnome.desktop.interface font-name Noto Sans Medium 12",
+                "gsettings set org.gnome.desktop.interface text-scaling-factor 1.5",
+            ]
+        );
+    }
+}
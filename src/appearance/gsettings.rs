@@ -0,0 +1,27 @@
+use crate::runner::runner::{Cmd, Runner};
+
+/// schema of the desktop wide theme and font keys
+pub const INTERFACE: &str = "org.gnome.desktop.interface";
+
+/// Value of `key` of [`INTERFACE`], without the quotes or type gsettings
+/// adds; `None` if unset or gsettings fails.
+pub fn get(runner: &Runner, key: &str) -> Option<String> {
+    let out = runner
+        .stdout(&Cmd::new("gsettings").args(["get", INTERFACE, key]))
+        .ok()?;
+    let value = out.trim();
+    // `uint32 24`
+    let value = match value.split_once(' ') {
+        Some((kind, number)) if kind.starts_with("uint") || kind.starts_with("int") => number,
+        _ => value,
+    };
+    let value = value.trim_matches('\'');
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// `gsettings set` of `key` of [`INTERFACE`]
+pub fn set(key: &str, value: &str) -> Cmd {
+    Cmd::new("gsettings")
+        .args(["set", INTERFACE, key])
+        .arg(value)
+}
@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use eframe::egui::{Color32, ColorImage};
+
+use crate::display::wayfire::Ini;
+use crate::error::error::{Error, Result};
+use crate::settings::settings::{config_home, data_dirs, data_home};
+
+/// icons shown as preview of an icon theme
+pub const PREVIEW_ICONS: [&str; 6] = [
+    "folder",
+    "user-home",
+    "text-x-generic",
+    "image-x-generic",
+    "utilities-terminal",
+    "preferences-system",
+];
+/// size of the preview icons, pixels
+pub const PREVIEW_SIZE: u32 = 48;
+/// cursor shown as preview of a cursor theme
+pub const PREVIEW_CURSOR: &str = "left_ptr";
+/// Xcursor chunk holding an image
+const XCURSOR_IMAGE: u32 = 0xfffd_0002;
+
+/// An installed theme of `icons/NAME`, of icons, cursors or both
+#[derive(Debug, Clone, PartialEq)]
+pub struct IconTheme {
+    /// dir name, what gsettings wants
+    pub name: String,
+    /// `Name=` of its `index.theme`
+    pub title: String,
+    /// where it was found first
+    pub path: PathBuf,
+    /// `Directories=` of icons, empty for cursor only themes
+    pub directories: Vec<String>,
+    /// has a `cursors` dir
+    pub cursors: bool,
+}
+
+impl IconTheme {
+    pub fn has_icons(&self) -> bool {
+        !self.directories.is_empty()
+    }
+
+    /// PNG of `icon` closest to `size` pixels, SVG only icons have none
+    pub fn icon(&self, icon: &str, size: u32) -> Option<PathBuf> {
+        let index = Ini::read(&self.path.join("index.theme")).ok()?;
+        self.directories
+            .iter()
+            .filter_map(|dir| {
+                let path = self.path.join(dir).join(format!("{}.png", icon));
+                let dir_size: u32 = index.get(dir, "Size")?.parse().ok()?;
+                path.is_file().then(|| (dir_size.abs_diff(size), path))
+            })
+            .min()
+            .map(|(_, path)| path)
+    }
+
+    /// Xcursor file of `cursor`
+    pub fn cursor(&self, cursor: &str) -> Option<PathBuf> {
+        let path = self.path.join("cursors").join(cursor);
+        path.is_file().then_some(path)
+    }
+}
+
+/// Directories holding icon themes, first ones win: `~/.icons`,
+/// `$XDG_DATA_HOME/icons` and `icons` of every `$XDG_DATA_DIRS`.
+pub fn dirs() -> Vec<PathBuf> {
+    let home = PathBuf::from(std::env::var_os("HOME").unwrap_or_default());
+    let mut dirs = vec![home.join(".icons"), data_home().join("icons")];
+    dirs.extend(data_dirs().into_iter().map(|dir| dir.join("icons")));
+    dirs
+}
+
+/// Icon and cursor themes of `dirs`, sorted by name.
+///
+/// Icons come from the `index.theme` found first; hidden themes and
+/// `default`, which only points to another cursor theme, are left out.
+pub fn discover(dirs: &[PathBuf]) -> Vec<IconTheme> {
+    let mut themes: BTreeMap<String, IconTheme> = BTreeMap::new();
+    for dir in dirs {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            let name = match path.file_name().and_then(|f| f.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let index = Ini::read(&path.join("index.theme")).unwrap_or_default();
+            if index.get("Icon Theme", "Hidden") == Some("true") {
+                continue;
+            }
+            let directories: Vec<String> = index
+                .get("Icon Theme", "Directories")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|d| !d.is_empty())
+                .map(str::to_string)
+                .collect();
+            let cursors = path.join("cursors").is_dir();
+            if directories.is_empty() && !cursors {
+                continue;
+            }
+            let theme = themes.entry(name.clone()).or_insert_with(|| IconTheme {
+                title: index.get("Icon Theme", "Name").unwrap_or(&name).to_string(),
+                name,
+                path: path.clone(),
+                directories: Vec::new(),
+                cursors: false,
+            });
+            if theme.directories.is_empty() && !directories.is_empty() {
+                theme.directories = directories;
+                theme.path = path.clone();
+            }
+            theme.cursors |= cursors;
+        }
+    }
+    themes.into_values().collect()
+}
+
+/// Image of `data`, an Xcursor file, at the nominal size closest to `size`
+pub fn xcursor(data: &[u8], size: u32) -> Result<ColorImage> {
+    let bad = |why: &str| Error::parse("xcursor", why);
+    let u32_at = |pos: usize| -> Result<u32> {
+        data.get(pos..pos + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| bad("truncated"))
+    };
+
+    // 1. header and table of contents: type, nominal size, position
+    if data.get(..4) != Some(b"Xcur") {
+        return Err(bad("no Xcur magic"));
+    }
+    let header = u32_at(4)? as usize;
+    let entries = u32_at(12)? as usize;
+    let mut best: Option<(u32, usize)> = None;
+    for i in 0..entries {
+        let entry = header + i * 12;
+        if u32_at(entry)? != XCURSOR_IMAGE {
+            continue;
+        }
+        let (nominal, position) = (u32_at(entry + 4)?, u32_at(entry + 8)? as usize);
+        if best.is_none_or(|(n, _)| nominal.abs_diff(size) < n.abs_diff(size)) {
+            best = Some((nominal, position));
+        }
+    }
+    let (_, position) = best.ok_or_else(|| bad("no image"))?;
+
+    // 2. the image, premultiplied ARGB after a header of 9 u32
+    let (width, height) = (
+        u32_at(position + 16)? as usize,
+        u32_at(position + 20)? as usize,
+    );
+    let pixels = (0..width * height)
+        .map(|i| {
+            let [b, g, r, a] = u32_at(position + 36 + i * 4)?.to_le_bytes();
+            Ok(Color32::from_rgba_premultiplied(r, g, b, a))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ColorImage {
+        size: [width, height],
+        pixels,
+    })
+}
+
+/// Image of the PNG at `path`
+pub fn png(path: &Path) -> Result<ColorImage> {
+    let image = image::io::Reader::open(path)
+        .map_err(|source| Error::File {
+            path: path.to_path_buf(),
+            source,
+        })?
+        .decode()?;
+    let size = [image.width() as _, image.height() as _];
+    Ok(ColorImage::from_rgba_unmultiplied(
+        size,
+        image.to_rgba8().as_flat_samples().as_slice(),
+    ))
+}
+
+/// `environment.d` file giving `XCURSOR_THEME` and `XCURSOR_SIZE` to the session
+pub fn environment_path() -> PathBuf {
+    config_home().join("environment.d/90-rsettings-cursor.conf")
+}
+
+/// Set the cursor for wayfire, in `[input]` of `wayfire_ini` if it exists
+/// or `in_wayfire` (wayfire is running), and for clients reading
+/// `XCURSOR_*` from the `environment` file.
+pub fn write_cursor(
+    wayfire_ini: &Path,
+    in_wayfire: bool,
+    environment: &Path,
+    theme: &str,
+    size: u32,
+) -> Result<()> {
+    let file_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| Error::File { path, source }
+    };
+    if wayfire_ini.exists() || in_wayfire {
+        let mut ini = Ini::read(wayfire_ini)?;
+        ini.set("input", "cursor_theme", theme);
+        ini.set("input", "cursor_size", &size.to_string());
+        if let Some(dir) = wayfire_ini.parent() {
+            fs::create_dir_all(dir).map_err(file_error(dir))?;
+        }
+        ini.write(wayfire_ini)?;
+    }
+
+    if let Some(dir) = environment.parent() {
+        fs::create_dir_all(dir).map_err(file_error(dir))?;
+    }
+    let env = format!("XCURSOR_THEME={}\nXCURSOR_SIZE={}\n", theme, size);
+    fs::write(environment, env).map_err(file_error(environment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dirs() -> Vec<PathBuf> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/icons");
+        vec![root.join("home/.icons"), root.join("usr/share/icons")]
+    }
+
+    #[test]
+    fn discover_icon_and_cursor_themes() {
+        let themes = discover(&dirs());
+        let names: Vec<&str> = themes.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["Adwaita", "Bibata", "Papirus"]);
+        let adwaita = &themes[0];
+        assert!(adwaita.has_icons() && adwaita.cursors);
+        assert_eq!(
+            adwaita.icon("folder", PREVIEW_SIZE),
+            Some(adwaita.path.join("48x48/places/folder.png"))
+        );
+        assert!(adwaita.cursor(PREVIEW_CURSOR).is_some());
+        assert!(!themes[1].has_icons() && themes[1].cursors);
+        assert_eq!(themes[2].title, "Papirus Dark");
+        assert_eq!(themes[2].icon("folder", PREVIEW_SIZE), None);
+    }
+
+    #[test]
+    fn xcursor_picks_closest_size() {
+        // two images, 2x1 at nominal 24 and 1x1 at nominal 48
+        let mut data = b"Xcur".to_vec();
+        let mut put = |words: &[u32]| words.iter().for_each(|w| data.extend(w.to_le_bytes()));
+        put(&[16, 0x1_0000, 2]);
+        put(&[XCURSOR_IMAGE, 24, 40, XCURSOR_IMAGE, 48, 84]);
+        put(&[
+            36,
+            XCURSOR_IMAGE,
+            24,
+            1,
+            2,
+            1,
+            0,
+            0,
+            0,
+            0xff00_0000,
+            0x8080_0000,
+        ]);
+        put(&[36, XCURSOR_IMAGE, 48, 1, 1, 1, 0, 0, 0, 0xffff_ffff]);
+        let image = xcursor(&data, 32).unwrap();
+        assert_eq!(image.size, [2, 1]);
+        assert_eq!(
+            image.pixels[1],
+            Color32::from_rgba_premultiplied(0x80, 0, 0, 0x80)
+        );
+        assert_eq!(xcursor(&data, 40).unwrap().size, [1, 1]);
+        assert!(xcursor(&data[..30], 24).is_err());
+    }
+
+    #[test]
+    fn cursor_writes_wayfire_ini_only_in_wayfire() {
+        let dir = std::env::temp_dir().join(format!("rsettings-{}-cursor", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (ini, env) = (dir.join("wayfire/wayfire.ini"), dir.join("cursor.conf"));
+        write_cursor(&ini, false, &env, "Bibata", 32).unwrap();
+        assert!(!ini.exists());
+        assert_eq!(
+            fs::read_to_string(&env).unwrap(),
+            "XCURSOR_THEME=Bibata\nXCURSOR_SIZE=32\n"
+        );
+
+        write_cursor(&ini, true, &env, "Adwaita", 24).unwrap();
+        let written = Ini::read(&ini).unwrap();
+        assert_eq!(written.get("input", "cursor_theme"), Some("Adwaita"));
+        assert_eq!(written.get("input", "cursor_size"), Some("24"));
+        assert_eq!(
+            fs::read_to_string(&env).unwrap(),
+            "XCURSOR_THEME=Adwaita\nXCURSOR_SIZE=24\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
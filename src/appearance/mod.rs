@@ -1 +1,5 @@
 pub mod appearance;
+pub mod fonts;
+pub mod gsettings;
+pub mod icons;
+pub mod theme;
@@ -0,0 +1,170 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::settings::settings::{data_dirs, data_home};
+
+/// What a theme can style, each has its own directory in the theme
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Toolkit {
+    Gtk2,
+    Gtk3,
+    Gtk4,
+    /// window decorations of xfwm4, metacity or openbox
+    Wm,
+    /// GNOME shell
+    Shell,
+}
+
+impl Toolkit {
+    pub const ALL: [Toolkit; 5] = [
+        Toolkit::Gtk2,
+        Toolkit::Gtk3,
+        Toolkit::Gtk4,
+        Toolkit::Wm,
+        Toolkit::Shell,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Toolkit::Gtk2 => "GTK 2",
+            Toolkit::Gtk3 => "GTK 3",
+            Toolkit::Gtk4 => "GTK 4",
+            Toolkit::Wm => "WM",
+            Toolkit::Shell => "Shell",
+        }
+    }
+
+    /// name on the command line
+    pub fn id(&self) -> &'static str {
+        match self {
+            Toolkit::Gtk2 => "gtk2",
+            Toolkit::Gtk3 => "gtk3",
+            Toolkit::Gtk4 => "gtk4",
+            Toolkit::Wm => "wm",
+            Toolkit::Shell => "shell",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.id() == id)
+    }
+
+    /// files below a theme dir that make it style this toolkit; only
+    /// `gtk-3.0/gtk-keys.css` or so are key themes, not looks
+    fn files(&self) -> &'static [&'static str] {
+        match self {
+            Toolkit::Gtk2 => &["gtk-2.0/gtkrc"],
+            Toolkit::Gtk3 => &["gtk-3.0/gtk.css"],
+            Toolkit::Gtk4 => &["gtk-4.0/gtk.css"],
+            Toolkit::Wm => &["xfwm4/themerc", "metacity-1", "openbox-3/themerc"],
+            Toolkit::Shell => &["gnome-shell/gnome-shell.css"],
+        }
+    }
+}
+
+/// An installed theme
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    /// where it was found first, the one toolkits read
+    pub path: PathBuf,
+    pub toolkits: BTreeSet<Toolkit>,
+}
+
+impl Theme {
+    pub fn supports(&self, toolkit: Toolkit) -> bool {
+        self.toolkits.contains(&toolkit)
+    }
+
+    pub fn has_gtk(&self) -> bool {
+        [Toolkit::Gtk2, Toolkit::Gtk3, Toolkit::Gtk4]
+            .iter()
+            .any(|t| self.supports(*t))
+    }
+
+    /// e.g. `GTK 3, GTK 4, Shell`
+    pub fn summary(&self) -> String {
+        self.toolkits
+            .iter()
+            .map(|t| t.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Directories holding themes, first ones win: `$XDG_DATA_HOME/themes`,
+/// `~/.themes` and `themes` of every `$XDG_DATA_DIRS`.
+pub fn dirs() -> Vec<PathBuf> {
+    let home = PathBuf::from(std::env::var_os("HOME").unwrap_or_default());
+    let mut dirs = vec![data_home().join("themes"), home.join(".themes")];
+    dirs.extend(data_dirs().into_iter().map(|dir| dir.join("themes")));
+    dirs
+}
+
+/// Themes of `dirs`, sorted by name; missing dirs are skipped.
+///
+/// A theme may be split across dirs, e.g. a user `gtk-4.0` next to a
+/// system theme of the same name, so its toolkits add up.
+pub fn discover(dirs: &[PathBuf]) -> Vec<Theme> {
+    let mut themes: BTreeMap<String, Theme> = BTreeMap::new();
+    for dir in dirs {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            let name = match path.file_name().and_then(|f| f.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let toolkits = classify(&path);
+            if toolkits.is_empty() {
+                continue;
+            }
+            themes
+                .entry(name.clone())
+                .or_insert_with(|| Theme {
+                    name,
+                    path: path.clone(),
+                    toolkits: BTreeSet::new(),
+                })
+                .toolkits
+                .extend(toolkits);
+        }
+    }
+    themes.into_values().collect()
+}
+
+/// toolkits the theme at `path` styles
+fn classify(path: &Path) -> BTreeSet<Toolkit> {
+    Toolkit::ALL
+        .into_iter()
+        .filter(|t| t.files().iter().any(|f| path.join(f).exists()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discover_classifies_and_merges() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/themes");
+        let dirs = [
+            root.join("home/.local/share/themes"),
+            root.join("home/.themes"),
+            root.join("missing/themes"),
+            root.join("usr/share/themes"),
+        ];
+        let themes = discover(&dirs);
+        let names: Vec<&str> = themes.iter().map(|t| t.name.as_str()).collect();
+        // key themes style nothing
+        assert_eq!(names, vec!["Adwaita", "Bear2", "Greybird", "Nordic"]);
+        assert_eq!(themes[0].summary(), "GTK 2, GTK 3, GTK 4");
+        assert_eq!(themes[0].path, dirs[0].join("Adwaita"));
+        assert!(!themes[1].has_gtk() && themes[1].supports(Toolkit::Wm));
+        assert_eq!(themes[2].summary(), "GTK 2, GTK 3, WM");
+        assert_eq!(themes[3].summary(), "GTK 3, GTK 4, Shell");
+    }
+}
@@ -0,0 +1,607 @@
+use std::path::Path;
+use std::slice::Iter;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::appearance::appearance::Appearance;
+use crate::appearance::theme::Toolkit;
+use crate::display::autolayout::{LayoutStore, Watcher};
+use crate::display::backend::Backend;
+use crate::display::custom_mode::CustomModeStore;
+use crate::display::cvt::Timing;
+use crate::display::display::{DisplayFiles, Displays};
+use crate::display::identify;
+use crate::display::kanshi::{self, KanshiProfile};
+use crate::error::error::{Error, Result};
+use crate::network::network::Network;
+use crate::nightlight::nightlight::{Config, NightLight, Service};
+use crate::power::power::Power;
+use crate::profiles::profiles::{Profile, ProfileStore};
+use crate::runner::runner::Runner;
+use crate::settings::settings::Settings;
+use crate::sysfs::sysfs::SysFs;
+use crate::tools::tools::Tools;
+
+const USAGE: &str = "\
+Usage: rsettings [COMMAND]
+
+Without a command the settings window is opened.
+
+Commands:
+  display [list]
+  display set <OUTPUT> [--mode WxH[@HZ]] [--custom-mode WxH@HZ] [--scale S] [--pos X,Y] [--transform T] [--on|--off] [--adaptive-sync on|off]
+  display power <OUTPUT> on|off
+  display cvt <WIDTH> <HEIGHT> <HZ> [--reduced]
+  display custom-mode list
+  display custom-mode add|remove <OUTPUT> <WxH@HZ>
+  display mirror <OUTPUT> <OUTPUT>...
+  display extend
+  display identify [--seconds SECS]
+  display save
+  display saved
+  display remember
+  display layouts
+  display daemon [--interval SECS]
+  display kanshi list
+  display kanshi apply <PROFILE>
+  display kanshi export <NAME>
+  power
+  power brightness <PERCENT>
+  appearance themes [--toolkit gtk2|gtk3|gtk4|wm|shell]
+  appearance gtk-theme [THEME]
+  appearance icon-themes
+  appearance icon-theme [THEME]
+  appearance cursor [THEME [SIZE]]
+  network devices
+  network list
+  network connect <SSID>
+  nightlight
+  nightlight daemon [--interval SECS]
+  tools pick-color
+  tools shotcut
+  profile list
+  profile save <NAME>
+  profile apply <NAME>
+  profile diff <NAME>
+  profile remove <NAME>
+  profile export <NAME> <FILE>
+  profile import <FILE> [NAME]
+  help
+";
+
+/// how often `display daemon` looks for plugged monitors if nobody tells it
+const DAEMON_INTERVAL: Duration = Duration::from_secs(2);
+/// how often `nightlight daemon` updates the temperature
+const NIGHTLIGHT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Run the command line `args` (without program name), returns the exit code
+pub fn run(args: &[String]) -> i32 {
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    match Runner::from_env().and_then(|runner| {
+        dispatch(
+            &args,
+            &runner,
+            &SysFs::from_env(),
+            &DisplayFiles::from_env(),
+        )
+    }) {
+        Ok(()) => 0,
+        Err(Error::Usage(msg)) => {
+            eprintln!("rsettings: {}\n\n{}", msg, USAGE);
+            2
+        }
+        Err(e) => {
+            eprintln!("rsettings: {}", e);
+            1
+        }
+    }
+}
+
+fn dispatch(args: &[&str], runner: &Runner, sysfs: &SysFs, files: &DisplayFiles) -> Result<()> {
+    match args {
+        ["display", args @ ..] => display(args, runner, sysfs, files),
+        ["power", args @ ..] => power(args, sysfs),
+        ["appearance", args @ ..] => appearance(args, runner),
+        ["network", args @ ..] => network(args, runner),
+        ["nightlight", args @ ..] => night_light(args, runner),
+        ["tools", args @ ..] => tools(args, runner),
+        ["profile", args @ ..] => profile(args, runner, sysfs, files, &ProfileStore::from_env()),
+        ["help"] | ["--help"] | ["-h"] => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(usage(args)),
+    }
+}
+
+fn display(args: &[&str], runner: &Runner, sysfs: &SysFs, files: &DisplayFiles) -> Result<()> {
+    if let ["daemon", options @ ..] = args {
+        let interval = match options {
+            [] => DAEMON_INTERVAL,
+            ["--interval", secs] => Duration::from_secs(parse(secs, "interval")?),
+            _ => return Err(usage(args)),
+        };
+        let backend = Backend::detect(runner.clone());
+        Watcher::new(backend, LayoutStore::from_env())
+            .with_sysfs(sysfs.clone())
+            .with_custom_modes(files.custom_modes.clone())
+            .run(interval);
+        return Ok(());
+    }
+    if let ["cvt", width, height, refresh, options @ ..] = args {
+        let reduced = match options {
+            [] => false,
+            ["--reduced"] => true,
+            _ => return Err(usage(args)),
+        };
+        let timing = Timing::cvt(
+            parse(width, "width")?,
+            parse(height, "height")?,
+            parse(refresh, "refresh")?,
+            reduced,
+        )
+        .ok_or_else(|| Error::parse("mode", args[1..4].join(" ")))?;
+        println!("{}", timing);
+        println!("--custom-mode {}", timing.mode());
+        return Ok(());
+    }
+    let mut displays = Displays::new(runner.clone())
+        .with_sysfs(sysfs.clone())
+        .with_files(files.clone());
+    displays.init()?;
+    // e.g. a bad custom mode, the rest still works
+    if let Some(e) = displays.take_error() {
+        eprintln!("rsettings: {}", e);
+    }
+    match args {
+        [] | ["list"] => {
+            for display in displays.iter() {
+                println!("{}", display.summary());
+            }
+            Ok(())
+        }
+        ["set", name, options @ ..] => {
+            let display = displays.get_mut(name)?;
+            let mut options = options.iter();
+            while let Some(option) = options.next() {
+                match *option {
+                    "--mode" => display.set_mode(value(&mut options, option)?)?,
+                    "--custom-mode" => display.set_custom_mode(value(&mut options, option)?)?,
+                    "--scale" => display.set_scale(parse(value(&mut options, option)?, "scale")?),
+                    "--pos" => display.set_position(position(value(&mut options, option)?)?),
+                    "--transform" => display.set_transform(value(&mut options, option)?)?,
+                    "--on" => display.set_enable(true),
+                    "--off" => display.set_enable(false),
+                    "--adaptive-sync" => match value(&mut options, option)? {
+                        "on" => display.set_adaptive_sync(true)?,
+                        "off" => display.set_adaptive_sync(false)?,
+                        other => return Err(Error::parse("adaptive sync", other)),
+                    },
+                    _ => return Err(Error::Usage(format!("unknown option {}", option))),
+                }
+            }
+            displays.check()?;
+            displays.get_mut(name)?.apply()
+        }
+        ["power", name, power] => {
+            let on = match *power {
+                "on" => true,
+                "off" => false,
+                other => return Err(Error::parse("power", other)),
+            };
+            displays.get_mut(name)?.set_power(on)
+        }
+        ["identify", options @ ..] => {
+            let time = match options {
+                [] => identify::IDENTIFY_TIME,
+                ["--seconds", secs] => Duration::from_secs(parse(secs, "seconds")?),
+                _ => return Err(usage(args)),
+            };
+            identify::show(&displays.labels(), time)
+        }
+        ["mirror", names @ ..] => {
+            displays.mirror(names)?;
+            displays.apply_now()
+        }
+        ["extend"] => {
+            displays.extend()?;
+            displays.apply_now()
+        }
+        ["save"] => displays.save_wayfire(&files.wayfire_ini),
+        ["saved"] => {
+            for output in displays.saved().values() {
+                println!("{} {}", output.name, output.summary());
+            }
+            Ok(())
+        }
+        ["custom-mode", args @ ..] => custom_mode(args, &mut displays, &files.custom_modes),
+        ["remember"] => LayoutStore::from_env().remember(&displays),
+        ["kanshi", args @ ..] => kanshi(args, &mut displays, &kanshi::config_path()),
+        ["layouts"] => {
+            for layout in LayoutStore::from_env().load()? {
+                println!("{}", layout.key().join(" + "));
+            }
+            Ok(())
+        }
+        _ => Err(usage(args)),
+    }
+}
+
+fn custom_mode(args: &[&str], displays: &mut Displays, store: &CustomModeStore) -> Result<()> {
+    match args {
+        ["list"] => {
+            for (id, modes) in store.load()? {
+                println!("{}: {}", id, modes.join(" "));
+            }
+            Ok(())
+        }
+        ["add", name, mode] => {
+            let display = displays.get_mut(name)?;
+            display.add_custom_mode(mode)?;
+            store.add(&display.id(), mode)
+        }
+        ["remove", name, mode] => store.remove(&displays.get_mut(name)?.id(), mode),
+        _ => Err(usage(args)),
+    }
+}
+
+fn kanshi(args: &[&str], displays: &mut Displays, config: &Path) -> Result<()> {
+    match args {
+        ["list"] => {
+            for profile in kanshi::read(config)? {
+                let name = profile.name.as_deref().unwrap_or("(anonymous)");
+                match profile.assign(displays) {
+                    Some(_) => println!("{} (matches)", name),
+                    None => println!("{}", name),
+                }
+            }
+            Ok(())
+        }
+        ["apply", name] => {
+            let profile = kanshi::read(config)?
+                .into_iter()
+                .find(|p| p.name.as_deref() == Some(*name))
+                .ok_or_else(|| Error::NotFound(format!("kanshi profile {}", name)))?;
+            profile.restore(displays)?;
+            displays.apply_now()
+        }
+        ["export", name] => {
+            print!("{}", KanshiProfile::capture(name, displays).to_config());
+            Ok(())
+        }
+        _ => Err(usage(args)),
+    }
+}
+
+fn power(args: &[&str], sysfs: &SysFs) -> Result<()> {
+    let mut power = Power::load(sysfs.clone())?;
+    match args {
+        [] => {
+            match power.brightness() {
+                Some(percent) => println!("brightness: {}%", percent),
+                None => println!("brightness: no backlight"),
+            }
+            if let Some(saving) = power.conservation_mode() {
+                println!("conservation mode: {}", if saving { "on" } else { "off" });
+            }
+            println!("cpu governor: {}", power.governor());
+            Ok(())
+        }
+        ["brightness", percent] => {
+            power.set_brightness(parse(percent, "brightness")?)?;
+            power.apply()
+        }
+        _ => Err(usage(args)),
+    }
+}
+
+fn appearance(args: &[&str], runner: &Runner) -> Result<()> {
+    let mut appearance = Appearance::load(runner.clone())?;
+    match args {
+        ["themes", options @ ..] => {
+            let toolkit = match options {
+                [] => None,
+                ["--toolkit", id] => {
+                    Some(Toolkit::from_id(id).ok_or_else(|| Error::parse("toolkit", *id))?)
+                }
+                _ => return Err(usage(args)),
+            };
+            appearance.set_toolkit(toolkit);
+            for theme in appearance.themes() {
+                println!("{}\t{}", theme.name, theme.summary());
+            }
+            Ok(())
+        }
+        ["gtk-theme"] => {
+            println!("{}", appearance.theme().unwrap_or(""));
+            Ok(())
+        }
+        ["gtk-theme", theme] => {
+            appearance.select_theme(theme)?;
+            appearance.apply()
+        }
+        ["icon-themes"] => {
+            for theme in appearance.icon_themes() {
+                let kinds = [(theme.has_icons(), "icons"), (theme.cursors, "cursors")];
+                let kinds: Vec<&str> = kinds.iter().filter(|(has, _)| *has).map(|k| k.1).collect();
+                println!("{}\t{}\t{}", theme.name, theme.title, kinds.join(", "));
+            }
+            Ok(())
+        }
+        ["icon-theme"] => {
+            println!("{}", appearance.icon_theme());
+            Ok(())
+        }
+        ["icon-theme", theme] => {
+            appearance.select_icon_theme(theme)?;
+            appearance.apply()
+        }
+        ["cursor"] => {
+            let (theme, size) = appearance.cursor();
+            println!("{} {}", theme, size);
+            Ok(())
+        }
+        ["cursor", theme, size @ ..] => {
+            let size = match size {
+                [] => appearance.cursor().1,
+                [size] => parse(size, "cursor size")?,
+                _ => return Err(usage(args)),
+            };
+            appearance.select_cursor(theme, size)?;
+            appearance.apply()
+        }
+        _ => Err(usage(args)),
+    }
+}
+
+fn network(args: &[&str], runner: &Runner) -> Result<()> {
+    let mut network = Network::new(runner.clone());
+    match args {
+        ["devices"] => {
+            for device in network.device_names()? {
+                println!("{}", device);
+            }
+            Ok(())
+        }
+        ["list"] => {
+            for wifi in network.wifi_list()? {
+                println!("{}", wifi.summary());
+            }
+            Ok(())
+        }
+        ["connect", ssid] => network.connect(ssid),
+        _ => Err(usage(args)),
+    }
+}
+
+fn night_light(args: &[&str], runner: &Runner) -> Result<()> {
+    match args {
+        [] => {
+            let night_light = NightLight::load(runner.clone())?;
+            println!("mode: {}", night_light.config().mode.as_str());
+            println!("temperature: {} K", night_light.temperature()?);
+            Ok(())
+        }
+        ["daemon", options @ ..] => {
+            let interval = match options {
+                [] => NIGHTLIGHT_INTERVAL,
+                ["--interval", secs] => Duration::from_secs(parse(secs, "interval")?),
+                _ => return Err(usage(args)),
+            };
+            Service::new(runner.clone(), Config::path()).run(interval);
+            Ok(())
+        }
+        _ => Err(usage(args)),
+    }
+}
+
+fn tools(args: &[&str], runner: &Runner) -> Result<()> {
+    let tools = Tools::new(runner.clone());
+    match args {
+        ["pick-color"] => tools.pick_pix_color(),
+        ["shotcut"] => {
+            println!("{}", tools.take_shotcut()?);
+            Ok(())
+        }
+        _ => Err(usage(args)),
+    }
+}
+
+fn profile(
+    args: &[&str],
+    runner: &Runner,
+    sysfs: &SysFs,
+    files: &DisplayFiles,
+    store: &ProfileStore,
+) -> Result<()> {
+    match args {
+        ["list"] => {
+            for name in store.list()? {
+                println!("{}", name);
+            }
+            Ok(())
+        }
+        ["save", name] => store.save(name, &capture(runner, sysfs, files)),
+        ["apply", name] => store.load(name)?.apply(runner, sysfs, files),
+        ["diff", name] => {
+            let current = capture(runner, sysfs, files);
+            for change in store.load(name)?.diff(&current) {
+                println!("{}", change);
+            }
+            Ok(())
+        }
+        ["remove", name] => store.remove(name),
+        ["export", name, file] => store.export(name, Path::new(file)),
+        ["import", file] => store.import(Path::new(file), None).map(|_| ()),
+        ["import", file, name] => store.import(Path::new(file), Some(name)).map(|_| ()),
+        _ => Err(usage(args)),
+    }
+}
+
+/// current state for a profile, warning about panels left out
+fn capture(runner: &Runner, sysfs: &SysFs, files: &DisplayFiles) -> Profile {
+    let (profile, skipped) = Profile::capture(runner, sysfs, files);
+    for e in skipped {
+        eprintln!("rsettings: {}", e);
+    }
+    profile
+}
+
+fn usage(args: &[&str]) -> Error {
+    if args.is_empty() {
+        Error::Usage("missing command".to_string())
+    } else {
+        Error::Usage(format!("unknown command: {}", args.join(" ")))
+    }
+}
+
+/// value following `option`
+fn value<'a>(options: &mut Iter<'_, &'a str>, option: &str) -> Result<&'a str> {
+    options
+        .next()
+        .copied()
+        .ok_or_else(|| Error::Usage(format!("{} needs a value", option)))
+}
+
+fn parse<T: FromStr>(value: &str, what: &'static str) -> Result<T> {
+    value.parse().map_err(|_| Error::parse(what, value))
+}
+
+/// `X,Y`
+fn position(value: &str) -> Result<(i32, i32)> {
+    let (x, y) = value
+        .split_once(',')
+        .ok_or_else(|| Error::parse("position", value))?;
+    Ok((parse(x, "position")?, parse(y, "position")?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::runner::FakeRunner;
+    use crate::sysfs::sysfs::fixture_sysfs;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn fake() -> Arc<FakeRunner> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/commands");
+        Arc::new(FakeRunner::from_dir(&dir).unwrap())
+    }
+
+    /// display files of a test, not the user's
+    fn files(name: &str) -> DisplayFiles {
+        let dir = std::env::temp_dir().join(format!("rsettings-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        DisplayFiles::in_dir(&dir)
+    }
+
+    #[test]
+    fn display_set_applies_only_that_output() {
+        let fake = fake();
+        let args = [
+            "display",
+            "set",
+            "HDMI-A-1",
+            "--mode",
+            "1920x1080@60",
+            "--scale",
+            "1.25",
+            "--pos",
+            "0,0",
+        ];
+        let files = files("cli-display-set");
+        dispatch(&args, &Runner::new(fake.clone()), &SysFs::default(), &files).unwrap();
+        assert_eq!(
+            fake.calls(),
+            vec![
+                "wlr-randr --json",
+                "wlr-randr",
+                "wlr-randr --output HDMI-A-1 --mode 1920x1080@60 --scale 1.25 --transform normal --on --pos 0,0"
+            ]
+        );
+    }
+
+    #[test]
+    fn power_brightness_writes_sysfs() {
+        let sysfs = fixture_sysfs("cli_brightness");
+        let files = files("cli-brightness-files");
+        dispatch(
+            &["power", "brightness", "40"],
+            &Runner::default(),
+            &sysfs,
+            &files,
+        )
+        .unwrap();
+        let brightness = sysfs
+            .read_to_string("/sys/class/backlight/intel_backlight/brightness")
+            .unwrap();
+        assert_eq!(brightness, "38400");
+        for percent in ["0", "250"] {
+            let args = ["power", "brightness", percent];
+            assert!(matches!(
+                dispatch(&args, &Runner::default(), &sysfs, &files),
+                Err(Error::Usage(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn network_connect_runs_nmcli() {
+        let fake = fake();
+        dispatch(
+            &["network", "connect", "Office"],
+            &Runner::new(fake.clone()),
+            &SysFs::default(),
+            &files("cli-network"),
+        )
+        .unwrap();
+        assert_eq!(fake.calls(), vec!["nmcli dev wifi connect Office"]);
+    }
+
+    #[test]
+    fn bad_arguments_are_usage_errors() {
+        let runner = Runner::new(fake());
+        let sysfs = SysFs::default();
+        let files = files("cli-bad-arguments");
+        assert!(matches!(
+            dispatch(&["nope"], &runner, &sysfs, &files),
+            Err(Error::Usage(_))
+        ));
+        assert!(matches!(
+            dispatch(
+                &["display", "set", "eDP-1", "--scale"],
+                &runner,
+                &sysfs,
+                &files
+            ),
+            Err(Error::Usage(_))
+        ));
+        assert!(matches!(
+            dispatch(
+                &["display", "power", "eDP-1", "dim"],
+                &runner,
+                &sysfs,
+                &files
+            ),
+            Err(Error::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn profile_save_then_apply_restores_displays() {
+        let fake = fake();
+        let runner = Runner::new(fake.clone());
+        let sysfs = fixture_sysfs("cli_profile");
+        let dir =
+            std::env::temp_dir().join(format!("rsettings-{}-cli-profiles", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = ProfileStore::new(dir);
+        let files = files("cli-profile-files");
+        profile(&["save", "docked"], &runner, &sysfs, &files, &store).unwrap();
+        assert_eq!(store.list().unwrap(), vec!["docked"]);
+        profile(&["apply", "docked"], &runner, &sysfs, &files, &store).unwrap();
+        assert!(fake
+            .calls()
+            .contains(&"wlr-randr --output eDP-1 --mode 1920x1080@60.02 --scale 1.25 --transform normal --on --pos 0,0".to_string()));
+    }
+}
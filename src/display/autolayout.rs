@@ -0,0 +1,336 @@
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::backend::Backend;
+use super::custom_mode::CustomModeStore;
+use super::display::{DisplayState, Displays};
+use super::hotplug;
+use crate::error::error::{Error, Result};
+use crate::settings::settings::{config_home, Settings};
+use crate::sysfs::sysfs::SysFs;
+
+/// Settings of one monitor in a saved layout
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedOutput {
+    /// monitor id, see [`super::display::Display::id`]
+    pub id: String,
+    pub state: DisplayState,
+}
+
+/// Layout of one set of monitors
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SavedLayout {
+    pub outputs: Vec<SavedOutput>,
+}
+
+impl SavedLayout {
+    pub fn capture(displays: &Displays) -> Self {
+        let outputs = displays
+            .iter()
+            .map(|d| SavedOutput {
+                id: d.id(),
+                state: d.state(),
+            })
+            .collect();
+        Self { outputs }
+    }
+
+    /// sorted monitor ids, the layout is used when exactly these are connected
+    pub fn key(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.outputs.iter().map(|o| o.id.clone()).collect();
+        ids.sort();
+        ids
+    }
+}
+
+/// sorted ids of the connected monitors
+pub fn key(displays: &Displays) -> Vec<String> {
+    let mut ids: Vec<String> = displays.iter().map(|d| d.id()).collect();
+    ids.sort();
+    ids
+}
+
+/// key of layouts saved before monitors were told apart by EDID serial
+fn legacy_key(displays: &Displays) -> Vec<String> {
+    let mut ids: Vec<String> = displays.iter().map(|d| d.monitor()).collect();
+    ids.sort();
+    ids
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct LayoutsFile {
+    #[serde(default, rename = "layout")]
+    layouts: Vec<SavedLayout>,
+}
+
+/// Saved layouts, one toml file
+pub struct LayoutStore {
+    path: PathBuf,
+}
+
+impl LayoutStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// `$XDG_CONFIG_HOME/rsettings/layouts.toml`, default `~/.config/rsettings/layouts.toml`
+    pub fn from_env() -> Self {
+        Self::new(config_home().join("rsettings/layouts.toml"))
+    }
+
+    /// all saved layouts, none if the file doesn't exist yet
+    pub fn load(&self) -> Result<Vec<SavedLayout>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let s = fs::read_to_string(&self.path).map_err(|source| Error::File {
+            path: self.path.clone(),
+            source,
+        })?;
+        let file: LayoutsFile =
+            toml::from_str(&s).map_err(|e| Error::parse("layouts", e.to_string()))?;
+        Ok(file.layouts)
+    }
+
+    fn save(&self, layouts: Vec<SavedLayout>) -> Result<()> {
+        let s = toml::to_string(&LayoutsFile { layouts })
+            .map_err(|e| Error::parse("layouts", e.to_string()))?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|source| Error::File {
+                path: dir.to_path_buf(),
+                source,
+            })?;
+        }
+        fs::write(&self.path, s).map_err(|source| Error::File {
+            path: self.path.clone(),
+            source,
+        })
+    }
+
+    /// Save the layout of `displays`, replacing the one of the same monitors.
+    pub fn remember(&self, displays: &Displays) -> Result<()> {
+        let layout = SavedLayout::capture(displays);
+        let mut layouts = self.load()?;
+        layouts.retain(|l| l.key() != layout.key());
+        layouts.push(layout);
+        self.save(layouts)
+    }
+
+    /// Layout saved for the monitors of `displays`.
+    ///
+    /// One saved under the [`Display::monitor`] names of before is moved
+    /// to the ids of now.
+    ///
+    /// [`Display::monitor`]: super::display::Display::monitor
+    pub fn find(&self, displays: &Displays) -> Result<Option<SavedLayout>> {
+        let mut layouts = self.load()?;
+        let key = key(displays);
+        if let Some(layout) = layouts.iter().find(|l| l.key() == key) {
+            return Ok(Some(layout.clone()));
+        }
+        let legacy = legacy_key(displays);
+        let layout = match layouts.iter_mut().find(|l| l.key() == legacy) {
+            Some(layout) => layout,
+            None => return Ok(None),
+        };
+        // identical monitors each take one id
+        let mut unused: Vec<_> = displays.iter().collect();
+        for output in &mut layout.outputs {
+            if let Some(i) = unused.iter().position(|d| d.monitor() == output.id) {
+                output.id = unused.remove(i).id();
+            }
+        }
+        let layout = layout.clone();
+        self.save(layouts)?;
+        Ok(Some(layout))
+    }
+}
+
+/// Applies the saved layout whenever the set of connected monitors changes
+pub struct Watcher {
+    backend: Backend,
+    store: LayoutStore,
+    /// monitors seen last time
+    last: Option<Vec<String>>,
+    /// where to find EDIDs, for monitor ids by serial
+    sysfs: Option<SysFs>,
+    /// custom modes saved layouts may use
+    custom_modes: Option<CustomModeStore>,
+}
+
+impl Watcher {
+    pub fn new(backend: Backend, store: LayoutStore) -> Self {
+        Self {
+            backend,
+            store,
+            last: None,
+            sysfs: None,
+            custom_modes: None,
+        }
+    }
+
+    /// tell identical monitors apart by the serial in their EDID
+    pub fn with_sysfs(mut self, sysfs: SysFs) -> Self {
+        self.sysfs = Some(sysfs);
+        self
+    }
+
+    /// offer the custom modes of `store` to saved layouts
+    pub fn with_custom_modes(mut self, store: CustomModeStore) -> Self {
+        self.custom_modes = Some(store);
+        self
+    }
+
+    /// Look at the monitors once, `true` if a saved layout was applied.
+    pub fn step(&mut self) -> Result<bool> {
+        // 1. monitors connected now
+        let mut displays = Displays::with_backend(self.backend.clone());
+        if let Some(sysfs) = &self.sysfs {
+            displays = displays.with_sysfs(sysfs.clone());
+        }
+        if let Some(store) = &self.custom_modes {
+            displays = displays.with_custom_modes(store.clone());
+        }
+        displays.init()?;
+        let key = key(&displays);
+        if self.last.as_ref() == Some(&key) {
+            return Ok(false);
+        }
+        self.last = Some(key);
+
+        // 2. their saved layout, if any
+        match self.store.find(&displays)? {
+            Some(layout) => {
+                displays.restore_layout(&layout)?;
+                displays.apply_now()?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Check whenever the outputs change, forever; without word from the
+    /// compositor or the kernel, and after failures, every `interval`.
+    pub fn run(&mut self, interval: Duration) {
+        let sysfs = self.sysfs.clone().unwrap_or_default();
+        let changes = hotplug::watch(self.backend.clone(), sysfs, interval, || {});
+        loop {
+            // 1. apply the layout of the monitors now
+            let failed = match self.step() {
+                Ok(true) => {
+                    println!("applied layout for {}", self.last_monitors());
+                    false
+                }
+                Ok(false) => false,
+                Err(e) => {
+                    eprintln!("rsettings: {}", e);
+                    // look again next time
+                    self.last = None;
+                    true
+                }
+            };
+
+            // 2. wait for a change, a burst of them counts once
+            if failed || changes.recv().is_err() {
+                thread::sleep(interval);
+            }
+            while changes.try_recv().is_ok() {}
+        }
+    }
+
+    fn last_monitors(&self) -> String {
+        self.last.as_deref().unwrap_or_default().join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::wlr_randr::WlrRandr;
+    use crate::runner::runner::{FakeRunner, Runner};
+    use crate::sysfs::sysfs::fixture_sysfs;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn fake() -> Arc<FakeRunner> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/commands");
+        Arc::new(FakeRunner::from_dir(&dir).unwrap())
+    }
+
+    fn temp_store(name: &str) -> LayoutStore {
+        let path = std::env::temp_dir()
+            .join(format!("rsettings-{}-{}", std::process::id(), name))
+            .join("layouts.toml");
+        let _ = fs::remove_file(&path);
+        LayoutStore::new(path)
+    }
+
+    #[test]
+    fn remember_replaces_layout_of_same_monitors() {
+        let store = temp_store("layouts_remember");
+        let backend = Backend::new(WlrRandr::new(Runner::new(fake())));
+        let mut displays = Displays::with_backend(backend);
+        displays.init().unwrap();
+        assert!(store.find(&displays).unwrap().is_none());
+        store.remember(&displays).unwrap();
+        displays.get_mut("eDP-1").unwrap().set_scale(1.0);
+        store.remember(&displays).unwrap();
+        let layouts = store.load().unwrap();
+        assert_eq!(layouts.len(), 1);
+        assert_eq!(
+            layouts[0].key(),
+            vec!["Dell Inc. DELL U2720Q 8K2JR83", "Sharp Corporation 0x1453"]
+        );
+        assert_eq!(
+            store.find(&displays).unwrap().unwrap().outputs[1]
+                .state
+                .scale,
+            1.0
+        );
+    }
+
+    #[test]
+    fn layout_saved_by_monitor_name_moves_to_edid_ids() {
+        let store = temp_store("layouts_legacy");
+        let backend = Backend::new(WlrRandr::new(Runner::new(fake())));
+        // no EDID, ids are the monitor names as before
+        let mut displays = Displays::with_backend(backend.clone());
+        displays.init().unwrap();
+        store.remember(&displays).unwrap();
+
+        let mut displays =
+            Displays::with_backend(backend).with_sysfs(fixture_sysfs("display_edid"));
+        displays.init().unwrap();
+        let layout = store.find(&displays).unwrap().unwrap();
+        assert_eq!(layout.key(), key(&displays));
+        assert_eq!(store.load().unwrap(), vec![layout]);
+    }
+
+    #[test]
+    fn watcher_applies_saved_layout_once_per_change() {
+        let store = temp_store("layouts_watch");
+        let fake = fake();
+        let backend = Backend::new(WlrRandr::new(Runner::new(fake.clone())));
+        let mut displays = Displays::with_backend(backend.clone());
+        displays.init().unwrap();
+        // saved while the monitor was on another connector
+        let mut layout = SavedLayout::capture(&displays);
+        for output in &mut layout.outputs {
+            output.state.name = format!("old-{}", output.state.name);
+        }
+        layout.outputs[0].state.scale = 1.5;
+        store.save(vec![layout]).unwrap();
+
+        let mut watcher = Watcher::new(backend, store);
+        assert!(watcher.step().unwrap());
+        assert!(fake.calls().contains(
+            &"wlr-randr --output HDMI-A-1 --mode 3840x2160@60 --scale 1.5 --transform normal --on --pos 1536,0"
+                .to_string()
+        ));
+        assert!(!watcher.step().unwrap());
+    }
+}
@@ -0,0 +1,95 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
+use super::display::{Display, DisplayState};
+use super::hyprland::{Hyprland, HYPRLAND_ENV};
+use super::sway::{Sway, SWAYSOCK_ENV};
+use super::wlr_output::WlrOutput;
+use super::wlr_randr::WlrRandr;
+use crate::error::error::{Error, Result};
+use crate::runner::runner::Runner;
+
+/// Env var forcing the display backend, `wayland`, `wlr-randr`, `sway` or `hyprland`
+pub const DISPLAY_BACKEND_ENV: &str = "RSETTINGS_DISPLAY_BACKEND";
+
+/// Reads and changes the output configuration of the compositor
+pub trait DisplayBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// connected outputs with their modes and current settings
+    fn outputs(&self) -> Result<Vec<Display>>;
+    /// check that the compositor accepts `states`, without changing anything
+    fn test(&self, states: &[DisplayState]) -> Result<()>;
+    /// apply `states`, outputs not listed keep their settings
+    fn apply(&self, states: &[DisplayState]) -> Result<()>;
+    /// switch the picture of output `name` off or on at once, it stays
+    /// enabled and keeps its place in the layout
+    fn set_power(&self, name: &str, _on: bool) -> Result<()> {
+        Err(Error::NotFound(format!(
+            "power control of {} in {}",
+            name,
+            self.name()
+        )))
+    }
+    /// Block, calling `changed` whenever the outputs change, until it
+    /// returns `false`; fails at once if the backend can't tell.
+    fn watch(&self, _changed: &mut dyn FnMut() -> bool) -> Result<()> {
+        Err(Error::NotFound(format!(
+            "output changes from {}",
+            self.name()
+        )))
+    }
+}
+
+/// Shared handle to the `DisplayBackend` of all displays
+#[derive(Clone)]
+pub struct Backend(Arc<dyn DisplayBackend>);
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::new(WlrRandr::new(Runner::default()))
+    }
+}
+
+impl Deref for Backend {
+    type Target = dyn DisplayBackend;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl Backend {
+    pub fn new<B: DisplayBackend + 'static>(backend: B) -> Self {
+        Self(Arc::new(backend))
+    }
+
+    /// The IPC of sway or Hyprland when running in them, else the wayland
+    /// protocol if the compositor supports it, else `wlr-randr`.
+    ///
+    /// Fake runners always get `wlr-randr`, so fixtures stay in charge.
+    pub fn detect(runner: Runner) -> Self {
+        match std::env::var(DISPLAY_BACKEND_ENV).as_deref() {
+            Ok("wayland") => return Self::new(WlrOutput::default().with_runner(runner)),
+            Ok("wlr-randr") => return Self::new(WlrRandr::new(runner)),
+            Ok("sway") => return Self::new(Sway::new(runner)),
+            Ok("hyprland") => return Self::new(Hyprland::new(runner)),
+            _ => {}
+        }
+        if runner.is_fake() {
+            return Self::new(WlrRandr::new(runner));
+        }
+        let running = |env: &str| std::env::var_os(env).is_some_and(|v| !v.is_empty());
+        if running(SWAYSOCK_ENV) {
+            return Self::new(Sway::new(runner));
+        }
+        if running(HYPRLAND_ENV) {
+            return Self::new(Hyprland::new(runner));
+        }
+        let native = WlrOutput::default();
+        if native.available() {
+            Self::new(native.with_runner(runner))
+        } else {
+            Self::new(WlrRandr::new(runner))
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+
+use eframe::egui::{Align2, Color32, FontId, Id, Pos2, Rect, Sense, Stroke, Ui, Vec2};
+
+use super::display::Display;
+
+/// screen pixels within which a dropped display snaps to an edge
+const SNAP_DISTANCE: f64 = 12.0;
+const CANVAS_HEIGHT: f32 = 200.0;
+const MARGIN: f32 = 10.0;
+
+/// position and size in logical pixels
+type Area = ((f64, f64), (f64, f64));
+
+/// Display being dragged on the canvas
+pub(super) struct Drag {
+    name: String,
+    /// logical position, not yet written back
+    pos: (f64, f64),
+    view: View,
+}
+
+/// mapping of logical pixels to the canvas, kept while dragging
+#[derive(Clone, Copy)]
+struct View {
+    origin: (f64, f64),
+    factor: f64,
+    offset: Pos2,
+}
+
+impl View {
+    /// fit all `areas` into `canvas`
+    fn fit(areas: &[(String, Area)], canvas: Rect) -> Self {
+        let (mut min, mut max) = ((f64::MAX, f64::MAX), (f64::MIN, f64::MIN));
+        for (_, ((x, y), (w, h))) in areas {
+            min = (min.0.min(*x), min.1.min(*y));
+            max = (max.0.max(x + w), max.1.max(y + h));
+        }
+        let size = ((max.0 - min.0).max(1.0), (max.1 - min.1).max(1.0));
+        let inner = canvas.shrink(MARGIN);
+        let factor = (inner.width() as f64 / size.0).min(inner.height() as f64 / size.1);
+        let used = Vec2::new((size.0 * factor) as f32, (size.1 * factor) as f32);
+        Self {
+            origin: min,
+            factor,
+            offset: inner.center() - used / 2.0,
+        }
+    }
+
+    fn to_screen(self, ((x, y), (w, h)): Area) -> Rect {
+        let min = self.offset
+            + Vec2::new(
+                ((x - self.origin.0) * self.factor) as f32,
+                ((y - self.origin.1) * self.factor) as f32,
+            );
+        Rect::from_min_size(
+            min,
+            Vec2::new((w * self.factor) as f32, (h * self.factor) as f32),
+        )
+    }
+}
+
+/// Draw the enabled displays to scale, drag them to move and click to select.
+pub(super) fn arrange(
+    ui: &mut Ui,
+    displays: &mut BTreeMap<String, Display>,
+    selected: &mut String,
+    drag: &mut Option<Drag>,
+) {
+    let width = ui.available_width().max(200.0);
+    let (response, painter) = ui.allocate_painter(Vec2::new(width, CANVAS_HEIGHT), Sense::hover());
+    let canvas = response.rect;
+    painter.rect_filled(canvas, 4.0, Color32::from_gray(220));
+
+    // 1. logical areas of enabled displays
+    let areas = areas(displays);
+    if areas.is_empty() {
+        painter.text(
+            canvas.center(),
+            Align2::CENTER_CENTER,
+            "no enabled display",
+            FontId::proportional(12.0),
+            Color32::GRAY,
+        );
+        return;
+    }
+    let view = drag
+        .as_ref()
+        .map_or_else(|| View::fit(&areas, canvas), |d| d.view);
+
+    // 2. draw and drag
+    for (name, (pos, size)) in &areas {
+        let pos = match drag {
+            Some(d) if &d.name == name => d.pos,
+            _ => *pos,
+        };
+        let rect = view.to_screen((pos, *size));
+        let response = ui.interact(
+            rect,
+            Id::new(("display_canvas", name)),
+            Sense::click_and_drag(),
+        );
+        if response.clicked() || response.drag_started() {
+            *selected = name.clone();
+        }
+        if response.drag_started() {
+            *drag = Some(Drag {
+                name: name.clone(),
+                pos,
+                view,
+            });
+        }
+        if let Some(d) = drag.as_mut().filter(|d| &d.name == name) {
+            let delta = response.drag_delta();
+            d.pos.0 += delta.x as f64 / view.factor;
+            d.pos.1 += delta.y as f64 / view.factor;
+        }
+        if response.drag_released() {
+            if let Some(d) = drag.take() {
+                let others: Vec<Area> = areas
+                    .iter()
+                    .filter(|(other, _)| other != name)
+                    .map(|(_, area)| *area)
+                    .collect();
+                let pos = snap((d.pos, *size), &others, SNAP_DISTANCE / view.factor);
+                place(displays, name, pos);
+            }
+        }
+
+        let fill = if name == selected {
+            Color32::from_rgb(140, 180, 230)
+        } else {
+            Color32::from_gray(180)
+        };
+        painter.rect_filled(rect, 2.0, fill);
+        painter.rect_stroke(rect, 2.0, Stroke::new(1.0, Color32::DARK_GRAY));
+        painter.text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            name,
+            FontId::proportional(10.0),
+            Color32::BLACK,
+        );
+    }
+}
+
+fn areas(displays: &BTreeMap<String, Display>) -> Vec<(String, Area)> {
+    displays
+        .values()
+        .filter(|d| d.enable)
+        .map(|d| {
+            let pos = (d.position.0 as f64, d.position.1 as f64);
+            (d.name.clone(), (pos, d.logical_size()))
+        })
+        .collect()
+}
+
+/// Move `area` onto the closest edges of `others` within `distance`,
+/// each axis on its own.
+fn snap(((x, y), (w, h)): Area, others: &[Area], distance: f64) -> (f64, f64) {
+    let closest = |now: f64, candidates: Vec<f64>| {
+        candidates
+            .into_iter()
+            .filter(|c| (c - now).abs() <= distance)
+            .min_by(|a, b| (a - now).abs().total_cmp(&(b - now).abs()))
+            .unwrap_or(now)
+    };
+    let mut xs = Vec::new();
+    let mut ys = Vec::new();
+    for ((ox, oy), (ow, oh)) in others {
+        // next to each other, or aligned
+        xs.extend([ox + ow, ox - w, *ox, ox + ow - w]);
+        ys.extend([oy + oh, oy - h, *oy, oy + oh - h]);
+    }
+    (closest(x, xs), closest(y, ys))
+}
+
+/// Set display `name` to `pos` and shift all so the layout starts at 0,0.
+fn place(displays: &mut BTreeMap<String, Display>, name: &str, pos: (f64, f64)) {
+    let positions: Vec<(String, (f64, f64))> = areas(displays)
+        .into_iter()
+        .map(|(n, (p, _))| if n == name { (n, pos) } else { (n, p) })
+        .collect();
+    let min_x = positions.iter().map(|(_, p)| p.0).fold(f64::MAX, f64::min);
+    let min_y = positions.iter().map(|(_, p)| p.1).fold(f64::MAX, f64::min);
+    for (n, (x, y)) in positions {
+        if let Some(display) = displays.get_mut(&n) {
+            display.position = ((x - min_x).round() as i32, (y - min_y).round() as i32);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snap_to_nearby_edges_only() {
+        let laptop = ((0.0, 0.0), (1536.0, 864.0));
+        let dropped = ((1550.0, 12.0), (1920.0, 1080.0));
+        assert_eq!(snap(dropped, &[laptop], 20.0), (1536.0, 0.0));
+        let left = ((-1900.0, 700.0), (1920.0, 1080.0));
+        assert_eq!(snap(left, &[laptop], 30.0), (-1920.0, 700.0));
+        let far = ((3000.0, 500.0), (1920.0, 1080.0));
+        assert_eq!(snap(far, &[laptop], 20.0), (3000.0, 500.0));
+    }
+
+    #[test]
+    fn place_keeps_layout_at_origin() {
+        let mut displays = BTreeMap::new();
+        for (name, position) in [("eDP-1", (0, 0)), ("HDMI-A-1", (1536, 0))] {
+            let display = Display {
+                name: name.to_string(),
+                enable: true,
+                position,
+                ..Default::default()
+            };
+            displays.insert(name.to_string(), display);
+        }
+        place(&mut displays, "HDMI-A-1", (-1920.0, -200.4));
+        assert_eq!(displays["eDP-1"].position, (1920, 200));
+        assert_eq!(displays["HDMI-A-1"].position, (0, 0));
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use super::cvt::Timing;
+use crate::error::error::{Error, Result};
+use crate::settings::settings::config_home;
+
+/// Custom mode being edited in the display panel
+#[derive(Clone)]
+pub(super) struct CustomMode {
+    pub(super) width: u32,
+    pub(super) height: u32,
+    pub(super) refresh: f64,
+    pub(super) reduced: bool,
+    /// "Save for this monitor" was clicked, handled by `Displays`
+    pub(super) save: bool,
+}
+
+impl Default for CustomMode {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            refresh: 60.0,
+            reduced: true,
+            save: false,
+        }
+    }
+}
+
+impl CustomMode {
+    pub(super) fn timing(&self) -> Option<Timing> {
+        Timing::cvt(self.width, self.height, self.refresh, self.reduced)
+    }
+}
+
+/// Custom modes saved per monitor id, one toml file
+#[derive(Debug, Clone)]
+pub struct CustomModeStore {
+    path: PathBuf,
+}
+
+impl CustomModeStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// `$XDG_CONFIG_HOME/rsettings/custom-modes.toml`, default `~/.config/rsettings/custom-modes.toml`
+    pub fn from_env() -> Self {
+        Self::new(config_home().join("rsettings/custom-modes.toml"))
+    }
+
+    /// `WxH@HZ` modes by monitor id, none if the file doesn't exist yet
+    pub fn load(&self) -> Result<BTreeMap<String, Vec<String>>> {
+        if !self.path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let s = fs::read_to_string(&self.path).map_err(|source| Error::File {
+            path: self.path.clone(),
+            source,
+        })?;
+        toml::from_str(&s).map_err(|e| Error::parse("custom modes", e.to_string()))
+    }
+
+    fn save(&self, modes: &BTreeMap<String, Vec<String>>) -> Result<()> {
+        let s = toml::to_string(modes).map_err(|e| Error::parse("custom modes", e.to_string()))?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|source| Error::File {
+                path: dir.to_path_buf(),
+                source,
+            })?;
+        }
+        fs::write(&self.path, s).map_err(|source| Error::File {
+            path: self.path.clone(),
+            source,
+        })
+    }
+
+    pub fn add(&self, id: &str, mode: &str) -> Result<()> {
+        let mut modes = self.load()?;
+        let list = modes.entry(id.to_string()).or_default();
+        if !list.iter().any(|m| m == mode) {
+            list.push(mode.to_string());
+        }
+        self.save(&modes)
+    }
+
+    pub fn remove(&self, id: &str, mode: &str) -> Result<()> {
+        let mut modes = self.load()?;
+        if let Some(list) = modes.get_mut(id) {
+            list.retain(|m| m != mode);
+            if list.is_empty() {
+                modes.remove(id);
+            }
+        }
+        self.save(&modes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_adds_once_and_removes() {
+        let path = std::env::temp_dir()
+            .join(format!("rsettings-{}-custom-modes", std::process::id()))
+            .join("custom-modes.toml");
+        let _ = fs::remove_file(&path);
+        let store = CustomModeStore::new(path);
+        let dell = "Dell Inc. DELL U2720Q 8K2JR83";
+        store.add(dell, "2560x1080@74.991").unwrap();
+        store.add(dell, "2560x1080@74.991").unwrap();
+        store.add(dell, "3840x1600@60").unwrap();
+        assert_eq!(
+            store.load().unwrap()[dell],
+            vec!["2560x1080@74.991", "3840x1600@60"]
+        );
+        store.remove(dell, "2560x1080@74.991").unwrap();
+        store.remove(dell, "3840x1600@60").unwrap();
+        assert!(store.load().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+//! VESA Coordinated Video Timings, computed the way the xorg `cvt` tool does.
+
+use std::fmt;
+
+/// pixel clock step, kHz
+const CLOCK_STEP: u32 = 250;
+/// horizontal timings are multiples of this
+const CELL_GRAN: u32 = 8;
+/// lines of the vertical front porch
+const MIN_V_PORCH: u32 = 3;
+/// lines of the vertical back porch
+const MIN_V_BPORCH: u32 = 6;
+/// time of vertical sync and back porch, µs
+const MIN_VSYNC_BP: f64 = 550.0;
+/// horizontal sync in percent of the line
+const HSYNC_PERCENTAGE: u32 = 8;
+/// blanking formula gradients
+const C_PRIME: f64 = 30.0;
+const M_PRIME: f64 = 300.0;
+
+/// reduced blanking: minimum vertical blanking, µs
+const RB_MIN_VBLANK: f64 = 460.0;
+const RB_H_SYNC: u32 = 32;
+const RB_H_BLANK: u32 = 160;
+const RB_V_FPORCH: u32 = 3;
+
+/// Timings of one video mode, in pixels and lines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timing {
+    /// kHz
+    pub clock: u32,
+    pub h_display: u32,
+    pub h_sync_start: u32,
+    pub h_sync_end: u32,
+    pub h_total: u32,
+    pub v_display: u32,
+    pub v_sync_start: u32,
+    pub v_sync_end: u32,
+    pub v_total: u32,
+    /// refresh asked for, Hz
+    pub target: f64,
+    /// reduced blanking, for digital displays that don't need long blanks
+    pub reduced: bool,
+}
+
+impl Timing {
+    /// Timing of `width`x`height` at `refresh` Hz, `None` for impossible modes.
+    pub fn cvt(width: u32, height: u32, refresh: f64, reduced: bool) -> Option<Self> {
+        if width < CELL_GRAN || height < 1 || refresh <= 0.0 {
+            return None;
+        }
+        let h_display = width - width % CELL_GRAN;
+        let v_sync = v_sync(width, height);
+        let field = 1_000_000.0 / refresh;
+
+        let timing = if reduced {
+            // 1. reduced blanking: fixed horizontal blank
+            let h_period = (field - RB_MIN_VBLANK) / height as f64;
+            if h_period <= 0.0 {
+                return None;
+            }
+            let vbi =
+                ((RB_MIN_VBLANK / h_period) as u32 + 1).max(RB_V_FPORCH + v_sync + MIN_V_BPORCH);
+            let v_total = height + vbi;
+            let h_total = h_display + RB_H_BLANK;
+            let clock = (refresh * v_total as f64 * h_total as f64 / 1000.0) as u32;
+            let h_sync_end = h_display + RB_H_BLANK / 2;
+            Self {
+                clock: clock - clock % CLOCK_STEP,
+                h_display,
+                h_sync_start: h_sync_end - RB_H_SYNC,
+                h_sync_end,
+                h_total,
+                v_display: height,
+                v_sync_start: height + RB_V_FPORCH,
+                v_sync_end: height + RB_V_FPORCH + v_sync,
+                v_total,
+                target: refresh,
+                reduced,
+            }
+        } else {
+            // 2. standard: blanking grows with the line frequency
+            let h_period = (field - MIN_VSYNC_BP) / (height + MIN_V_PORCH) as f64;
+            if h_period <= 0.0 {
+                return None;
+            }
+            let sync_bp = ((MIN_VSYNC_BP / h_period) as u32 + 1).max(v_sync + MIN_V_BPORCH);
+            let v_total = height + sync_bp + MIN_V_PORCH;
+            let duty = (C_PRIME - M_PRIME * h_period / 1000.0).max(20.0);
+            let h_blank = (h_display as f64 * duty / (100.0 - duty)) as u32;
+            let h_blank = h_blank - h_blank % (2 * CELL_GRAN);
+            let h_total = h_display + h_blank;
+            let clock = (h_total as f64 * 1000.0 / h_period) as u32;
+            let h_sync = h_total * HSYNC_PERCENTAGE / 100;
+            let h_sync = h_sync - h_sync % CELL_GRAN;
+            let h_sync_end = h_display + h_blank / 2;
+            Self {
+                clock: clock - clock % CLOCK_STEP,
+                h_display,
+                h_sync_start: h_sync_end - h_sync,
+                h_sync_end,
+                h_total,
+                v_display: height,
+                v_sync_start: height + MIN_V_PORCH,
+                v_sync_end: height + MIN_V_PORCH + v_sync,
+                v_total,
+                target: refresh,
+                reduced,
+            }
+        };
+        Some(timing)
+    }
+
+    /// refresh the rounded pixel clock gives, Hz
+    pub fn refresh(&self) -> f64 {
+        self.clock as f64 * 1000.0 / (self.h_total as f64 * self.v_total as f64)
+    }
+
+    /// `WxH@HZ` as `--custom-mode` takes it
+    pub fn mode(&self) -> String {
+        format!(
+            "{}x{}@{}",
+            self.h_display,
+            self.v_display,
+            (self.refresh() * 1000.0).round() / 1000.0
+        )
+    }
+}
+
+impl fmt::Display for Timing {
+    /// xorg modeline
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (h, v) = if self.reduced {
+            ("+hsync", "-vsync")
+        } else {
+            ("-hsync", "+vsync")
+        };
+        let name = if self.reduced {
+            format!("{}x{}R", self.h_display, self.v_display)
+        } else {
+            format!("{}x{}_{:.2}", self.h_display, self.v_display, self.target)
+        };
+        write!(
+            f,
+            "Modeline \"{}\" {:.2} {} {} {} {} {} {} {} {} {} {}",
+            name,
+            self.clock as f64 / 1000.0,
+            self.h_display,
+            self.h_sync_start,
+            self.h_sync_end,
+            self.h_total,
+            self.v_display,
+            self.v_sync_start,
+            self.v_sync_end,
+            self.v_total,
+            h,
+            v
+        )
+    }
+}
+
+/// vertical sync lines, telling the aspect ratio to the monitor
+fn v_sync(width: u32, height: u32) -> u32 {
+    let is = |w: u32, h: u32| height.is_multiple_of(h) && height * w / h == width;
+    if is(4, 3) {
+        4
+    } else if is(16, 9) {
+        5
+    } else if is(16, 10) {
+        6
+    } else if is(5, 4) || is(15, 9) {
+        7
+    } else {
+        10
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_xorg_cvt() {
+        let standard = Timing::cvt(1920, 1080, 60.0, false).unwrap();
+        assert_eq!(
+            standard.to_string(),
+            "Modeline \"1920x1080_60.00\" 173.00 1920 2048 2248 2576 1080 1083 1088 1120 -hsync +vsync"
+        );
+        let reduced = Timing::cvt(1920, 1080, 60.0, true).unwrap();
+        assert_eq!(
+            reduced.to_string(),
+            "Modeline \"1920x1080R\" 138.50 1920 1968 2000 2080 1080 1083 1088 1111 +hsync -vsync"
+        );
+        assert_eq!(reduced.mode(), "1920x1080@59.934");
+    }
+
+    #[test]
+    fn ultrawide_and_bad_input() {
+        let timing = Timing::cvt(2560, 1080, 75.0, true).unwrap();
+        assert_eq!((timing.h_total, timing.v_total), (2720, 1119));
+        assert!((timing.refresh() - 75.0).abs() < 0.1);
+        assert!(Timing::cvt(1920, 1080, 0.0, false).is_none());
+        assert!(Timing::cvt(0, 1080, 60.0, true).is_none());
+    }
+}
@@ -1,45 +1,186 @@
+use super::autolayout::{LayoutStore, SavedLayout, SavedOutput};
+use super::backend::Backend;
+use super::canvas::{self, Drag};
+use super::custom_mode::{CustomMode, CustomModeStore};
+use super::edid::{self, Edid};
+use super::hotplug;
+use super::identify::{self, Label};
+use super::kanshi::{self, KanshiProfile};
+use super::layout::{self, Severity};
+use super::wayfire::{self, Ini, WayfireOutput};
+use crate::error::error::{Error, Result};
+use crate::runner::runner::Runner;
 use crate::settings::settings::Settings;
+use crate::sysfs::sysfs::SysFs;
 
-use eframe::egui::{self, ComboBox, Grid, Ui};
-use regex::Regex;
-use std::collections::BTreeMap;
-use std::process::Command;
-use std::str::Lines;
+use eframe::egui::{self, Align2, Color32, ComboBox, Grid, Ui};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// time to confirm new display settings before the old ones come back
+pub const REVERT_TIMEOUT: Duration = Duration::from_secs(15);
+/// text size of the preview at scale 1, pixels
+const TEXT_PREVIEW_SIZE: f32 = 14.0;
 
 /// Display for display
 #[derive(Default)]
 pub struct Displays {
     displays: BTreeMap<String, Display>,
     now: String,
+    /// settings on screen, confirmed by the user
+    applied: Vec<DisplayState>,
+    pending: Option<Pending>,
+    /// display dragged on the canvas
+    drag: Option<Drag>,
+    /// where to find the EDID of the monitors
+    sysfs: Option<SysFs>,
+    /// modes saved by "Save for this monitor"
+    custom_modes: Option<CustomModeStore>,
+    /// displays ticked for mirroring
+    mirror_select: BTreeSet<String>,
+    /// settings before mirroring, brought back by [`Displays::extend`]
+    extended: Option<Vec<DisplayState>>,
+    /// `[output:NAME]` sections of wayfire.ini, restored on next login
+    saved: BTreeMap<String, WayfireOutput>,
+    /// wayfire.ini of `saved`, [`wayfire::config_path`] if unset
+    wayfire_ini: Option<PathBuf>,
+    /// overlay of "Identify" while it is shown
+    identify: Option<JoinHandle<Result<()>>>,
+    /// output changes, from [`hotplug::watch`] once shown
+    hotplug: Option<Receiver<()>>,
+    /// outputs changed while waiting for confirmation, refresh after
+    outdated: bool,
     init: bool,
+    backend: Backend,
+    error: Option<Error>,
+}
+
+/// Files of the user's display settings
+#[derive(Clone)]
+pub struct DisplayFiles {
+    /// `[output:NAME]` sections read on init and written by "Save"
+    pub wayfire_ini: PathBuf,
+    pub custom_modes: CustomModeStore,
+}
+
+impl DisplayFiles {
+    /// [`wayfire::config_path`] and [`CustomModeStore::from_env`]
+    pub fn from_env() -> Self {
+        Self {
+            wayfire_ini: wayfire::config_path(),
+            custom_modes: CustomModeStore::from_env(),
+        }
+    }
+
+    /// files below `dir`, none of them there yet
+    #[cfg(test)]
+    pub fn in_dir(dir: &Path) -> Self {
+        Self {
+            wayfire_ini: dir.join("wayfire.ini"),
+            custom_modes: CustomModeStore::new(dir.join("custom-modes.toml")),
+        }
+    }
+}
+
+/// Applied settings waiting for confirmation
+struct Pending {
+    previous: Vec<DisplayState>,
+    /// settings on screen, kept if confirmed
+    applied: Vec<DisplayState>,
+    deadline: Instant,
+    /// `true` keeps the new settings, `false` reverts at once
+    answer: Sender<bool>,
+    /// `Ok(true)` if kept, `Ok(false)` if reverted
+    done: Receiver<Result<bool>>,
+}
+
+impl Drop for Pending {
+    /// unanswered, e.g. on quit: revert and wait until it is done
+    fn drop(&mut self) {
+        let _ = self.answer.send(false);
+        let _ = self.done.recv();
+    }
 }
 
 /// Display
-#[derive(Default)]
-struct Display {
-    name: String,
-    description: String,
-    enable: bool,
-    physical_size: String,
-    mode: Vec<Resolution>,
-    now_mode: Resolution,
-    position: (u16, u16),
-    transform: Transform,
-    scale: f64,
+#[derive(Default, Clone)]
+pub struct Display {
+    pub(super) name: String,
+    pub(super) description: String,
+    pub(super) enable: bool,
+    pub(super) physical_size: String,
+    pub(super) mode: Vec<Resolution>,
+    pub(super) now_mode: Resolution,
+    pub(super) position: (i32, i32),
+    pub(super) transform: Transform,
+    pub(super) scale: f64,
+    pub(super) backend: Backend,
+    /// custom mode being edited
+    pub(super) custom: CustomMode,
+    /// identity and capabilities, if the kernel has its EDID
+    pub(super) edid: Option<Edid>,
+    /// variable refresh rate, `None` if the output doesn't advertise it
+    pub(super) adaptive_sync: Option<bool>,
+    /// picture on or off, `None` if the backend can't switch it
+    pub(super) power: Option<bool>,
 }
 
 #[derive(Default, Clone)]
-struct Resolution {
-    resolution: String,
-    refresh: f64,
+pub(super) struct Resolution {
+    pub(super) resolution: String,
+    pub(super) refresh: f64,
+    /// not advertised by the monitor, set with `--custom-mode`
+    pub(super) custom: bool,
+}
+
+impl Resolution {
+    /// width and height in pixels, zero if unknown
+    pub(super) fn size(&self) -> (f64, f64) {
+        self.resolution
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.parse::<f64>().ok()?, h.parse::<f64>().ok()?)))
+            .unwrap_or_default()
+    }
+}
+
+/// Saved configuration of one display
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DisplayState {
+    pub name: String,
+    pub enable: bool,
+    pub mode: String,
+    pub refresh: f64,
+    pub position: (i32, i32),
+    pub transform: String,
+    pub scale: f64,
+    /// `mode` is a custom mode
+    #[serde(default)]
+    pub custom: bool,
+    /// variable refresh rate, `None` leaves it as it is
+    #[serde(default)]
+    pub adaptive_sync: Option<bool>,
 }
 
 impl Settings for Displays {
-    fn init(&mut self) {
-        let displays = Self::_init();
+    fn init(&mut self) -> Result<()> {
+        let displays = Self::_init(&self.backend)?;
         self.displays = displays.displays;
         self.now = displays.now;
+        self.read_edids();
+        self.add_custom_modes();
+        self.applied = self.state();
+        self.saved = Ini::read(&self.wayfire_ini())
+            .map(|ini| wayfire::outputs(&ini))
+            .unwrap_or_else(|e| {
+                self.error = Some(e);
+                BTreeMap::new()
+            });
         self.init = true;
+        Ok(())
     }
 
     fn is_init(&self) -> bool {
@@ -50,10 +191,53 @@ impl Settings for Displays {
         "Displays And Resolution"
     }
     fn heading(&self) -> &str {
-        "Resize and Roate display"  
+        "Resize and Roate display"
     }
 
     fn show(&mut self, ui: &mut eframe::egui::Ui) {
+        if self.hotplug.is_none() {
+            let ctx = ui.ctx().clone();
+            self.hotplug = Some(hotplug::watch(
+                self.backend.clone(),
+                self.sysfs.clone().unwrap_or_default(),
+                hotplug::POLL_INTERVAL,
+                move || ctx.request_repaint(),
+            ));
+        }
+        self.poll();
+        self.show_confirm(ui);
+        canvas::arrange(ui, &mut self.displays, &mut self.now, &mut self.drag);
+        self.show_issues(ui);
+        self.show_mirror(ui);
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(self.identify.is_none(), egui::Button::new("Identify"))
+                .on_hover_text("show the name of each display on it")
+                .clicked()
+            {
+                self.identify(identify::IDENTIFY_TIME);
+            }
+            if ui
+                .button("Remember for these monitors")
+                .on_hover_text("applied again by `rsettings display daemon` when they are plugged")
+                .clicked()
+            {
+                if let Err(e) = LayoutStore::from_env().remember(self) {
+                    self.error = Some(e);
+                }
+            }
+            if ui
+                .button("Save to wayfire.ini")
+                .on_hover_text("keep these settings after the next login")
+                .clicked()
+            {
+                if let Err(e) = self.save_wayfire(&self.wayfire_ini()) {
+                    self.error = Some(e);
+                }
+            }
+            self.show_kanshi(ui);
+        });
+        ui.add_space(8.0);
         Grid::new("display_grid")
             .num_columns(2)
             .spacing([100.0, 8.0])
@@ -68,19 +252,39 @@ impl Settings for Displays {
                         }
                     });
                 ui.end_row();
-                let now_dis = self.displays.get_mut(&self.now).unwrap();
-                now_dis.show(ui);
+                ui.label("Backend");
+                ui.label(self.backend.name());
+                ui.end_row();
+                ui.label("Saved");
+                ui.label(
+                    self.saved
+                        .get(&self.now)
+                        .map_or("not in wayfire.ini".to_string(), |o| o.summary()),
+                );
+                ui.end_row();
+                self.show_power(ui);
+                if let Some(now_dis) = self.displays.get_mut(&self.now) {
+                    now_dis.show(ui);
+                }
             });
+        self.save_custom_mode();
     }
-    fn apply(&mut self) {
-        for (_, display) in &mut self.displays {
-            display.apply();
-        }
+    fn apply(&mut self) -> Result<()> {
+        self.apply_with_timeout(REVERT_TIMEOUT)
+    }
+
+    fn can_apply(&self) -> bool {
+        self.pending.is_none()
+    }
+
+    fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
     }
 }
 
 impl Settings for Display {
-    fn init(&mut self) {
+    fn init(&mut self) -> Result<()> {
+        Ok(())
     }
     fn name(&self) -> &str {
         &self.name
@@ -92,46 +296,70 @@ impl Settings for Display {
         ui.label("Physical Size");
         ui.label(&self.physical_size);
         ui.end_row();
+        if let Some(edid) = &self.edid {
+            show_edid(ui, edid);
+        }
         ui.label("Enable");
         ui.checkbox(&mut self.enable, "enable display");
         ui.end_row();
+        if let Some(adaptive_sync) = &mut self.adaptive_sync {
+            ui.label("Adaptive Sync");
+            ui.checkbox(adaptive_sync, "variable refresh rate")
+                .on_hover_text("let the refresh rate follow the frame rate of games and videos");
+            ui.end_row();
+        }
         ui.label("Transform");
-        ComboBox::from_label("transform")
-            .selected_text(self.transform.as_str())
-            .show_ui(ui, |ui| {
-                ui.selectable_value(&mut self.transform, Transform::Normal, "normal");
-                ui.selectable_value(&mut self.transform, Transform::Roate90, "90");
-                ui.selectable_value(&mut self.transform, Transform::Roate180, "180");
-                ui.selectable_value(&mut self.transform, Transform::Roate270, "270");
-                ui.selectable_value(&mut self.transform, Transform::Flipped, "flipped");
-            });
+        ui.horizontal(|ui| {
+            ComboBox::from_label("transform")
+                .selected_text(self.transform.as_str())
+                .show_ui(ui, |ui| {
+                    for transform in Transform::ALL {
+                        ui.selectable_value(&mut self.transform, transform, transform.as_str());
+                    }
+                });
+            orientation_preview(ui, self);
+        });
         ui.end_row();
         ui.label("Resolution&Refresh");
         ui.horizontal(|ui| {
+            let mut selected = None;
             ComboBox::from_label("")
                 .selected_text(self.now_mode.resolution.as_str())
                 .show_ui(ui, |ui| {
+                    let mut seen = Vec::new();
                     for res in &self.mode {
-                        ui.selectable_value(
-                            &mut self.now_mode.resolution,
-                            res.resolution.to_owned(),
-                            res.resolution.as_str(),
-                        );
+                        if seen.contains(&&res.resolution) {
+                            continue;
+                        }
+                        seen.push(&res.resolution);
+                        let now = res.resolution == self.now_mode.resolution;
+                        if ui.selectable_label(now, res.resolution.as_str()).clicked() {
+                            selected = Some(res.clone());
+                        }
                     }
                 });
             ComboBox::from_label("Hz")
                 .selected_text(self.now_mode.refresh.to_string().as_str())
                 .show_ui(ui, |ui| {
                     for res in &self.mode {
-                        if res.resolution == self.now_mode.resolution {
-                            ui.selectable_value(
-                                &mut self.now_mode.refresh,
-                                res.refresh,
-                                res.refresh.to_string().as_str(),
-                            );
+                        if res.resolution != self.now_mode.resolution {
+                            continue;
+                        }
+                        let now = res.refresh == self.now_mode.refresh
+                            && res.custom == self.now_mode.custom;
+                        let text = if res.custom {
+                            format!("{} (custom)", res.refresh)
+                        } else {
+                            res.refresh.to_string()
+                        };
+                        if ui.selectable_label(now, text).clicked() {
+                            selected = Some(res.clone());
                         }
                     }
                 });
+            if let Some(mode) = selected {
+                self.now_mode = mode;
+            }
         });
         ui.end_row();
         ui.label("Position");
@@ -144,134 +372,894 @@ impl Settings for Display {
         ui.label("Scale");
         ui.add(egui::Slider::new(&mut self.scale, 0.1..=5.0));
         ui.end_row();
+        self.show_scale(ui);
+        self.show_custom_mode(ui);
     }
-    fn apply(&mut self) {
+    fn apply(&mut self) -> Result<()> {
         println!("apply {}", self.name);
-        let enable = if self.enable { "--on" } else { "--off" };
-        let output = Command::new("wlr-randr")
-            .arg("--output")
-            .arg(&self.name)
-            .arg("--mode")
-            .arg(format!(
-                "{}@{}",
-                &self.now_mode.resolution, self.now_mode.refresh
-            ))
-            .arg("--scale")
-            .arg(&self.scale.to_string())
-            .arg("--transform")
-            .arg(&self.transform.as_str())
-            .arg(enable)
-            .arg("--pos")
-            .arg(&format!("{},{}", self.position.0, self.position.1))
-            .output()
-            .expect("Execute wlr-randr error");
-        if !output.status.success() {
-            eprintln!("wlr-randr execute error");
-        }
+        self.backend.apply(&[self.state()])
     }
 }
 
 impl Displays {
-    fn _init() -> Self {
+    /// displays of the backend found by [`Backend::detect`]
+    pub fn new(runner: Runner) -> Self {
+        Self::with_backend(Backend::detect(runner))
+    }
+
+    pub fn with_backend(backend: Backend) -> Self {
+        Self {
+            backend,
+            ..Default::default()
+        }
+    }
+
+    /// read the EDID of the monitors below `sysfs` on init
+    pub fn with_sysfs(mut self, sysfs: SysFs) -> Self {
+        self.sysfs = Some(sysfs);
+        self
+    }
+
+    /// offer and save custom modes of `store`
+    pub fn with_custom_modes(mut self, store: CustomModeStore) -> Self {
+        self.custom_modes = Some(store);
+        self
+    }
+
+    /// read and save wayfire.ini and custom modes of `files`
+    pub fn with_files(mut self, files: DisplayFiles) -> Self {
+        self.wayfire_ini = Some(files.wayfire_ini);
+        self.with_custom_modes(files.custom_modes)
+    }
+
+    fn wayfire_ini(&self) -> PathBuf {
+        self.wayfire_ini
+            .clone()
+            .unwrap_or_else(wayfire::config_path)
+    }
+
+    fn _init(backend: &Backend) -> Result<Self> {
         // 1. get display info
-        let output = Command::new("wlr-randr").output().unwrap();
-        let out = String::from_utf8(output.stdout).unwrap();
-        let mut outs = out.lines();
+        let outputs = backend.outputs()?;
 
-        // 2. parser display info
+        // 2. share the backend with every display
+        Ok(Self::from_outputs(outputs, backend))
+    }
+
+    fn from_outputs(outputs: Vec<Display>, backend: &Backend) -> Self {
         let mut now = String::new();
         let mut displays = BTreeMap::new();
-        while let Some(line) = outs.next() {
-            let display = Self::parser_display(&mut outs, line);
+        for mut display in outputs {
+            display.backend = backend.clone();
             now = display.name.to_owned();
             displays.insert(display.name.to_owned(), display);
         }
+        Self {
+            displays,
+            now,
+            backend: backend.clone(),
+            ..Default::default()
+        }
+    }
 
-        Self { displays, now, init: false }
-    }
-
-    fn parser_display(outs: &mut Lines, first_line: &str) -> Display {
-        // 1. parser name and description
-        let mut display = Display::default();
-        let datas: Vec<&str> = first_line.trim().split(' ').collect();
-        let mut iter = datas.iter();
-        let name = iter.next().unwrap();
-        let mut description = String::new();
-        for d in iter {
-            description += d;
-            description += " ";
-        }
-        display.name = name.to_string();
-        display.description = description;
-
-        // 2. parser physical_size
-        let line = outs.next().unwrap();
-        let datas: Vec<&str> = line.trim().split(':').collect();
-        let physical_size = datas.get(1).unwrap();
-        display.physical_size = physical_size.to_string();
-
-        // 3. parser enable
-        let line = outs.next().unwrap();
-        let datas: Vec<&str> = line.trim().split(':').collect();
-        let enable_s = datas.get(1).unwrap();
-        let mut enable = false;
-        if enable_s.trim() == "yes" {
-            enable = true;
-        }
-        display.enable = enable;
-
-        // 4. parser modes and position
-        outs.next();
-        let re = Regex::new(r"\s*(.*)\spx,\s(\d*\.\d*)\sHz").unwrap();
-        loop {
-            let line = outs.next().unwrap();
-            if !line.contains("Hz") {
-                let re = Regex::new(r"\s*:\s(\d+),(\d+)").unwrap();
-                let caps = re.captures(line).unwrap();
-                display.position = (
-                    caps.get(1).unwrap().as_str().parse().unwrap(),
-                    caps.get(2).unwrap().as_str().parse().unwrap(),
-                );
-                break;
+    fn read_edids(&mut self) {
+        if let Some(sysfs) = &self.sysfs {
+            let mut edids = edid::read_all(sysfs);
+            for display in self.displays.values_mut() {
+                display.edid = edids.remove(&display.name);
+            }
+        }
+    }
+
+    /// Read the outputs again, after monitors were plugged or unplugged.
+    ///
+    /// Displays showing the same monitor as before keep the edits not
+    /// applied yet, the others take what the compositor says now.
+    pub fn refresh(&mut self) -> Result<()> {
+        let outputs = self.backend.outputs()?;
+        self.merge(outputs)
+    }
+
+    fn merge(&mut self, outputs: Vec<Display>) -> Result<()> {
+        // 1. the outputs as on init
+        let mut fresh = Self::from_outputs(outputs, &self.backend);
+        fresh.sysfs = self.sysfs.clone();
+        fresh.custom_modes = self.custom_modes.clone();
+        fresh.read_edids();
+        fresh.add_custom_modes();
+        if let Some(e) = fresh.error.take() {
+            self.error = Some(e);
+        }
+
+        // 2. bring back the edits
+        let mut applied = Vec::new();
+        for (name, display) in fresh.displays.iter_mut() {
+            let was = self.applied.iter().find(|s| s.name == *name);
+            match (self.displays.remove(name), was) {
+                (Some(edited), Some(was))
+                    if edited.id() == display.id() && edited.state() != *was =>
+                {
+                    applied.push(was.clone());
+                    let power = display.power;
+                    *display = edited;
+                    display.power = power;
+                }
+                _ => applied.push(display.state()),
+            }
+        }
+
+        // 3. forget unplugged ones
+        self.displays = fresh.displays;
+        self.applied = applied;
+        self.mirror_select
+            .retain(|name| self.displays.contains_key(name));
+        if !self.displays.contains_key(&self.now) {
+            self.now = fresh.now;
+        }
+        Ok(())
+    }
+
+    /// displays currently connected, with the custom modes of `files`
+    pub fn load(runner: Runner, files: &DisplayFiles) -> Result<Self> {
+        let mut displays = Self::new(runner).with_files(files.clone());
+        displays.init()?;
+        Ok(displays)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Display> {
+        self.displays.values()
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Result<&mut Display> {
+        self.displays
+            .get_mut(name)
+            .ok_or_else(|| Error::NotFound(format!("display {}", name)))
+    }
+
+    pub fn state(&self) -> Vec<DisplayState> {
+        self.displays.values().map(|d| d.state()).collect()
+    }
+
+    /// Set displays to `states`; displays not connected now are skipped.
+    pub fn restore(&mut self, states: &[DisplayState]) -> Result<()> {
+        for state in states {
+            if let Some(display) = self.displays.get_mut(&state.name) {
+                display.restore(state)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Offer the custom modes saved for each monitor next to its own;
+    /// a bad file or mode is reported and skipped.
+    fn add_custom_modes(&mut self) {
+        let saved = match self.custom_modes.as_ref().map(CustomModeStore::load) {
+            Some(Ok(saved)) => saved,
+            Some(Err(e)) => {
+                self.error = Some(e);
+                return;
+            }
+            None => return,
+        };
+        let mut errors = Vec::new();
+        for display in self.displays.values_mut() {
+            // saved under the monitor name before ids came from the EDID
+            let (id, monitor) = (display.id(), display.monitor());
+            let legacy = saved.get(&monitor).filter(|_| monitor != id);
+            let modes: Vec<String> = saved
+                .get(&id)
+                .into_iter()
+                .chain(legacy)
+                .flatten()
+                .cloned()
+                .collect();
+            for mode in &modes {
+                if let Err(e) = display.add_custom_mode(mode) {
+                    errors.push(e);
+                }
+            }
+        }
+        if let Some(e) = Error::many(errors) {
+            self.error = Some(e);
+        }
+    }
+
+    /// handle "Save for this monitor" of the selected display
+    fn save_custom_mode(&mut self) {
+        let display = match self.displays.get_mut(&self.now) {
+            Some(display) if display.custom.save => display,
+            _ => return,
+        };
+        display.custom.save = false;
+        let store = match &self.custom_modes {
+            Some(store) => store,
+            None => return,
+        };
+        let result = display
+            .custom
+            .timing()
+            .ok_or_else(|| Error::parse("custom mode", display.custom_mode_text()))
+            .and_then(|timing| {
+                let mode = timing.mode();
+                display.add_custom_mode(&mode)?;
+                store.add(&display.id(), &mode)
+            });
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+
+    /// Show the picture of `names[0]` on all of `names`, see [`layout::mirror`].
+    pub fn mirror(&mut self, names: &[&str]) -> Result<()> {
+        let before = self.state();
+        if !layout::mirror(&mut self.displays, names) {
+            return Err(Error::Layout(
+                "mirroring needs two connected displays".to_string(),
+            ));
+        }
+        // keep the layout from before the first mirror
+        self.extended.get_or_insert(before);
+        Ok(())
+    }
+
+    /// Undo [`Displays::mirror`], or put all displays side by side if
+    /// they weren't mirrored here.
+    pub fn extend(&mut self) -> Result<()> {
+        match self.extended.take() {
+            Some(states) => self.restore(&states),
+            None => {
+                layout::auto_arrange(&mut self.displays);
+                Ok(())
+            }
+        }
+    }
+
+    /// outputs saved in wayfire.ini, by connector name
+    pub fn saved(&self) -> &BTreeMap<String, WayfireOutput> {
+        &self.saved
+    }
+
+    /// Write all displays into their `[output:NAME]` sections of the
+    /// wayfire config at `path`, leaving the rest of the file alone.
+    pub fn save_wayfire(&mut self, path: &Path) -> Result<()> {
+        let mut ini = Ini::read(path)?;
+        wayfire::set_outputs(&mut ini, &self.state());
+        ini.write(path)?;
+        self.saved = wayfire::outputs(&ini);
+        Ok(())
+    }
+
+    /// Set displays to a saved `layout`, matching monitors by id so it
+    /// doesn't matter which connector they are plugged into.
+    pub fn restore_layout(&mut self, layout: &SavedLayout) -> Result<()> {
+        let mut unused: Vec<&SavedOutput> = layout.outputs.iter().collect();
+        for display in self.displays.values_mut() {
+            let id = display.id();
+            if let Some(i) = unused.iter().position(|o| o.id == id) {
+                let saved = unused.remove(i);
+                display.restore(&saved.state)?;
             }
-            let caps = re.captures(line).unwrap();
-            println!("{:?}", caps);
-            let mode = Resolution {
-                resolution: caps.get(1).unwrap().as_str().to_string(),
-                refresh: caps.get(2).unwrap().as_str().parse().unwrap(),
+        }
+        Ok(())
+    }
+
+    /// Apply all displays, the previous settings come back after `timeout`
+    /// unless [`Displays::keep`] is called.
+    pub fn apply_with_timeout(&mut self, timeout: Duration) -> Result<()> {
+        // 1. settings on screen now, confirmed
+        if self.pending.is_some() {
+            return Err(Error::Usage(
+                "keep or revert the last display change first".to_string(),
+            ));
+        }
+        self.check()?;
+        let previous = self.applied.clone();
+        let states = self.state();
+
+        // 2. apply what the compositor accepts, on failure go back at once
+        self.backend.test(&states)?;
+        if let Err(e) = self.backend.apply(&states) {
+            let _ = self.backend.apply(&previous);
+            self.restore(&previous)?;
+            return Err(e);
+        }
+
+        // 3. wait for confirmation in background
+        let backend = self.backend.clone();
+        let (answer, answer_rx) = channel();
+        let (done_tx, done) = channel();
+        thread::spawn(move || {
+            let done = match answer_rx.recv_timeout(timeout) {
+                Ok(true) => Ok(true),
+                _ => backend.apply(&previous).map(|()| false),
             };
-            display.mode.push(mode.clone());
-            if line.contains("current") {
-                display.now_mode = mode;
+            let _ = done_tx.send(done);
+        });
+        self.pending = Some(Pending {
+            previous: self.applied.clone(),
+            applied: states,
+            deadline: Instant::now() + timeout,
+            answer,
+            done,
+        });
+        Ok(())
+    }
+
+    /// apply all displays without asking for confirmation
+    pub fn apply_now(&mut self) -> Result<()> {
+        self.check()?;
+        self.keep()?;
+        let states = self.state();
+        self.backend.test(&states)?;
+        self.backend.apply(&states)?;
+        self.applied = states;
+        Ok(())
+    }
+
+    /// fail on the first layout error
+    pub fn check(&self) -> Result<()> {
+        match layout::validate(self.displays.values())
+            .into_iter()
+            .find(|issue| issue.severity == Severity::Error)
+        {
+            Some(issue) => Err(Error::Layout(issue.message)),
+            None => Ok(()),
+        }
+    }
+
+    /// keep the settings of the last apply
+    pub fn keep(&mut self) -> Result<()> {
+        self.answer(true)
+    }
+
+    /// go back to the settings before the last apply
+    pub fn revert(&mut self) -> Result<()> {
+        self.answer(false)
+    }
+
+    /// time left to confirm, `None` if nothing waits for confirmation
+    pub fn remaining(&self) -> Option<Duration> {
+        self.pending
+            .as_ref()
+            .map(|p| p.deadline.saturating_duration_since(Instant::now()))
+    }
+
+    fn answer(&mut self, keep: bool) -> Result<()> {
+        if let Some(pending) = self.pending.take() {
+            let _ = pending.answer.send(keep);
+            let done = pending.done.recv().unwrap_or(Ok(keep));
+            self.finish(&pending, done)?;
+        }
+        Ok(())
+    }
+
+    /// pick up the result of the background revert and output changes
+    fn poll(&mut self) {
+        while let Some(Ok(())) = self.hotplug.as_ref().map(|r| r.try_recv()) {
+            self.outdated = true;
+        }
+        if self.outdated && self.pending.is_none() {
+            self.outdated = false;
+            if let Err(e) = self.refresh() {
+                self.error = Some(e);
+            }
+        }
+        self.join_identify();
+        let done = match &self.pending {
+            Some(pending) => pending.done.try_recv(),
+            None => return,
+        };
+        match done {
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => self.pending = None,
+            Ok(done) => {
+                if let Some(pending) = self.pending.take() {
+                    if let Err(e) = self.finish(&pending, done) {
+                        self.error = Some(e);
+                    }
+                }
             }
         }
+    }
 
-        // 5. parser transform and scale
-        let re = Regex::new(r"\s*:\s([\w|\.]*)").unwrap();
-        let transform = re
-            .captures(outs.next().unwrap())
-            .unwrap()
-            .get(1)
-            .unwrap()
-            .as_str();
-        display.transform = Transform::from_str(transform).unwrap();
-        display.scale = re
-            .captures(outs.next().unwrap())
-            .unwrap()
-            .get(1)
-            .unwrap()
-            .as_str()
+    /// what "Identify" shows on the enabled displays
+    pub fn labels(&self) -> Vec<Label> {
+        self.iter().filter(|d| d.enable).map(Label::new).collect()
+    }
+
+    /// Show the connector of every enabled display on it for `time`,
+    /// without blocking; does nothing while they are shown already.
+    pub fn identify(&mut self, time: Duration) {
+        self.join_identify();
+        if self.identify.is_some() {
+            return;
+        }
+        let labels = self.labels();
+        self.identify = Some(thread::spawn(move || identify::show(&labels, time)));
+    }
+
+    /// forget the identify thread once it is done, keeping its error
+    fn join_identify(&mut self) {
+        if self.identify.as_ref().is_some_and(|h| h.is_finished()) {
+            let shown = self.identify.take().map(|h| h.join());
+            if let Some(Ok(Err(e))) = shown {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn finish(&mut self, pending: &Pending, done: Result<bool>) -> Result<()> {
+        match done {
+            Ok(true) => {
+                self.applied = pending.applied.clone();
+                Ok(())
+            }
+            Ok(false) => self.restore(&pending.previous),
+            Err(e) => {
+                self.restore(&pending.previous)?;
+                Err(e)
+            }
+        }
+    }
+
+    fn show_issues(&mut self, ui: &mut Ui) {
+        let issues = layout::validate(self.displays.values());
+        if issues.is_empty() {
+            return;
+        }
+        for issue in &issues {
+            let color = match issue.severity {
+                Severity::Error => Color32::RED,
+                Severity::Warning => Color32::from_rgb(200, 120, 0),
+            };
+            ui.colored_label(color, &issue.message);
+        }
+        if ui.button("Auto-arrange left-to-right").clicked() {
+            layout::auto_arrange(&mut self.displays);
+        }
+    }
+
+    /// pick displays to mirror, or go back to extended
+    fn show_mirror(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Mirror");
+            for name in self.displays.keys() {
+                let mut ticked = self.mirror_select.contains(name);
+                if ui.checkbox(&mut ticked, name.as_str()).changed() {
+                    if ticked {
+                        self.mirror_select.insert(name.clone());
+                    } else {
+                        self.mirror_select.remove(name);
+                    }
+                }
+            }
+            // the selected display leads, its picture is copied
+            let mut names: Vec<String> = self.mirror_select.iter().cloned().collect();
+            if let Some(i) = names.iter().position(|n| *n == self.now) {
+                names.swap(0, i);
+            }
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            if ui
+                .add_enabled(names.len() >= 2, egui::Button::new("Mirror"))
+                .clicked()
+            {
+                if let Err(e) = self.mirror(&names) {
+                    self.error = Some(e);
+                }
+            }
+            if ui.button("Extend").clicked() {
+                if let Err(e) = self.extend() {
+                    self.error = Some(e);
+                }
+            }
+        });
+    }
+
+    /// load a matching kanshi profile for preview, or copy the layout as one
+    fn show_kanshi(&mut self, ui: &mut Ui) {
+        ui.menu_button("Load kanshi profile", |ui| {
+            let profiles = match kanshi::read(&kanshi::config_path()) {
+                Ok(profiles) => profiles,
+                Err(e) => {
+                    ui.label(e.to_string());
+                    return;
+                }
+            };
+            let matching: Vec<KanshiProfile> = profiles
+                .into_iter()
+                .filter(|p| p.assign(self).is_some())
+                .collect();
+            if matching.is_empty() {
+                ui.label("no profile for these monitors");
+            }
+            for profile in &matching {
+                let name = profile.name.as_deref().unwrap_or("(anonymous)");
+                if ui.button(name).clicked() {
+                    if let Err(e) = profile.restore(self) {
+                        self.error = Some(e);
+                    }
+                    ui.close_menu();
+                }
+            }
+        });
+        if ui.button("Copy as kanshi profile").clicked() {
+            ui.output().copied_text = KanshiProfile::capture("rsettings", self).to_config();
+        }
+    }
+
+    /// switch the picture of the selected display off or on, at once
+    fn show_power(&mut self, ui: &mut Ui) {
+        let lit = |d: &Display| d.enable && d.power != Some(false);
+        let others = self
+            .displays
+            .values()
+            .filter(|d| d.name != self.now && lit(d))
+            .count();
+        let display = match self.displays.get_mut(&self.now) {
+            Some(display) => display,
+            None => return,
+        };
+        let on = match display.power {
+            Some(on) => on,
+            None => return,
+        };
+        ui.label("Power");
+        let text = if on { "Turn off" } else { "Turn on" };
+        if ui
+            .add_enabled(!on || others > 0, egui::Button::new(text))
+            .on_hover_text("the picture only, the display keeps its place in the layout")
+            .on_disabled_hover_text("the only display with a picture")
+            .clicked()
+        {
+            if let Err(e) = display.set_power(!on) {
+                self.error = Some(e);
+            }
+        }
+        ui.end_row();
+    }
+
+    fn show_confirm(&mut self, ui: &mut Ui) {
+        let remaining = match self.remaining() {
+            Some(remaining) => remaining,
+            None => return,
+        };
+        let (mut keep, mut revert) = (false, false);
+        egui::Window::new("Display settings")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ui.ctx(), |ui| {
+                ui.label(format!(
+                    "Keep these settings? Reverting in {}s",
+                    remaining.as_secs() + 1
+                ));
+                ui.horizontal(|ui| {
+                    keep = ui.button("Keep").clicked();
+                    revert = ui.button("Revert").clicked();
+                });
+            });
+        let result = if keep {
+            self.keep()
+        } else if revert {
+            self.revert()
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+        // count down without input
+        ui.ctx().request_repaint();
+    }
+}
+
+impl Display {
+    pub fn state(&self) -> DisplayState {
+        DisplayState {
+            name: self.name.clone(),
+            enable: self.enable,
+            mode: self.now_mode.resolution.clone(),
+            refresh: self.now_mode.refresh,
+            position: self.position,
+            transform: self.transform.as_str().to_string(),
+            scale: self.scale,
+            custom: self.now_mode.custom,
+            adaptive_sync: self.adaptive_sync,
+        }
+    }
+
+    /// Disabled outputs have no current mode, they get the preferred one,
+    /// else the first, so turning them on has a mode to ask for.
+    pub(super) fn with_mode(mut self, preferred: Option<Resolution>) -> Self {
+        if self.now_mode.resolution.is_empty() {
+            if let Some(mode) = preferred.or_else(|| self.mode.first().cloned()) {
+                self.now_mode = mode;
+            }
+        }
+        self
+    }
+
+    /// Set all of `state`; an empty mode, of outputs without any, stays as it is.
+    pub fn restore(&mut self, state: &DisplayState) -> Result<()> {
+        let mode = format!("{}@{}", state.mode, state.refresh);
+        if state.custom {
+            self.set_custom_mode(&mode)?;
+        } else if !state.mode.is_empty() {
+            self.set_mode(&mode)?;
+        }
+        self.set_transform(&state.transform)?;
+        self.enable = state.enable;
+        self.position = state.position;
+        self.scale = state.scale;
+        if let Some(on) = state.adaptive_sync {
+            // saved on another output without it
+            let _ = self.set_adaptive_sync(on);
+        }
+        Ok(())
+    }
+
+    /// Stable id of the monitor: its EDID serial, else what
+    /// [`Display::monitor`] says.
+    pub fn id(&self) -> String {
+        self.edid
+            .as_ref()
+            .and_then(|edid| edid.id())
+            .unwrap_or_else(|| self.monitor())
+    }
+
+    /// Make and model as the compositor describes them, without the
+    /// connector; the connector if there is no description.
+    pub fn monitor(&self) -> String {
+        let description = self.description.trim().trim_matches('"');
+        let suffix = format!("({})", self.name);
+        let id = description
+            .strip_suffix(&suffix)
+            .unwrap_or(description)
+            .trim();
+        if id.is_empty() {
+            self.name.clone()
+        } else {
+            id.to_string()
+        }
+    }
+
+    /// size in the compositor space, after scale and transform
+    pub fn logical_size(&self) -> (f64, f64) {
+        let (width, height) = self.mode_size();
+        let scale = if self.scale > 0.0 { self.scale } else { 1.0 };
+        let (width, height) = (width / scale, height / scale);
+        if self.transform.is_rotated() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// pixels of the current mode, as the panel has them
+    pub(super) fn mode_size(&self) -> (f64, f64) {
+        self.now_mode.size()
+    }
+
+    /// one line summary, e.g. `eDP-1 1920x1080@60 +0,0 scale 1.25 normal`
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {}@{} +{},{} scale {} {}{}",
+            self.name,
+            self.now_mode.resolution,
+            self.now_mode.refresh,
+            self.position.0,
+            self.position.1,
+            self.scale,
+            self.transform.as_str(),
+            if self.enable { "" } else { " (off)" },
+        )
+    }
+
+    /// Select a mode given as `WxH` or `WxH@HZ`.
+    ///
+    /// The refresh rate closest to `HZ` is taken, without it the first listed one.
+    pub fn set_mode(&mut self, mode: &str) -> Result<()> {
+        let (resolution, refresh) = match mode.split_once('@') {
+            Some((resolution, refresh)) => {
+                let refresh: f64 = refresh
+                    .trim_end_matches("Hz")
+                    .parse()
+                    .map_err(|_| Error::parse("mode", mode))?;
+                (resolution, Some(refresh))
+            }
+            None => (mode, None),
+        };
+        let mut candidates = self.mode.iter().filter(|m| m.resolution == resolution);
+        let found = match refresh {
+            Some(refresh) => candidates.min_by(|a, b| {
+                (a.refresh - refresh)
+                    .abs()
+                    .total_cmp(&(b.refresh - refresh).abs())
+            }),
+            None => candidates.next(),
+        };
+        self.now_mode = found
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("mode {} of {}", mode, self.name)))?;
+        Ok(())
+    }
+
+    /// Offer `WxH@HZ` as a custom mode, unless it is one already.
+    pub fn add_custom_mode(&mut self, mode: &str) -> Result<()> {
+        let (resolution, refresh) = match mode.split_once('@') {
+            Some((resolution, refresh)) => (resolution, refresh.trim_end_matches("Hz")),
+            None => return Err(Error::parse("custom mode", mode)),
+        };
+        let refresh: f64 = refresh
             .parse()
-            .unwrap();
-        display
+            .map_err(|_| Error::parse("custom mode", mode))?;
+        let valid = resolution
+            .split_once('x')
+            .is_some_and(|(w, h)| w.parse::<u32>().is_ok() && h.parse::<u32>().is_ok());
+        if !valid || refresh <= 0.0 {
+            return Err(Error::parse("custom mode", mode));
+        }
+        let known = self
+            .mode
+            .iter()
+            .any(|m| m.custom && m.resolution == resolution && (m.refresh - refresh).abs() < 0.001);
+        if !known {
+            self.mode.push(Resolution {
+                resolution: resolution.to_string(),
+                refresh,
+                custom: true,
+            });
+        }
+        Ok(())
+    }
+
+    /// Select `WxH@HZ` as a custom mode, applied with `--custom-mode`.
+    pub fn set_custom_mode(&mut self, mode: &str) -> Result<()> {
+        self.add_custom_mode(mode)?;
+        let (resolution, refresh) = mode.split_once('@').unwrap_or_default();
+        let refresh: f64 = refresh.trim_end_matches("Hz").parse().unwrap_or_default();
+        self.now_mode = self
+            .mode
+            .iter()
+            .find(|m| m.custom && m.resolution == resolution && (m.refresh - refresh).abs() < 0.001)
+            .cloned()
+            .unwrap_or_default();
+        Ok(())
+    }
+
+    fn custom_mode_text(&self) -> String {
+        format!(
+            "{}x{}@{}",
+            self.custom.width, self.custom.height, self.custom.refresh
+        )
+    }
+
+    /// logical size, warnings and recommendations for the scale
+    fn show_scale(&mut self, ui: &mut Ui) {
+        let (width, height) = self.logical_size();
+        ui.label("Logical Size");
+        ui.label(format!("{:.0}x{:.0}", width, height));
+        ui.end_row();
+        for warning in self.scale_warnings() {
+            ui.label("");
+            ui.colored_label(Color32::YELLOW, warning);
+            ui.end_row();
+        }
+        let recommended = self.recommended_scales();
+        if !recommended.is_empty() {
+            ui.label("Recommended");
+            ui.horizontal(|ui| {
+                for scale in recommended {
+                    if ui
+                        .selectable_label(self.scale == scale, scale.to_string())
+                        .clicked()
+                    {
+                        self.set_scale(scale);
+                    }
+                }
+                if let Some(dpi) = self.dpi() {
+                    ui.label(format!("({:.0} dpi)", dpi));
+                }
+            });
+            ui.end_row();
+        }
+        // the window is drawn at the scale of its output already
+        let size = TEXT_PREVIEW_SIZE * self.scale as f32 / ui.ctx().pixels_per_point();
+        ui.label("Text Preview");
+        ui.label(egui::RichText::new("The quick brown fox jumps over the lazy dog").size(size));
+        ui.end_row();
+    }
+
+    /// editor and CVT timing of a custom mode
+    fn show_custom_mode(&mut self, ui: &mut Ui) {
+        ui.label("Custom Mode");
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut self.custom.width).clamp_range(8..=16384));
+            ui.label("x");
+            ui.add(egui::DragValue::new(&mut self.custom.height).clamp_range(1..=16384));
+            ui.label("@");
+            ui.add(
+                egui::DragValue::new(&mut self.custom.refresh)
+                    .clamp_range(1.0..=500.0)
+                    .speed(0.1)
+                    .suffix(" Hz"),
+            );
+            ui.checkbox(&mut self.custom.reduced, "reduced blanking");
+        });
+        ui.end_row();
+        ui.label("");
+        match self.custom.timing() {
+            Some(timing) => {
+                ui.vertical(|ui| {
+                    ui.label(format!(
+                        "{:.2} MHz, {:.3} Hz",
+                        timing.clock as f64 / 1000.0,
+                        timing.refresh()
+                    ))
+                    .on_hover_text(timing.to_string());
+                    ui.horizontal(|ui| {
+                        if ui.button("Use").clicked() {
+                            let _ = self.set_custom_mode(&timing.mode());
+                        }
+                        if ui.button("Save for this monitor").clicked() {
+                            self.custom.save = true;
+                        }
+                    });
+                });
+            }
+            None => {
+                ui.label("no timing for this mode");
+            }
+        }
+        ui.end_row();
+    }
+
+    pub fn set_scale(&mut self, scale: f64) {
+        self.scale = scale;
+    }
+
+    pub fn set_position(&mut self, position: (i32, i32)) {
+        self.position = position;
+    }
+
+    pub fn set_transform(&mut self, transform: &str) -> Result<()> {
+        self.transform =
+            Transform::from_str(transform).ok_or_else(|| Error::parse("transform", transform))?;
+        Ok(())
+    }
+
+    pub fn set_enable(&mut self, enable: bool) {
+        self.enable = enable;
+    }
+
+    /// Turn variable refresh rate on or off, if the output supports it.
+    pub fn set_adaptive_sync(&mut self, on: bool) -> Result<()> {
+        match &mut self.adaptive_sync {
+            Some(adaptive_sync) => {
+                *adaptive_sync = on;
+                Ok(())
+            }
+            None => Err(Error::NotFound(format!("adaptive sync on {}", self.name))),
+        }
+    }
+
+    /// Switch the picture off or on at once, if the backend can; unlike
+    /// [`Display::set_enable`] the output keeps its place in the layout.
+    pub fn set_power(&mut self, on: bool) -> Result<()> {
+        if self.power.is_none() {
+            return Err(Error::NotFound(format!("power control of {}", self.name)));
+        }
+        self.backend.set_power(&self.name, on)?;
+        self.power = Some(on);
+        Ok(())
     }
 }
 
-fn num_edit(ui: &mut Ui, num: u16) -> u16 {
+fn num_edit(ui: &mut Ui, num: i32) -> i32 {
     let mut s = num.to_string();
     let resp = ui.add(egui::TextEdit::singleline(&mut s).desired_width(100.0));
     if resp.changed() {
-        let n = s.trim().parse::<u16>();
+        let n = s.trim().parse::<i32>();
         if let Ok(n) = n {
             return n;
         }
@@ -279,40 +1267,519 @@ fn num_edit(ui: &mut Ui, num: u16) -> u16 {
     num
 }
 
-#[derive(PartialEq)]
-enum Transform {
+/// rows with what the EDID tells about the monitor
+fn show_edid(ui: &mut Ui, edid: &Edid) {
+    let mut row = |label: &str, value: String| {
+        ui.label(label);
+        ui.label(value);
+        ui.end_row();
+    };
+    let model = edid
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("{:04X}", edid.product));
+    row(
+        "Monitor",
+        format!("{} {} ({})", edid.maker(), model, edid.year),
+    );
+    if let Some(id) = edid.id() {
+        row("Serial", id);
+    }
+    if edid.size != (0, 0) {
+        row(
+            "Screen",
+            format!(
+                "{}x{} cm, {:.1}\"",
+                edid.size.0,
+                edid.size.1,
+                edid.diagonal()
+            ),
+        );
+    }
+    if let Some(native) = edid.native {
+        row(
+            "Native Mode",
+            format!("{}x{}@{:.2}", native.width, native.height, native.refresh),
+        );
+    }
+    if let Some(range) = edid.range {
+        row(
+            "Timing Range",
+            format!(
+                "{}-{} Hz, {}-{} kHz, up to {} MHz",
+                range.v_min, range.v_max, range.h_min, range.h_max, range.max_clock
+            ),
+        );
+    }
+    if let Some(hdr) = &edid.hdr {
+        let mut text = hdr.eotfs.join(", ");
+        if let Some(max) = hdr.max_luminance {
+            text += &format!(", up to {:.0} cd/m²", max);
+        }
+        row("HDR", text);
+    }
+    if !edid.colorimetry.is_empty() {
+        row("Colorimetry", edid.colorimetry.join(", "));
+    }
+}
+
+/// Rotation clockwise, after an optional horizontal flip
+#[derive(PartialEq, Default, Clone, Copy, Debug)]
+pub(super) enum Transform {
+    #[default]
     Normal,
     Roate90,
     Roate180,
     Roate270,
     Flipped,
-}
-
-impl Default for Transform {
-    fn default() -> Self {
-        Transform::Normal
-    }
+    Flipped90,
+    Flipped180,
+    Flipped270,
 }
 
 impl Transform {
-    fn as_str(&self) -> &str {
+    pub(super) const ALL: [Transform; 8] = [
+        Transform::Normal,
+        Transform::Roate90,
+        Transform::Roate180,
+        Transform::Roate270,
+        Transform::Flipped,
+        Transform::Flipped90,
+        Transform::Flipped180,
+        Transform::Flipped270,
+    ];
+
+    pub(super) fn as_str(&self) -> &'static str {
         match self {
             Transform::Normal => "normal",
             Transform::Roate90 => "90",
             Transform::Roate180 => "180",
             Transform::Roate270 => "270",
             Transform::Flipped => "flipped",
+            Transform::Flipped90 => "flipped-90",
+            Transform::Flipped180 => "flipped-180",
+            Transform::Flipped270 => "flipped-270",
+        }
+    }
+
+    pub(super) fn from_str(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == s)
+    }
+
+    /// clockwise rotation in degrees
+    pub(super) fn degrees(&self) -> u16 {
+        match self {
+            Transform::Normal | Transform::Flipped => 0,
+            Transform::Roate90 | Transform::Flipped90 => 90,
+            Transform::Roate180 | Transform::Flipped180 => 180,
+            Transform::Roate270 | Transform::Flipped270 => 270,
+        }
+    }
+
+    pub(super) fn is_flipped(&self) -> bool {
+        matches!(
+            self,
+            Transform::Flipped
+                | Transform::Flipped90
+                | Transform::Flipped180
+                | Transform::Flipped270
+        )
+    }
+
+    /// width and height swap in the compositor space
+    pub(super) fn is_rotated(&self) -> bool {
+        self.degrees() % 180 == 90
+    }
+
+    /// Where the up and right of the picture point on the panel, as unit
+    /// vectors with y growing downwards.
+    pub(super) fn picture_axes(&self) -> ((f32, f32), (f32, f32)) {
+        let right = if self.is_flipped() { -1.0 } else { 1.0 };
+        let rotate = |(x, y): (f32, f32)| match self.degrees() {
+            90 => (-y, x),
+            180 => (-x, -y),
+            270 => (y, -x),
+            _ => (x, y),
+        };
+        (rotate((0.0, -1.0)), rotate((right, 0.0)))
+    }
+}
+
+/// Draw the panel with an `F` showing how the picture sits on it.
+fn orientation_preview(ui: &mut Ui, display: &Display) {
+    let (width, height) = display.mode_size();
+    let aspect = if width > 0.0 && height > 0.0 {
+        (height / width) as f32
+    } else {
+        9.0 / 16.0
+    };
+    let size = egui::Vec2::new(80.0, 80.0 * aspect);
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, Color32::from_gray(60));
+
+    let (up, right) = display.transform.picture_axes();
+    let unit = rect.height().min(rect.width()) * 0.35;
+    let point = |u: f32, v: f32| {
+        rect.center() + egui::Vec2::new(u * right.0 + v * up.0, u * right.1 + v * up.1) * unit
+    };
+    let stroke = egui::Stroke::new(2.0, Color32::WHITE);
+    painter.line_segment([point(-0.3, -1.0), point(-0.3, 1.0)], stroke);
+    painter.line_segment([point(-0.3, 1.0), point(0.5, 1.0)], stroke);
+    painter.line_segment([point(-0.3, 0.1), point(0.3, 0.1)], stroke);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::wlr_randr;
+    use crate::runner::runner::FakeRunner;
+    use crate::sysfs::sysfs::fixture_sysfs;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn parsed() -> Displays {
+        let outputs = wlr_randr::parse(&fixture("wlr-randr")).unwrap();
+        Displays::from_outputs(outputs, &Backend::default())
+    }
+
+    fn fixture(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/commands")
+            .join(name);
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn parser_wlr_randr() {
+        let displays = parsed();
+        assert_eq!(displays.displays.len(), 2);
+        let edp = displays.displays.get("eDP-1").unwrap();
+        assert!(edp.enable);
+        assert_eq!(edp.physical_size.trim(), "290x170 mm");
+        assert_eq!(edp.mode.len(), 3);
+        assert_eq!(edp.now_mode.resolution, "1920x1080");
+        assert_eq!(edp.now_mode.refresh, 60.02);
+        assert_eq!(edp.scale, 1.25);
+        assert_eq!(edp.logical_size(), (1536.0, 864.0));
+        let hdmi = displays.displays.get("HDMI-A-1").unwrap();
+        assert_eq!(hdmi.position, (1536, 0));
+        assert!(hdmi.transform == Transform::Normal);
+    }
+
+    #[test]
+    fn flipped_rotations_parse_and_swap_size() {
+        let out = fixture("wlr-randr").replacen("Transform: normal", "Transform: flipped-270", 1);
+        let outputs = wlr_randr::parse(&out).unwrap();
+        let edp = &outputs[0];
+        assert_eq!(edp.transform, Transform::Flipped270);
+        assert_eq!(edp.logical_size(), (864.0, 1536.0));
+        assert_eq!(edp.state().transform, "flipped-270");
+        for transform in Transform::ALL {
+            assert_eq!(Transform::from_str(transform.as_str()), Some(transform));
         }
     }
 
-    fn from_str(s: &str) -> Option<Self> {
-        match s {
-            "normal" => Some(Transform::Normal),
-            "90" => Some(Transform::Roate90),
-            "180" => Some(Transform::Roate180),
-            "270" => Some(Transform::Roate270),
-            "flipped" => Some(Transform::Flipped),
-            _ => None,
+    #[test]
+    fn picture_axes_follow_rotation_and_flip() {
+        assert_eq!(Transform::Normal.picture_axes(), ((0.0, -1.0), (1.0, 0.0)));
+        assert_eq!(Transform::Roate90.picture_axes(), ((1.0, 0.0), (0.0, 1.0)));
+        assert_eq!(
+            Transform::Flipped.picture_axes(),
+            ((0.0, -1.0), (-1.0, 0.0))
+        );
+        assert_eq!(
+            Transform::Flipped90.picture_axes(),
+            ((1.0, 0.0), (0.0, -1.0))
+        );
+    }
+
+    #[test]
+    fn parser_reports_truncated_output() {
+        let out = "eDP-1 \"Sharp\"\n  Physical size: 290x170 mm\n";
+        assert!(matches!(wlr_randr::parse(out), Err(Error::Parse { .. })));
+    }
+
+    #[test]
+    fn set_mode_picks_closest_refresh() {
+        let mut displays = parsed();
+        let edp = displays.get_mut("eDP-1").unwrap();
+        edp.set_mode("1920x1080@48").unwrap();
+        assert_eq!(edp.now_mode.refresh, 48.0);
+        edp.set_mode("1920x1080@60").unwrap();
+        assert_eq!(edp.now_mode.refresh, 60.02);
+        edp.set_mode("1280x720").unwrap();
+        assert_eq!(edp.now_mode.resolution, "1280x720");
+        assert!(edp.set_mode("800x600").is_err());
+    }
+
+    #[test]
+    fn state_restore_round_trip() {
+        let mut displays = parsed();
+        let saved = displays.state();
+        let hdmi = displays.get_mut("HDMI-A-1").unwrap();
+        hdmi.set_mode("2560x1440").unwrap();
+        hdmi.set_position((0, 1080));
+        assert_ne!(displays.state(), saved);
+        displays.restore(&saved).unwrap();
+        assert_eq!(displays.state(), saved);
+    }
+
+    #[test]
+    fn output_without_modes_restores_and_applies() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/wlr-randr/headless.txt");
+        let out = std::fs::read_to_string(path).unwrap();
+        let fake = Arc::new(FakeRunner::new().with_output("wlr-randr", &out));
+        let mut displays = Displays::new(Runner::new(fake.clone()));
+        displays.init().unwrap();
+        let saved = displays.state();
+        assert_eq!(saved[0].mode, "");
+        displays.get_mut("HEADLESS-1").unwrap().set_scale(2.0);
+        displays.restore(&saved).unwrap();
+        assert_eq!(displays.state(), saved);
+        displays.apply_now().unwrap();
+        assert_eq!(
+            fake.calls().last().unwrap(),
+            "wlr-randr --output HEADLESS-1 --scale 1 --transform flipped-90 --on --pos -1920,0"
+        );
+    }
+
+    #[test]
+    fn apply_runs_wlr_randr() {
+        let fake = Arc::new(FakeRunner::new().with_output("wlr-randr", &fixture("wlr-randr")));
+        let mut displays = Displays::new(Runner::new(fake.clone()));
+        displays.init().unwrap();
+        displays.apply().unwrap();
+        let calls = fake.calls();
+        assert_eq!(calls.len(), 4);
+        assert_eq!(
+            calls[2],
+            "wlr-randr --output HDMI-A-1 --mode 3840x2160@60 --scale 2 --transform normal --on --pos 1536,0"
+        );
+    }
+
+    #[test]
+    fn adaptive_sync_only_where_advertised() {
+        let out = fixture("wlr-randr") + "  Adaptive Sync: disabled\n";
+        let fake = Arc::new(FakeRunner::new().with_output("wlr-randr", &out));
+        let mut displays = Displays::new(Runner::new(fake.clone()));
+        displays.init().unwrap();
+        let saved = displays.state();
+        let edp = displays.get_mut("eDP-1").unwrap();
+        assert_eq!(edp.adaptive_sync, None);
+        assert!(edp.set_adaptive_sync(true).is_err());
+        // wlr-randr can't switch pictures off
+        assert_eq!(edp.power, None);
+        assert!(edp.set_power(false).is_err());
+        let hdmi = displays.get_mut("HDMI-A-1").unwrap();
+        assert_eq!(hdmi.adaptive_sync, Some(false));
+        hdmi.set_adaptive_sync(true).unwrap();
+        hdmi.apply().unwrap();
+        assert_eq!(
+            fake.calls()[2],
+            "wlr-randr --output HDMI-A-1 --mode 3840x2160@60 --scale 2 --transform normal --on --pos 1536,0 --adaptive-sync enabled"
+        );
+        displays.restore(&saved).unwrap();
+        assert_eq!(displays.state(), saved);
+    }
+
+    #[test]
+    fn custom_mode_applies_and_round_trips() {
+        let fake = Arc::new(FakeRunner::new().with_output("wlr-randr", &fixture("wlr-randr")));
+        let mut displays = Displays::new(Runner::new(fake.clone()));
+        displays.init().unwrap();
+        let saved = displays.state();
+        let hdmi = displays.get_mut("HDMI-A-1").unwrap();
+        hdmi.set_custom_mode("2560x1080@74.991").unwrap();
+        hdmi.set_custom_mode("2560x1080@74.991").unwrap();
+        assert_eq!(hdmi.mode.iter().filter(|m| m.custom).count(), 1);
+        assert!(hdmi.add_custom_mode("2560x1080").is_err());
+        hdmi.apply().unwrap();
+        assert_eq!(
+            fake.calls().last().unwrap(),
+            "wlr-randr --output HDMI-A-1 --custom-mode 2560x1080@74.991 --scale 2 --transform normal --on --pos 1536,0"
+        );
+
+        let custom = displays.state();
+        assert!(custom[0].custom);
+        displays.restore(&saved).unwrap();
+        assert!(!displays.state()[0].custom);
+        displays.restore(&custom).unwrap();
+        assert_eq!(displays.state(), custom);
+    }
+
+    #[test]
+    fn bad_custom_modes_are_reported_not_fatal() {
+        let path = std::env::temp_dir()
+            .join(format!("rsettings-{}-bad-modes", std::process::id()))
+            .join("custom-modes.toml");
+        let _ = std::fs::remove_file(&path);
+        let store = CustomModeStore::new(path.clone());
+        let id = parsed().get_mut("HDMI-A-1").unwrap().id();
+        store.add(&id, "2560x1080@74.991").unwrap();
+        store.add(&id, "2560x1080").unwrap();
+        let fake = FakeRunner::new().with_output("wlr-randr", &fixture("wlr-randr"));
+        let mut displays = Displays::new(Runner::new(fake)).with_custom_modes(store);
+        displays.init().unwrap();
+        assert!(displays.take_error().is_some());
+        let hdmi = displays.get_mut("HDMI-A-1").unwrap();
+        assert_eq!(hdmi.mode.iter().filter(|m| m.custom).count(), 1);
+
+        std::fs::write(&path, "not toml [").unwrap();
+        let fake = FakeRunner::new().with_output("wlr-randr", &fixture("wlr-randr"));
+        let mut displays =
+            Displays::new(Runner::new(fake)).with_custom_modes(CustomModeStore::new(path));
+        displays.init().unwrap();
+        assert!(matches!(displays.take_error(), Some(Error::Parse { .. })));
+        assert_eq!(displays.displays.len(), 2);
+    }
+
+    #[test]
+    fn mirror_then_extend_restores_layout() {
+        let mut displays = parsed();
+        let before = displays.state();
+        assert!(displays.mirror(&["eDP-1"]).is_err());
+        displays.mirror(&["eDP-1", "HDMI-A-1"]).unwrap();
+        displays.mirror(&["HDMI-A-1", "eDP-1"]).unwrap();
+        let mirrored = displays.state();
+        assert_eq!(mirrored[0].position, mirrored[1].position);
+        assert_eq!(mirrored[0].mode, "1920x1080");
+        assert!(displays.check().is_ok());
+        displays.extend().unwrap();
+        assert_eq!(displays.state(), before);
+        // nothing to undo, side by side
+        displays.mirror(&["eDP-1", "HDMI-A-1"]).unwrap();
+        displays.extended = None;
+        displays.extend().unwrap();
+        assert!(layout::validate(displays.iter()).is_empty());
+    }
+
+    #[test]
+    fn refresh_keeps_edits_of_the_same_monitors() {
+        let fake = FakeRunner::new().with_output("wlr-randr", &fixture("wlr-randr"));
+        let mut displays = Displays::new(Runner::new(fake));
+        displays.init().unwrap();
+        displays.get_mut("HDMI-A-1").unwrap().set_scale(1.5);
+        // DP-2 plugged, HDMI-A-1 rotated and eDP-1 told its adaptive sync meanwhile
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/wlr-randr/0.4.txt");
+        let plugged = wlr_randr::parse(&std::fs::read_to_string(path).unwrap()).unwrap();
+        displays.merge(plugged.clone()).unwrap();
+        assert_eq!(displays.displays.len(), 3);
+        let hdmi = displays.get_mut("HDMI-A-1").unwrap();
+        assert_eq!((hdmi.scale, hdmi.transform), (1.5, Transform::Normal));
+        assert_eq!(
+            displays.get_mut("eDP-1").unwrap().adaptive_sync,
+            Some(false)
+        );
+        assert_eq!(displays.applied[1].scale, 2.0);
+
+        // unplugged, the edits go with it
+        displays.merge(plugged[..1].to_vec()).unwrap();
+        assert_eq!(displays.state(), displays.applied);
+        assert_eq!(displays.now, "eDP-1");
+    }
+
+    #[test]
+    fn edid_serial_is_the_monitor_id() {
+        // a custom mode saved under the monitor name, before EDID ids
+        let path = std::env::temp_dir()
+            .join(format!("rsettings-{}-legacy-modes", std::process::id()))
+            .join("custom-modes.toml");
+        let _ = std::fs::remove_file(&path);
+        let store = CustomModeStore::new(path);
+        store
+            .add("Dell Inc. DELL U2720Q 8K2JR83", "2560x1080@74.991")
+            .unwrap();
+        let fake = Arc::new(FakeRunner::new().with_output("wlr-randr", &fixture("wlr-randr")));
+        let mut displays = Displays::new(Runner::new(fake))
+            .with_sysfs(fixture_sysfs("display_edid"))
+            .with_custom_modes(store);
+        displays.init().unwrap();
+        let ids: Vec<String> = displays.iter().map(|d| d.id()).collect();
+        assert_eq!(ids, vec!["DEL-A0EC-8K2JR83", "Sharp Corporation 0x1453"]);
+        let hdmi = displays.get_mut("HDMI-A-1").unwrap();
+        assert_eq!(hdmi.monitor(), "Dell Inc. DELL U2720Q 8K2JR83");
+        assert!(hdmi.edid.as_ref().unwrap().hdr.is_some());
+        assert!(hdmi.mode.iter().any(|m| m.custom));
+    }
+
+    fn changed_displays() -> (Arc<FakeRunner>, Displays) {
+        let fake = Arc::new(FakeRunner::new().with_output("wlr-randr", &fixture("wlr-randr")));
+        let mut displays = Displays::new(Runner::new(fake.clone()));
+        displays.init().unwrap();
+        displays.get_mut("HDMI-A-1").unwrap().set_scale(1.5);
+        (fake, displays)
+    }
+
+    const HDMI_OLD: &str =
+        "wlr-randr --output HDMI-A-1 --mode 3840x2160@60 --scale 2 --transform normal --on --pos 1536,0";
+    const HDMI_NEW: &str =
+        "wlr-randr --output HDMI-A-1 --mode 3840x2160@60 --scale 1.5 --transform normal --on --pos 1536,0";
+
+    #[test]
+    fn apply_reverts_without_confirmation() {
+        let (fake, mut displays) = changed_displays();
+        displays
+            .apply_with_timeout(Duration::from_millis(10))
+            .unwrap();
+        let start = Instant::now();
+        while displays.pending.is_some() && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+            displays.poll();
         }
+        assert!(displays.remaining().is_none());
+        let calls = fake.calls();
+        assert_eq!(calls.len(), 6);
+        assert_eq!(calls[2], HDMI_NEW);
+        assert_eq!(calls[4], HDMI_OLD);
+        assert_eq!(displays.state(), displays.applied);
+        assert_eq!(displays.get_mut("HDMI-A-1").unwrap().scale, 2.0);
+    }
+
+    #[test]
+    fn keep_confirms_and_revert_restores() {
+        let (fake, mut displays) = changed_displays();
+        displays.apply_with_timeout(REVERT_TIMEOUT).unwrap();
+        assert!(displays.remaining().is_some());
+        let applied = displays.state();
+        // edited while waiting, not applied
+        displays.get_mut("eDP-1").unwrap().set_scale(1.0);
+        displays.keep().unwrap();
+        assert!(displays.remaining().is_none());
+        assert_eq!(fake.calls().len(), 4);
+        assert_eq!(displays.applied, applied);
+        displays.get_mut("eDP-1").unwrap().set_scale(1.25);
+        assert_eq!(displays.applied, displays.state());
+
+        displays.get_mut("HDMI-A-1").unwrap().set_scale(1.0);
+        displays.apply_with_timeout(REVERT_TIMEOUT).unwrap();
+        displays.revert().unwrap();
+        let calls = fake.calls();
+        assert_eq!(calls.len(), 8);
+        assert_eq!(calls[6], HDMI_NEW);
+        assert_eq!(displays.get_mut("HDMI-A-1").unwrap().scale, 1.5);
+    }
+
+    #[test]
+    fn pending_change_blocks_apply_and_reverts_on_drop() {
+        let (fake, mut displays) = changed_displays();
+        displays.apply_with_timeout(REVERT_TIMEOUT).unwrap();
+        assert!(!displays.can_apply());
+        assert!(displays.apply_with_timeout(REVERT_TIMEOUT).is_err());
+        drop(displays);
+        let calls = fake.calls();
+        assert_eq!(calls.len(), 6);
+        assert_eq!(calls[4], HDMI_OLD);
+    }
+
+    #[test]
+    fn apply_refuses_overlapping_layout() {
+        let (fake, mut displays) = changed_displays();
+        displays
+            .get_mut("HDMI-A-1")
+            .unwrap()
+            .set_position((100, 100));
+        assert!(matches!(displays.apply_now(), Err(Error::Layout(_))));
+        assert_eq!(fake.calls(), vec!["wlr-randr --json", "wlr-randr"]);
     }
 }
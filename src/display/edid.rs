@@ -0,0 +1,341 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::error::{Error, Result};
+use crate::sysfs::sysfs::SysFs;
+
+pub(super) const DRM: &str = "/sys/class/drm";
+const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+const BLOCK: usize = 128;
+
+/// Monitor identity and capabilities, decoded from its EDID
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Edid {
+    /// three letter PNP id, e.g. `DEL`
+    pub manufacturer: String,
+    pub product: u16,
+    /// serial number of the base block, 0 if unset
+    pub serial_number: u32,
+    /// serial number descriptor
+    pub serial: Option<String>,
+    /// monitor name descriptor
+    pub name: Option<String>,
+    pub year: u16,
+    /// image size, cm
+    pub size: (u8, u8),
+    /// first detailed timing, the preferred mode
+    pub native: Option<NativeMode>,
+    pub range: Option<RangeLimits>,
+    pub hdr: Option<Hdr>,
+    /// extended colorimetry, e.g. `BT2020RGB`
+    pub colorimetry: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NativeMode {
+    pub width: u16,
+    pub height: u16,
+    pub refresh: f64,
+}
+
+/// Timings the monitor accepts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RangeLimits {
+    /// vertical rate, Hz
+    pub v_min: u16,
+    pub v_max: u16,
+    /// horizontal rate, kHz
+    pub h_min: u16,
+    pub h_max: u16,
+    /// pixel clock, MHz
+    pub max_clock: u16,
+}
+
+/// HDR static metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hdr {
+    /// transfer functions, e.g. `PQ`
+    pub eotfs: Vec<&'static str>,
+    /// cd/m²
+    pub max_luminance: Option<f64>,
+    pub max_average: Option<f64>,
+    pub min_luminance: Option<f64>,
+}
+
+impl Edid {
+    /// Decode the base block and CTA extensions of `data`.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        // 1. a whole base block
+        let bad = |why: &str| Error::parse("edid", why);
+        if data.len() < BLOCK || data[..8] != HEADER {
+            return Err(bad("no edid header"));
+        }
+        if checksum(&data[..BLOCK]) != 0 {
+            return Err(bad("bad checksum"));
+        }
+
+        // 2. vendor and product
+        let id = u16::from_be_bytes([data[8], data[9]]);
+        let letter = |shift: u16| (b'A' - 1 + ((id >> shift) & 0x1f) as u8) as char;
+        let mut edid = Self {
+            manufacturer: [letter(10), letter(5), letter(0)].iter().collect(),
+            product: u16::from_le_bytes([data[10], data[11]]),
+            serial_number: u32::from_le_bytes([data[12], data[13], data[14], data[15]]),
+            year: 1990 + data[17] as u16,
+            size: (data[21], data[22]),
+            ..Default::default()
+        };
+
+        // 3. detailed timings and descriptors
+        for offset in [54, 72, 90, 108] {
+            edid.descriptor(&data[offset..offset + 18]);
+        }
+
+        // 4. extensions, whole ones only
+        for block in data[BLOCK..].chunks_exact(BLOCK) {
+            if block[0] == 0x02 && checksum(block) == 0 {
+                edid.cta(block);
+            }
+        }
+        Ok(edid)
+    }
+
+    /// Stable id of the monitor if it has a serial number, e.g.
+    /// `DEL-A0EC-8K2JR83`; two monitors of the same model differ.
+    pub fn id(&self) -> Option<String> {
+        let serial = match &self.serial {
+            Some(serial) => serial.clone(),
+            None if self.serial_number != 0 => self.serial_number.to_string(),
+            None => return None,
+        };
+        Some(format!(
+            "{}-{:04X}-{}",
+            self.manufacturer, self.product, serial
+        ))
+    }
+
+    /// maker, from the PNP id
+    pub fn maker(&self) -> &str {
+        match self.manufacturer.as_str() {
+            "ACR" => "Acer",
+            "AUO" => "AU Optronics",
+            "AUS" => "ASUS",
+            "APP" => "Apple",
+            "BNQ" => "BenQ",
+            "BOE" => "BOE",
+            "CMN" => "Chimei Innolux",
+            "DEL" => "Dell",
+            "EIZ" => "Eizo",
+            "GSM" => "LG",
+            "HWP" => "HP",
+            "LEN" => "Lenovo",
+            "PHL" => "Philips",
+            "SAM" => "Samsung",
+            "SDC" => "Samsung Display",
+            "SHP" => "Sharp",
+            "SNY" => "Sony",
+            "VSC" => "ViewSonic",
+            other => other,
+        }
+    }
+
+    /// diagonal of the image, inches
+    pub fn diagonal(&self) -> f64 {
+        let (w, h) = (self.size.0 as f64, self.size.1 as f64);
+        (w * w + h * h).sqrt() / 2.54
+    }
+
+    fn descriptor(&mut self, d: &[u8]) {
+        // 1. detailed timing
+        let clock = u16::from_le_bytes([d[0], d[1]]);
+        if clock != 0 {
+            if self.native.is_none() {
+                let h_active = d[2] as u32 | ((d[4] as u32 >> 4) << 8);
+                let h_blank = d[3] as u32 | ((d[4] as u32 & 0xf) << 8);
+                let v_active = d[5] as u32 | ((d[7] as u32 >> 4) << 8);
+                let v_blank = d[6] as u32 | ((d[7] as u32 & 0xf) << 8);
+                let total = ((h_active + h_blank) * (v_active + v_blank)) as f64;
+                self.native = Some(NativeMode {
+                    width: h_active as u16,
+                    height: v_active as u16,
+                    refresh: clock as f64 * 10_000.0 / total,
+                });
+            }
+            return;
+        }
+
+        // 2. display descriptors
+        match d[3] {
+            0xff => self.serial = text(&d[5..]),
+            0xfc => self.name = text(&d[5..]),
+            0xfd => {
+                let plus = |bit: u8| if d[4] & bit != 0 { 255 } else { 0 };
+                self.range = Some(RangeLimits {
+                    v_min: d[5] as u16 + plus(0x01),
+                    v_max: d[6] as u16 + plus(0x02),
+                    h_min: d[7] as u16 + plus(0x04),
+                    h_max: d[8] as u16 + plus(0x08),
+                    max_clock: d[9] as u16 * 10,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// data blocks of a CTA-861 extension
+    fn cta(&mut self, block: &[u8]) {
+        let end = (block[2] as usize).clamp(4, BLOCK - 1);
+        let mut i = 4;
+        while i < end {
+            let tag = block[i] >> 5;
+            let len = (block[i] & 0x1f) as usize;
+            let data = &block[(i + 1).min(end)..(i + 1 + len).min(end)];
+            i += 1 + len;
+            // extended tag blocks only
+            if tag != 7 || data.is_empty() {
+                continue;
+            }
+            match data[0] {
+                0x05 if data.len() >= 3 => {
+                    let names = [
+                        "xvYCC601",
+                        "xvYCC709",
+                        "sYCC601",
+                        "opYCC601",
+                        "opRGB",
+                        "BT2020cYCC",
+                        "BT2020YCC",
+                        "BT2020RGB",
+                    ];
+                    self.colorimetry = bits(data[1], &names);
+                    if data[2] & 0x80 != 0 {
+                        self.colorimetry.push("DCI-P3");
+                    }
+                }
+                0x06 if data.len() >= 3 => {
+                    let byte = |n: usize| data.get(n).copied().filter(|b| *b != 0);
+                    let luminance = |cv: u8| 50.0 * 2f64.powf(cv as f64 / 32.0);
+                    let max_luminance = byte(3).map(luminance);
+                    self.hdr = Some(Hdr {
+                        eotfs: bits(data[1], &["SDR", "HDR", "PQ", "HLG"]),
+                        max_luminance,
+                        max_average: byte(4).map(luminance),
+                        min_luminance: byte(5)
+                            .zip(max_luminance)
+                            .map(|(cv, max)| max * (cv as f64 / 255.0).powi(2) / 100.0),
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn checksum(block: &[u8]) -> u8 {
+    block.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+/// descriptor text, ended by a newline and padded with spaces
+fn text(bytes: &[u8]) -> Option<String> {
+    let end = bytes
+        .iter()
+        .position(|b| *b == b'\n')
+        .unwrap_or(bytes.len());
+    let s = String::from_utf8_lossy(&bytes[..end]).trim().to_string();
+    (!s.is_empty()).then_some(s)
+}
+
+fn bits(byte: u8, names: &[&'static str]) -> Vec<&'static str> {
+    names
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| byte & (1 << i) != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// connector of a `/sys/class/drm/cardN-CONNECTOR` directory
+pub(super) fn connector(path: &Path) -> Option<&str> {
+    match path.file_name()?.to_str()?.split_once('-')? {
+        (card, connector) if card.starts_with("card") => Some(connector),
+        _ => None,
+    }
+}
+
+/// EDID of every connected connector, by connector name (`card1-DP-1` is `DP-1`).
+///
+/// Connectors without a readable EDID are left out.
+pub fn read_all(sysfs: &SysFs) -> BTreeMap<String, Edid> {
+    let mut edids = BTreeMap::new();
+    for path in sysfs.read_dir(DRM).unwrap_or_default() {
+        let connector = match connector(&path) {
+            Some(connector) => connector.to_string(),
+            None => continue,
+        };
+        if let Ok(edid) = sysfs
+            .read(Path::new(&path).join("edid"))
+            .and_then(|d| Edid::parse(&d))
+        {
+            edids.insert(connector, edid);
+        }
+    }
+    edids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysfs::sysfs::fixture_sysfs;
+
+    #[test]
+    fn decode_monitor_with_cta_extension() {
+        let edids = read_all(&fixture_sysfs("edid_read_all"));
+        assert_eq!(edids.keys().collect::<Vec<_>>(), vec!["HDMI-A-1", "eDP-1"]);
+        let dell = &edids["HDMI-A-1"];
+        assert_eq!(dell.maker(), "Dell");
+        assert_eq!(dell.name.as_deref(), Some("DELL U2720Q"));
+        assert_eq!(dell.id().as_deref(), Some("DEL-A0EC-8K2JR83"));
+        assert_eq!((dell.year, dell.size), (2020, (60, 34)));
+        let native = dell.native.unwrap();
+        assert_eq!((native.width, native.height), (3840, 2160));
+        assert!((native.refresh - 60.0).abs() < 0.01);
+        assert_eq!(
+            dell.range,
+            Some(RangeLimits {
+                v_min: 24,
+                v_max: 86,
+                h_min: 30,
+                h_max: 140,
+                max_clock: 600,
+            })
+        );
+        let hdr = dell.hdr.as_ref().unwrap();
+        assert_eq!(hdr.eotfs, vec!["SDR", "PQ"]);
+        assert_eq!(hdr.max_luminance, Some(400.0));
+        assert!((hdr.min_luminance.unwrap() - 0.098).abs() < 0.001);
+        assert_eq!(
+            dell.colorimetry,
+            vec!["BT2020cYCC", "BT2020YCC", "BT2020RGB", "DCI-P3"]
+        );
+    }
+
+    #[test]
+    fn panel_without_serial_has_no_id() {
+        let edids = read_all(&fixture_sysfs("edid_panel"));
+        let panel = &edids["eDP-1"];
+        assert_eq!(panel.maker(), "Sharp");
+        assert_eq!(panel.product, 0x1453);
+        assert_eq!(panel.id(), None);
+        assert!(panel.hdr.is_none());
+        assert!((panel.diagonal() - 13.2).abs() < 0.1);
+    }
+
+    #[test]
+    fn reject_broken_edid() {
+        let sysfs = fixture_sysfs("edid_broken");
+        let mut data = sysfs.read(format!("{}/card1-eDP-1/edid", DRM)).unwrap();
+        assert!(Edid::parse(&data[..100]).is_err());
+        data[20] ^= 0xff;
+        assert!(matches!(Edid::parse(&data), Err(Error::Parse { .. })));
+    }
+}
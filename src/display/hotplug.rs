@@ -0,0 +1,93 @@
+//! Notice monitors being plugged and unplugged, to refresh the panel.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use super::backend::Backend;
+use super::edid::{self, DRM};
+use crate::sysfs::sysfs::SysFs;
+
+/// how often the DRM connectors are looked at when the backend can't watch
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Connectors with a monitor plugged, e.g. `HDMI-A-1`
+pub fn connected(sysfs: &SysFs) -> BTreeSet<String> {
+    sysfs
+        .read_dir(DRM)
+        .unwrap_or_default()
+        .iter()
+        .filter(|path| {
+            sysfs
+                .read_to_string(Path::new(path).join("status"))
+                .is_ok_and(|status| status.trim() == "connected")
+        })
+        .filter_map(|path| edid::connector(path).map(str::to_string))
+        .collect()
+}
+
+/// Watch the outputs on a thread: the receiver gets `()` at every
+/// change, after which `notify` is called to wake the reader.
+///
+/// The compositor tells through the backend when it can, else the DRM
+/// connectors below `sysfs` are looked at every `interval`. The thread
+/// ends once the receiver is dropped and another change comes.
+pub fn watch<F>(backend: Backend, sysfs: SysFs, interval: Duration, notify: F) -> Receiver<()>
+where
+    F: Fn() + Send + 'static,
+{
+    let (sender, receiver) = channel();
+    // changes from now on
+    let mut last = connected(&sysfs);
+    thread::spawn(move || {
+        let mut changed = || {
+            let listening = sender.send(()).is_ok();
+            notify();
+            listening
+        };
+
+        // 1. from the compositor
+        if backend.watch(&mut changed).is_ok() {
+            return;
+        }
+
+        // 2. from the kernel
+        loop {
+            thread::sleep(interval);
+            let now = connected(&sysfs);
+            if now != last {
+                last = now;
+                if !changed() {
+                    return;
+                }
+            }
+        }
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::wlr_randr::WlrRandr;
+    use crate::runner::runner::{FakeRunner, Runner};
+    use crate::sysfs::sysfs::fixture_sysfs;
+
+    #[test]
+    fn plugging_a_connector_is_noticed() {
+        let sysfs = fixture_sysfs("hotplug");
+        let plugged = vec!["HDMI-A-1".to_string(), "eDP-1".to_string()];
+        assert_eq!(connected(&sysfs).into_iter().collect::<Vec<_>>(), plugged);
+
+        // wlr-randr can't watch, so sysfs is polled
+        let backend = Backend::new(WlrRandr::new(Runner::new(FakeRunner::new())));
+        let changes = watch(backend, sysfs.clone(), Duration::from_millis(10), || {});
+        sysfs
+            .write("/sys/class/drm/card1-DP-1/status", "connected\n")
+            .unwrap();
+        assert!(changes.recv_timeout(Duration::from_secs(5)).is_ok());
+        assert_eq!(connected(&sysfs).len(), 3);
+    }
+}
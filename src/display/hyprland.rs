@@ -0,0 +1,237 @@
+use serde::Deserialize;
+
+use super::backend::DisplayBackend;
+use super::display::{Display, DisplayState, Resolution, Transform};
+use crate::error::error::{Error, Result};
+use crate::runner::runner::{Cmd, Runner};
+
+/// Env var Hyprland sets for its IPC socket
+pub const HYPRLAND_ENV: &str = "HYPRLAND_INSTANCE_SIGNATURE";
+
+/// Backend for Hyprland, through `hyprctl`
+pub struct Hyprland {
+    runner: Runner,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Monitor {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    width: u32,
+    #[serde(default)]
+    height: u32,
+    #[serde(default)]
+    refresh_rate: f64,
+    #[serde(default)]
+    x: i32,
+    #[serde(default)]
+    y: i32,
+    #[serde(default)]
+    scale: f64,
+    /// `wl_output` transform, 0 to 7
+    #[serde(default)]
+    transform: usize,
+    #[serde(default)]
+    vrr: Option<bool>,
+    /// picture on
+    #[serde(default)]
+    dpms_status: Option<bool>,
+    #[serde(default)]
+    disabled: bool,
+    /// `WxH@HZHz`
+    #[serde(default)]
+    available_modes: Vec<String>,
+}
+
+impl Monitor {
+    fn display(self) -> Result<Display> {
+        let mode = |s: &str| {
+            let (resolution, refresh) = s.trim_end_matches("Hz").split_once('@')?;
+            Some(Resolution {
+                resolution: resolution.to_string(),
+                refresh: refresh.parse().ok()?,
+                custom: false,
+            })
+        };
+        let modes = self
+            .available_modes
+            .iter()
+            .map(|s| mode(s).ok_or_else(|| Error::parse("hyprland mode", s.as_str())))
+            .collect::<Result<Vec<_>>>()?;
+        let now = Resolution {
+            resolution: format!("{}x{}", self.width, self.height),
+            refresh: (self.refresh_rate * 1000.0).round() / 1000.0,
+            custom: false,
+        };
+        // the closest advertised mode, hyprctl rounds differently
+        let now_mode = modes
+            .iter()
+            .filter(|m| m.resolution == now.resolution)
+            .min_by(|a, b| {
+                (a.refresh - now.refresh)
+                    .abs()
+                    .total_cmp(&(b.refresh - now.refresh).abs())
+            })
+            .cloned()
+            .unwrap_or(now);
+        Ok(Display {
+            name: self.name,
+            description: self.description,
+            enable: !self.disabled,
+            mode: modes,
+            now_mode,
+            position: (self.x, self.y),
+            transform: *Transform::ALL
+                .get(self.transform)
+                .ok_or_else(|| Error::parse("transform", self.transform.to_string()))?,
+            scale: if self.scale > 0.0 { self.scale } else { 1.0 },
+            adaptive_sync: self.vrr,
+            power: self.dpms_status,
+            ..Default::default()
+        })
+    }
+}
+
+impl Hyprland {
+    pub fn new(runner: Runner) -> Self {
+        Self { runner }
+    }
+
+    /// `hyprctl keyword monitor` command setting `state`
+    fn cmd(state: &DisplayState) -> Cmd {
+        let cmd = Cmd::new("hyprctl").arg("keyword").arg("monitor");
+        if !state.enable {
+            return cmd.arg(format!("{},disable", state.name));
+        }
+        let transform = Transform::ALL
+            .iter()
+            .position(|t| t.as_str() == state.transform)
+            .unwrap_or_default();
+        let mut rule = format!(
+            "{},{}@{},{}x{},{},transform,{}",
+            state.name,
+            state.mode,
+            state.refresh,
+            state.position.0,
+            state.position.1,
+            state.scale,
+            transform
+        );
+        if let Some(on) = state.adaptive_sync {
+            rule += if on { ",vrr,1" } else { ",vrr,0" };
+        }
+        cmd.arg(rule)
+    }
+
+    /// hyprctl exits with success on bad rules too, it answers `ok` to good ones
+    fn run(&self, cmd: &Cmd) -> Result<()> {
+        let out = self.runner.stdout(cmd)?;
+        if out.trim() != "ok" {
+            return Err(Error::Command {
+                cmd: cmd.line(),
+                stderr: out,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// monitors of `hyprctl monitors all -j`
+pub fn parse(json: &str) -> Result<Vec<Display>> {
+    let monitors: Vec<Monitor> =
+        serde_json::from_str(json).map_err(|e| Error::parse("hyprland monitors", e.to_string()))?;
+    monitors.into_iter().map(Monitor::display).collect()
+}
+
+impl DisplayBackend for Hyprland {
+    fn name(&self) -> &'static str {
+        "hyprland"
+    }
+
+    fn outputs(&self) -> Result<Vec<Display>> {
+        let json = self
+            .runner
+            .stdout(&Cmd::new("hyprctl").args(["monitors", "all", "-j"]))?;
+        parse(&json)
+    }
+
+    /// Hyprland can't check a configuration without applying it
+    fn test(&self, _states: &[DisplayState]) -> Result<()> {
+        Ok(())
+    }
+
+    fn apply(&self, states: &[DisplayState]) -> Result<()> {
+        for state in states {
+            self.run(&Self::cmd(state))?;
+        }
+        Ok(())
+    }
+
+    fn set_power(&self, name: &str, on: bool) -> Result<()> {
+        let power = if on { "on" } else { "off" };
+        self.run(&Cmd::new("hyprctl").args(["dispatch", "dpms", power, name]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::backend::Backend;
+    use crate::display::display::Displays;
+    use crate::runner::runner::FakeRunner;
+    use crate::settings::settings::Settings;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn fake() -> FakeRunner {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/hyprland/monitors.json");
+        let json = std::fs::read_to_string(path).unwrap();
+        FakeRunner::new().with_output("hyprctl monitors all -j", &json)
+    }
+
+    #[test]
+    fn parse_monitors() {
+        let outputs = Hyprland::new(Runner::new(fake())).outputs().unwrap();
+        assert_eq!(outputs.len(), 2);
+        let edp = &outputs[0];
+        assert_eq!(edp.monitor(), "Sharp Corporation 0x1453");
+        assert_eq!(edp.now_mode.refresh, 60.02);
+        assert_eq!(edp.mode.len(), 3);
+        assert_eq!(edp.adaptive_sync, Some(false));
+        assert_eq!(edp.power, Some(true));
+        let hdmi = &outputs[1];
+        assert_eq!(hdmi.transform, Transform::Roate90);
+        assert_eq!(hdmi.logical_size(), (1080.0, 1920.0));
+        assert_eq!(hdmi.mode[2].refresh, 59.95);
+    }
+
+    #[test]
+    fn apply_runs_hyprctl_keyword() {
+        let ok = "hyprctl keyword monitor HDMI-A-1,2560x1440@59.95,1536x0,1,transform,1,vrr,1";
+        let fake = Arc::new(fake().with_output(ok, "ok\n"));
+        let backend = Backend::new(Hyprland::new(Runner::new(fake.clone())));
+        let mut displays = Displays::with_backend(backend);
+        displays.init().unwrap();
+        let hdmi = displays.get_mut("HDMI-A-1").unwrap();
+        hdmi.set_mode("2560x1440").unwrap();
+        hdmi.set_scale(1.0);
+        hdmi.apply().unwrap();
+        assert_eq!(fake.calls()[1], ok);
+        // unknown to the fake, so no `ok`
+        hdmi.set_enable(false);
+        assert!(matches!(hdmi.apply(), Err(Error::Command { .. })));
+    }
+
+    #[test]
+    fn power_runs_hyprctl_dpms() {
+        let off = "hyprctl dispatch dpms off HDMI-A-1";
+        let fake = Arc::new(fake().with_output(off, "ok\n"));
+        let backend = Hyprland::new(Runner::new(fake.clone()));
+        backend.set_power("HDMI-A-1", false).unwrap();
+        assert_eq!(fake.calls(), vec![off]);
+        assert!(backend.set_power("HDMI-A-1", true).is_err());
+    }
+}
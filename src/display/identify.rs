@@ -0,0 +1,311 @@
+//! "Identify displays": a layer-shell overlay on every output with its
+//! connector name, drawn with a small built-in bitmap font.
+
+use std::cell::RefCell;
+use std::os::unix::io::AsRawFd;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use wayland_client::protocol::{wl_compositor, wl_shm};
+use wayland_client::Display as WlDisplay;
+use wayland_protocols::wlr::unstable::layer_shell::v1::client::{
+    zwlr_layer_shell_v1::{self, ZwlrLayerShellV1},
+    zwlr_layer_surface_v1::{self, Anchor},
+};
+
+use super::display::Display;
+use super::wayland::{self, roundtrip, shm_file};
+use crate::error::error::{Error, Result};
+
+/// how long the overlay stays
+pub const IDENTIFY_TIME: Duration = Duration::from_secs(4);
+
+/// translucent dark background, premultiplied ARGB
+const BACKGROUND: u32 = 0xc018_1818;
+const TEXT: u32 = 0xffff_ffff;
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+/// What the overlay of one output shows
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    /// connector, e.g. `DP-1`
+    pub name: String,
+    pub description: String,
+    /// matches outputs of compositors without `wl_output.name`
+    pub position: (i32, i32),
+}
+
+impl Label {
+    pub fn new(display: &Display) -> Self {
+        Self {
+            name: display.name.clone(),
+            description: display.monitor(),
+            position: display.position,
+        }
+    }
+}
+
+/// one overlay surface, drawn once the compositor tells its size
+struct Overlay {
+    label: Label,
+    size: Option<(u32, u32)>,
+    drawn: bool,
+    closed: bool,
+}
+
+/// Show `labels` on their outputs for `time`, blocking.
+pub fn show(labels: &[Label], time: Duration) -> Result<()> {
+    // 1. connect and bind the globals
+    let display = WlDisplay::connect_to_env().map_err(|e| Error::Wayland(e.to_string()))?;
+    let mut queue = display.create_event_queue();
+    let attached = (*display).clone().attach(queue.token());
+    let (globals, outputs) = wayland::globals(&attached);
+    roundtrip(&mut queue)?;
+    let missing = |name: &str| Error::NotFound(name.to_string());
+    let compositor = globals
+        .instantiate_range::<wl_compositor::WlCompositor>(1, 4)
+        .map_err(|_| missing("wl_compositor"))?;
+    let shm = globals
+        .instantiate_exact::<wl_shm::WlShm>(1)
+        .map_err(|_| missing("wl_shm"))?;
+    let layer_shell = globals
+        .instantiate_range::<ZwlrLayerShellV1>(1, 4)
+        .map_err(|_| missing("zwlr_layer_shell_v1"))?;
+    roundtrip(&mut queue)?;
+
+    // 2. a fullscreen overlay surface on every output with a label
+    let mut surfaces = Vec::new();
+    for output in outputs.borrow().iter() {
+        let label = match labels.iter().find(|l| output.is(&l.name, Some(l.position))) {
+            Some(label) => label.clone(),
+            None => continue,
+        };
+        let surface = compositor.create_surface();
+        let layer = layer_shell.get_layer_surface(
+            &surface,
+            Some(&output.proxy),
+            zwlr_layer_shell_v1::Layer::Overlay,
+            "rsettings-identify".to_string(),
+        );
+        layer.set_anchor(Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right);
+        layer.set_exclusive_zone(-1);
+        let overlay = Rc::new(RefCell::new(Overlay {
+            label,
+            size: None,
+            drawn: false,
+            closed: false,
+        }));
+        let events = overlay.clone();
+        layer.quick_assign(move |layer, event, _| match event {
+            zwlr_layer_surface_v1::Event::Configure {
+                serial,
+                width,
+                height,
+            } => {
+                layer.ack_configure(serial);
+                let mut overlay = events.borrow_mut();
+                if overlay.size != Some((width, height)) {
+                    overlay.size = Some((width, height));
+                    overlay.drawn = false;
+                }
+            }
+            zwlr_layer_surface_v1::Event::Closed => events.borrow_mut().closed = true,
+            _ => {}
+        });
+        surface.commit();
+        surfaces.push((surface, layer, overlay));
+    }
+    if surfaces.is_empty() {
+        return Err(Error::NotFound("outputs to identify".to_string()));
+    }
+
+    // 3. draw on configure until the time is up
+    let deadline = Instant::now() + time;
+    while Instant::now() < deadline {
+        // files open and buffers kept until sent
+        let mut sent = Vec::new();
+        for (surface, _, overlay) in &surfaces {
+            let mut overlay = overlay.borrow_mut();
+            let (width, height) = match overlay.size {
+                Some(size) if !overlay.drawn && !overlay.closed => size,
+                _ => continue,
+            };
+            let pixels = render(width as usize, height as usize, &overlay.label);
+            let file = shm_file(&pixels)?;
+            let pool = shm.create_pool(file.as_raw_fd(), pixels.len() as i32);
+            let buffer = pool.create_buffer(
+                0,
+                width as i32,
+                height as i32,
+                width as i32 * 4,
+                wl_shm::Format::Argb8888,
+            );
+            pool.destroy();
+            surface.attach(Some(&buffer), 0, 0);
+            surface.damage(0, 0, width as i32, height as i32);
+            surface.commit();
+            overlay.drawn = true;
+            sent.push((file, buffer));
+        }
+        if surfaces.iter().all(|(_, _, o)| o.borrow().closed) {
+            break;
+        }
+        roundtrip(&mut queue)?;
+        for (file, buffer) in sent {
+            drop(file);
+            buffer.destroy();
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    // 4. clean up
+    for (surface, layer, _) in surfaces {
+        layer.destroy();
+        surface.destroy();
+    }
+    roundtrip(&mut queue)
+}
+
+/// `label` centered on a `width`x`height` ARGB8888 picture
+fn render(width: usize, height: usize, label: &Label) -> Vec<u8> {
+    let mut pixels = vec![BACKGROUND; width * height];
+
+    // 1. connector large, description below it at a third of the size
+    let big = (height / 6 / GLYPH_HEIGHT)
+        .min(width * 2 / 3 / text_width(&label.name, 1).max(1))
+        .max(1);
+    let small = (big / 3)
+        .min(width * 9 / 10 / text_width(&label.description, 1).max(1))
+        .max(1);
+    let total = GLYPH_HEIGHT * big + GLYPH_HEIGHT * small * 2;
+    let top = height.saturating_sub(total) / 2;
+    let mut draw = |text: &str, y: usize, scale: usize| {
+        let x = width.saturating_sub(text_width(text, scale)) / 2;
+        draw_text(&mut pixels, width, (x, y), text, scale);
+    };
+    draw(&label.name, top, big);
+    draw(
+        &label.description,
+        top + GLYPH_HEIGHT * (big + small),
+        small,
+    );
+
+    // 2. little endian words, as wl_shm wants them
+    pixels.iter().flat_map(|p| p.to_le_bytes()).collect()
+}
+
+/// width of `text` in pixels, a column of space between glyphs
+fn text_width(text: &str, scale: usize) -> usize {
+    let n = text.chars().count();
+    (n * (GLYPH_WIDTH + 1)).saturating_sub(1) * scale
+}
+
+fn draw_text(pixels: &mut [u32], width: usize, at: (usize, usize), text: &str, scale: usize) {
+    let height = pixels.len() / width;
+    for (i, c) in text.chars().enumerate() {
+        let left = at.0 + i * (GLYPH_WIDTH + 1) * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (0x10 >> col) == 0 {
+                    continue;
+                }
+                for y in at.1 + row * scale..at.1 + (row + 1) * scale {
+                    for x in left + col * scale..left + (col + 1) * scale {
+                        if x < width && y < height {
+                            pixels[y * width + x] = TEXT;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 5x7 glyph of `c`, a row per byte with the leftmost pixel in bit 4;
+/// lowercase is drawn as uppercase, unknown characters as `?`
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        'A' => [0x0e, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'D' => [0x1e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1e],
+        'E' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+        'F' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+        'G' => [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+        'H' => [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'I' => [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'P' => [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+        'Q' => [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+        'X' => [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x0a, 0x04, 0x04, 0x04, 0x04],
+        'Z' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        ' ' => [0; GLYPH_HEIGHT],
+        '-' => [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08],
+        ':' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '/' => [0x01, 0x01, 0x02, 0x04, 0x08, 0x10, 0x10],
+        '+' => [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00],
+        '"' => [0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00, 0x00],
+        _ => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_centers_the_connector() {
+        let label = Label {
+            name: "DP-1".to_string(),
+            description: "Dell Inc. DELL U2720Q".to_string(),
+            position: (0, 0),
+        };
+        let (width, height) = (640, 360);
+        let bytes = render(width, height, &label);
+        assert_eq!(bytes.len(), width * height * 4);
+        let pixels: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|p| u32::from_le_bytes([p[0], p[1], p[2], p[3]]))
+            .collect();
+        assert_eq!(pixels[0], BACKGROUND);
+        let lit: Vec<usize> = (0..pixels.len()).filter(|i| pixels[*i] == TEXT).collect();
+        let columns = lit.iter().map(|i| i % width);
+        let (left, right) = (columns.clone().min().unwrap(), columns.max().unwrap());
+        assert!((left as i64 + right as i64 - width as i64).abs() <= 8);
+    }
+
+    #[test]
+    fn unknown_characters_draw_a_question_mark() {
+        assert_eq!(glyph('é'), glyph('?'));
+        assert_eq!(glyph('d'), glyph('D'));
+        assert_eq!(text_width("DP-1", 2), (4 * 6 - 1) * 2);
+    }
+}
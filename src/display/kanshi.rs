@@ -0,0 +1,539 @@
+use std::fs;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+
+use super::display::{Display, Displays};
+use crate::error::error::{Error, Result};
+use crate::settings::settings::config_home;
+
+/// Settings of the outputs matching `criteria`, unset ones are left alone
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KanshiOutput {
+    /// connector name, monitor description or `*`
+    pub criteria: String,
+    pub enable: Option<bool>,
+    /// `WxH` or `WxH@HZ`
+    pub mode: Option<String>,
+    /// mode given with `--custom`
+    pub custom: bool,
+    pub position: Option<(i32, i32)>,
+    pub scale: Option<f64>,
+    pub transform: Option<String>,
+    pub adaptive_sync: Option<bool>,
+}
+
+/// One `profile` block of a kanshi config
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KanshiProfile {
+    /// `None` for anonymous profiles
+    pub name: Option<String>,
+    pub outputs: Vec<KanshiOutput>,
+    /// `exec` commands, run by kanshi when the profile is applied
+    pub exec: Vec<String>,
+}
+
+/// `$XDG_CONFIG_HOME/kanshi/config`, default `~/.config/kanshi/config`
+pub fn config_path() -> PathBuf {
+    config_home().join("kanshi/config")
+}
+
+/// profiles of the kanshi config at `path`
+pub fn read(path: &Path) -> Result<Vec<KanshiProfile>> {
+    let s = fs::read_to_string(path).map_err(|source| Error::File {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    parse(&s)
+}
+
+/// Parse the profiles of a kanshi config; global `output` blocks and
+/// `include` lines are skipped.
+pub fn parse(s: &str) -> Result<Vec<KanshiProfile>> {
+    let mut lexer = Lexer::new(s);
+    let mut profiles = Vec::new();
+    while let Some(token) = lexer.next()? {
+        match token {
+            Token::Newline => {}
+            Token::Word(w) if w == "profile" => {
+                let name = match lexer.next()? {
+                    Some(Token::Word(name)) => {
+                        lexer.expect_open()?;
+                        Some(name)
+                    }
+                    Some(Token::Open) => None,
+                    _ => return Err(Error::parse("kanshi profile", lexer.line())),
+                };
+                profiles.push(lexer.profile(name)?);
+            }
+            Token::Open => profiles.push(lexer.profile(None)?),
+            Token::Word(w) if w == "output" => lexer.skip_block()?,
+            Token::Word(w) if w == "include" => {
+                lexer.rest_of_line();
+            }
+            _ => return Err(Error::parse("kanshi config", lexer.line())),
+        }
+    }
+    Ok(profiles)
+}
+
+impl KanshiProfile {
+    /// the current settings of `displays` as profile `name`
+    pub fn capture(name: &str, displays: &Displays) -> Self {
+        let outputs = displays
+            .iter()
+            .map(|d| {
+                let state = d.state();
+                if !state.enable {
+                    return KanshiOutput {
+                        criteria: d.monitor(),
+                        enable: Some(false),
+                        ..Default::default()
+                    };
+                }
+                KanshiOutput {
+                    criteria: d.monitor(),
+                    enable: Some(true),
+                    mode: Some(format!("{}@{}Hz", state.mode, state.refresh)),
+                    custom: state.custom,
+                    position: Some(state.position),
+                    scale: Some(state.scale),
+                    transform: Some(state.transform),
+                    adaptive_sync: state.adaptive_sync,
+                }
+            })
+            .collect();
+        Self {
+            name: Some(name.to_string()),
+            outputs,
+            exec: Vec::new(),
+        }
+    }
+
+    pub fn to_config(&self) -> String {
+        let mut s = match &self.name {
+            Some(name) => format!("profile {} {{\n", quote(name)),
+            None => "{\n".to_string(),
+        };
+        for output in &self.outputs {
+            s += &format!("    {}\n", output.to_config());
+        }
+        for exec in &self.exec {
+            s += &format!("    exec {}\n", exec);
+        }
+        s + "}\n"
+    }
+
+    /// connector each output of the profile is matched to, `None` unless
+    /// every connected monitor is matched by exactly one output
+    pub fn assign(&self, displays: &Displays) -> Option<Vec<String>> {
+        let displays: Vec<&Display> = displays.iter().collect();
+        if displays.len() != self.outputs.len() {
+            return None;
+        }
+        let mut used = vec![false; displays.len()];
+        let mut names = Vec::new();
+        if assign(&self.outputs, &displays, &mut used, &mut names) {
+            Some(names)
+        } else {
+            None
+        }
+    }
+
+    /// Set `displays` to this profile, for preview before applying.
+    pub fn restore(&self, displays: &mut Displays) -> Result<()> {
+        let names = self.assign(displays).ok_or_else(|| {
+            Error::NotFound(format!(
+                "connected monitors for kanshi profile {}",
+                self.name.as_deref().unwrap_or("(anonymous)")
+            ))
+        })?;
+        for (output, name) in self.outputs.iter().zip(names) {
+            output.restore(displays.get_mut(&name)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// backtracking match of `outputs` to unused `displays`, kanshi style
+fn assign(
+    outputs: &[KanshiOutput],
+    displays: &[&Display],
+    used: &mut [bool],
+    names: &mut Vec<String>,
+) -> bool {
+    let (output, rest) = match outputs.split_first() {
+        Some(first) => first,
+        None => return true,
+    };
+    for (i, display) in displays.iter().enumerate() {
+        if used[i] || !output.matches(display) {
+            continue;
+        }
+        used[i] = true;
+        names.push(display.name.clone());
+        if assign(rest, displays, used, names) {
+            return true;
+        }
+        used[i] = false;
+        names.pop();
+    }
+    false
+}
+
+impl KanshiOutput {
+    pub fn matches(&self, display: &Display) -> bool {
+        self.criteria == "*" || self.criteria == display.name || self.criteria == display.monitor()
+    }
+
+    /// Set the settings given in this output on `display`.
+    pub fn restore(&self, display: &mut Display) -> Result<()> {
+        match &self.mode {
+            Some(mode) if self.custom => display.set_custom_mode(mode)?,
+            Some(mode) => display.set_mode(mode)?,
+            None => {}
+        }
+        if let Some(position) = self.position {
+            display.set_position(position);
+        }
+        if let Some(scale) = self.scale {
+            display.set_scale(scale);
+        }
+        if let Some(transform) = &self.transform {
+            display.set_transform(transform)?;
+        }
+        if let Some(enable) = self.enable {
+            display.set_enable(enable);
+        }
+        if let Some(adaptive_sync) = self.adaptive_sync {
+            // kanshi ignores it too on outputs without VRR
+            let _ = display.set_adaptive_sync(adaptive_sync);
+        }
+        Ok(())
+    }
+
+    pub fn to_config(&self) -> String {
+        let mut s = format!("output {}", quote(&self.criteria));
+        match self.enable {
+            Some(true) => s += " enable",
+            Some(false) => s += " disable",
+            None => {}
+        }
+        if let Some(mode) = &self.mode {
+            let custom = if self.custom { "--custom " } else { "" };
+            s += &format!(" mode {}{}", custom, mode);
+        }
+        if let Some((x, y)) = self.position {
+            s += &format!(" position {},{}", x, y);
+        }
+        if let Some(scale) = self.scale {
+            s += &format!(" scale {}", scale);
+        }
+        if let Some(transform) = &self.transform {
+            s += &format!(" transform {}", transform);
+        }
+        if let Some(adaptive_sync) = self.adaptive_sync {
+            s += &format!(
+                " adaptive_sync {}",
+                if adaptive_sync { "on" } else { "off" }
+            );
+        }
+        s
+    }
+}
+
+/// quote `s` unless it is a single plain word
+fn quote(s: &str) -> String {
+    if !s.is_empty() && !s.contains(|c: char| c.is_whitespace() || "\"{}#\\".contains(c)) {
+        return s.to_string();
+    }
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Open,
+    Close,
+    Newline,
+}
+
+/// Splits a kanshi config into words, braces and line ends
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    /// current line, for errors
+    line: usize,
+    lines: Vec<&'a str>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(s: &'a str) -> Self {
+        Self {
+            chars: s.chars().peekable(),
+            line: 0,
+            lines: s.lines().collect(),
+        }
+    }
+
+    fn line(&self) -> String {
+        self.lines.get(self.line).unwrap_or(&"").trim().to_string()
+    }
+
+    fn next(&mut self) -> Result<Option<Token>> {
+        // 1. skip blanks and comments
+        while let Some(&c) = self.chars.peek() {
+            match c {
+                '#' => {
+                    self.rest_of_line();
+                }
+                c if c.is_whitespace() && c != '\n' => {
+                    self.chars.next();
+                }
+                _ => break,
+            }
+        }
+
+        // 2. one token
+        let token = match self.chars.next() {
+            None => return Ok(None),
+            Some('\n') => {
+                self.line += 1;
+                Token::Newline
+            }
+            Some('{') => Token::Open,
+            Some('}') => Token::Close,
+            Some('"') => {
+                let mut word = String::new();
+                loop {
+                    match self.chars.next() {
+                        Some('"') => break,
+                        Some('\\') => word.extend(self.chars.next()),
+                        Some('\n') | None => {
+                            return Err(Error::parse("kanshi quoted string", self.line()))
+                        }
+                        Some(c) => word.push(c),
+                    }
+                }
+                Token::Word(word)
+            }
+            Some(c) => {
+                let mut word = c.to_string();
+                while let Some(&c) = self.chars.peek() {
+                    if c.is_whitespace() || "{}#\"".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    self.chars.next();
+                }
+                Token::Word(word)
+            }
+        };
+        Ok(Some(token))
+    }
+
+    /// raw text up to the line end, which is left for `next`
+    fn rest_of_line(&mut self) -> String {
+        let mut rest = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c == '\n' {
+                break;
+            }
+            rest.push(c);
+            self.chars.next();
+        }
+        rest.trim().to_string()
+    }
+
+    fn expect_open(&mut self) -> Result<()> {
+        match self.next()? {
+            Some(Token::Open) => Ok(()),
+            _ => Err(Error::parse("kanshi profile", self.line())),
+        }
+    }
+
+    /// skip the rest of a directive, with its block if it has one
+    fn skip_block(&mut self) -> Result<()> {
+        let mut depth = 0;
+        while let Some(token) = self.next()? {
+            match token {
+                Token::Open => depth += 1,
+                Token::Close => {
+                    depth -= 1;
+                    if depth <= 0 {
+                        break;
+                    }
+                }
+                Token::Newline if depth == 0 => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// body of a profile, after its `{`
+    fn profile(&mut self, name: Option<String>) -> Result<KanshiProfile> {
+        let mut profile = KanshiProfile {
+            name,
+            ..Default::default()
+        };
+        loop {
+            match self.next()? {
+                Some(Token::Close) => return Ok(profile),
+                Some(Token::Newline) => {}
+                Some(Token::Word(w)) if w == "output" => profile.outputs.push(self.output()?),
+                Some(Token::Word(w)) if w == "exec" => profile.exec.push(self.rest_of_line()),
+                _ => return Err(Error::parse("kanshi profile", self.line())),
+            }
+        }
+    }
+
+    /// `output` directive up to the line end
+    fn output(&mut self) -> Result<KanshiOutput> {
+        let line = self.line();
+        let bad = || Error::parse("kanshi output", line.clone());
+        let criteria = self.word("kanshi output")?;
+        let mut output = KanshiOutput {
+            criteria,
+            ..Default::default()
+        };
+
+        // 1. words up to the line end
+        let mut words = Vec::new();
+        while let Some(token) = self.next()? {
+            match token {
+                Token::Word(w) => words.push(w),
+                Token::Newline => break,
+                _ => return Err(bad()),
+            }
+        }
+
+        // 2. directives with their values
+        let mut words = words.into_iter();
+        while let Some(word) = words.next() {
+            let mut value = || words.next().ok_or_else(bad);
+            match word.as_str() {
+                "enable" => output.enable = Some(true),
+                "disable" => output.enable = Some(false),
+                "mode" => {
+                    let mut mode = value()?;
+                    if mode == "--custom" {
+                        output.custom = true;
+                        mode = value()?;
+                    }
+                    output.mode = Some(mode);
+                }
+                "position" => {
+                    let pos = value()?;
+                    let (x, y) = match pos.split_once(',') {
+                        Some((x, y)) => (x.to_string(), y.to_string()),
+                        None => (pos, value()?),
+                    };
+                    let parse = |n: &str| n.parse::<i32>().map_err(|_| bad());
+                    output.position = Some((parse(&x)?, parse(&y)?));
+                }
+                "scale" => output.scale = Some(value()?.parse().map_err(|_| bad())?),
+                "transform" => output.transform = Some(value()?),
+                "adaptive_sync" => output.adaptive_sync = Some(value()? == "on"),
+                _ => return Err(bad()),
+            }
+        }
+        Ok(output)
+    }
+
+    fn word(&mut self, what: &'static str) -> Result<String> {
+        match self.next()? {
+            Some(Token::Word(w)) => Ok(w),
+            _ => Err(Error::parse(what, self.line())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::backend::Backend;
+    use crate::display::wlr_randr::WlrRandr;
+    use crate::runner::runner::{FakeRunner, Runner};
+    use crate::settings::settings::Settings;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join(name)
+    }
+
+    fn displays() -> Displays {
+        let fake = FakeRunner::from_dir(&fixture("fixtures/commands")).unwrap();
+        let mut displays = Displays::with_backend(Backend::new(WlrRandr::new(Runner::new(fake))));
+        displays.init().unwrap();
+        displays
+    }
+
+    #[test]
+    fn parse_sample_config_and_round_trip() {
+        let profiles = read(&fixture("fixtures/kanshi/config")).unwrap();
+        let names: Vec<Option<&str>> = profiles.iter().map(|p| p.name.as_deref()).collect();
+        assert_eq!(
+            names,
+            vec![Some("laptop"), Some("docked"), Some("presentation"), None]
+        );
+        let docked = &profiles[1];
+        assert_eq!(docked.outputs[0].criteria, "Sharp Corporation 0x1453");
+        assert_eq!(docked.outputs[0].enable, Some(false));
+        assert_eq!(
+            docked.outputs[1],
+            KanshiOutput {
+                criteria: "Dell Inc. DELL U2720Q 8K2JR83".to_string(),
+                enable: None,
+                mode: Some("3840x2160@60Hz".to_string()),
+                custom: false,
+                position: Some((0, 0)),
+                scale: Some(1.5),
+                transform: Some("normal".to_string()),
+                adaptive_sync: Some(true),
+            }
+        );
+        assert_eq!(
+            docked.exec,
+            vec![r#"notify-send "docked" && swaymsg 'workspace 1'"#]
+        );
+        assert!(profiles[2].outputs[0].custom);
+        assert_eq!(profiles[2].outputs[1].criteria, "*");
+
+        let config: String = profiles.iter().map(|p| p.to_config()).collect();
+        assert_eq!(parse(&config).unwrap(), profiles);
+        let left = parse("profile {\n    output DP-1 position -1920,0\n}\n").unwrap();
+        assert_eq!(left[0].outputs[0].position, Some((-1920, 0)));
+    }
+
+    #[test]
+    fn restore_matches_monitors_by_description() {
+        let profiles = read(&fixture("fixtures/kanshi/config")).unwrap();
+        let mut displays = displays();
+        assert!(profiles[0].assign(&displays).is_none());
+        assert_eq!(
+            profiles[1].assign(&displays).unwrap(),
+            vec!["eDP-1", "HDMI-A-1"]
+        );
+        profiles[1].restore(&mut displays).unwrap();
+        let state = displays.state();
+        assert!(!state[1].enable);
+        assert_eq!((state[0].position, state[0].scale), ((0, 0), 1.5));
+        assert!(profiles[0].restore(&mut displays).is_err());
+    }
+
+    #[test]
+    fn export_round_trips_through_restore() {
+        let mut displays = displays();
+        let exported = KanshiProfile::capture("home", &displays);
+        let config = exported.to_config();
+        assert!(config.starts_with(
+            "profile home {\n    output \"Dell Inc. DELL U2720Q 8K2JR83\" enable mode 3840x2160@60Hz position 1536,0 scale 2 transform normal\n"
+        ));
+        let parsed = parse(&config).unwrap();
+        assert_eq!(parsed, vec![exported]);
+
+        let before = displays.state();
+        displays.get_mut("eDP-1").unwrap().set_scale(1.0);
+        parsed[0].restore(&mut displays).unwrap();
+        assert_eq!(displays.state(), before);
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::BTreeMap;
+
+use super::display::{Display, Resolution};
+
+/// logical pixels two edges may be apart and still count as touching
+const TOLERANCE: f64 = 1.0;
+/// share of their size mirrored displays may differ by, when no mode of
+/// one has the exact shape of the other
+const MIRROR_SLACK: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// works, but probably not what was meant
+    Warning,
+    /// must be fixed before applying
+    Error,
+}
+
+/// Problem found in the layout of the displays
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Issue {
+    fn warning(message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+        }
+    }
+
+    fn error(message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+        }
+    }
+}
+
+/// logical area of an enabled display
+struct Area<'a> {
+    name: &'a str,
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+}
+
+impl Area<'_> {
+    fn overlaps(&self, other: &Area) -> bool {
+        let w = (self.x + self.w).min(other.x + other.w) - self.x.max(other.x);
+        let h = (self.y + self.h).min(other.y + other.h) - self.y.max(other.y);
+        w > TOLERANCE && h > TOLERANCE
+    }
+
+    /// same origin and about the same size, mirrored displays
+    fn mirrors(&self, other: &Area) -> bool {
+        let near = |a: f64, b: f64, slack: f64| (a - b).abs() <= slack.max(TOLERANCE);
+        near(self.x, other.x, 0.0)
+            && near(self.y, other.y, 0.0)
+            && near(self.w, other.w, self.w.max(other.w) * MIRROR_SLACK)
+            && near(self.h, other.h, self.h.max(other.h) * MIRROR_SLACK)
+    }
+
+    /// share a piece of edge, so the pointer can move across
+    fn touches(&self, other: &Area) -> bool {
+        let side = |a: f64, b: f64| (a - b).abs() <= TOLERANCE;
+        let along = |a: f64, al: f64, b: f64, bl: f64| (a + al).min(b + bl) - a.max(b) > TOLERANCE;
+        let horizontal = (side(self.x + self.w, other.x) || side(other.x + other.w, self.x))
+            && along(self.y, self.h, other.y, other.h);
+        let vertical = (side(self.y + self.h, other.y) || side(other.y + other.h, self.y))
+            && along(self.x, self.w, other.x, other.w);
+        horizontal || vertical || self.overlaps(other)
+    }
+}
+
+/// Check the layout of `displays`, errors first.
+pub fn validate<'a>(displays: impl Iterator<Item = &'a Display>) -> Vec<Issue> {
+    let areas: Vec<Area> = displays
+        .filter(|d| d.enable)
+        .map(|d| {
+            let (w, h) = d.logical_size();
+            Area {
+                name: &d.name,
+                x: d.position.0 as f64,
+                y: d.position.1 as f64,
+                w,
+                h,
+            }
+        })
+        .collect();
+    let mut issues = Vec::new();
+
+    // 1. nothing to show
+    if areas.is_empty() {
+        issues.push(Issue::error("all displays are disabled".to_string()));
+        return issues;
+    }
+
+    // 2. overlaps, displays at the same origin are mirrors
+    for (i, a) in areas.iter().enumerate() {
+        for b in &areas[i + 1..] {
+            if a.overlaps(b) && !a.mirrors(b) {
+                issues.push(Issue::error(format!("{} overlaps {}", a.name, b.name)));
+            }
+        }
+    }
+
+    // 3. gaps, displays the pointer can't reach from the top left one
+    let start = (0..areas.len())
+        .min_by(|i, j| {
+            let (a, b) = (&areas[*i], &areas[*j]);
+            a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y))
+        })
+        .unwrap_or_default();
+    let mut reached = vec![false; areas.len()];
+    let mut todo = vec![start];
+    reached[start] = true;
+    while let Some(i) = todo.pop() {
+        for (j, b) in areas.iter().enumerate() {
+            if !reached[j] && areas[i].touches(b) {
+                reached[j] = true;
+                todo.push(j);
+            }
+        }
+    }
+    for (a, reached) in areas.iter().zip(&reached) {
+        if !reached {
+            issues.push(Issue::warning(format!(
+                "gap between {} and {}, the pointer can't reach it",
+                a.name, areas[start].name
+            )));
+        }
+    }
+
+    // 4. empty space at the top left
+    let min_x = areas.iter().map(|a| a.x).fold(f64::MAX, f64::min);
+    let min_y = areas.iter().map(|a| a.y).fold(f64::MAX, f64::min);
+    if min_x > TOLERANCE || min_y > TOLERANCE {
+        issues.push(Issue::warning("layout does not start at 0,0".to_string()));
+    }
+
+    issues.sort_by_key(|issue| issue.severity == Severity::Warning);
+    issues
+}
+
+/// Put enabled displays in a row, in their current left to right order.
+pub fn auto_arrange(displays: &mut BTreeMap<String, Display>) {
+    let mut row: Vec<&mut Display> = displays.values_mut().filter(|d| d.enable).collect();
+    row.sort_by_key(|d| d.position);
+    let mut x: f64 = 0.0;
+    for display in row {
+        display.position = (x.round() as i32, 0);
+        x += display.logical_size().0;
+    }
+}
+
+/// Largest resolution all `displays` offer, not counting custom modes.
+pub fn common_mode(displays: &[&Display]) -> Option<String> {
+    let (first, rest) = displays.split_first()?;
+    first
+        .mode
+        .iter()
+        .filter(|m| !m.custom)
+        .filter(|m| {
+            rest.iter().all(|d| {
+                d.mode
+                    .iter()
+                    .any(|o| !o.custom && o.resolution == m.resolution)
+            })
+        })
+        .max_by(|a, b| area(a).total_cmp(&area(b)))
+        .map(|m| m.resolution.clone())
+}
+
+fn area(mode: &Resolution) -> f64 {
+    let (w, h) = mode.size();
+    w * h
+}
+
+/// Show the same picture on displays `names`, the first one leads.
+///
+/// All get the largest common mode, else their own mode closest to the
+/// shape of the first one, scaled to the same logical width. They are put
+/// at 0,0 with other enabled displays in a row to the right. Returns
+/// `false`, changing nothing, for less than two connected displays.
+pub fn mirror(displays: &mut BTreeMap<String, Display>, names: &[&str]) -> bool {
+    let group: Vec<&Display> = names.iter().filter_map(|n| displays.get(*n)).collect();
+    if group.len() < 2 {
+        return false;
+    }
+
+    // 1. the mode of each display
+    let lead = group[0].clone();
+    let modes: Vec<(String, Resolution, f64)> = match common_mode(&group) {
+        Some(common) => group
+            .iter()
+            .map(|d| (d.name.clone(), fastest(d, &common), lead.scale))
+            .collect(),
+        None => {
+            let (width, height) = lead.logical_size();
+            group
+                .iter()
+                .map(|d| {
+                    let mode = if d.name == lead.name {
+                        lead.now_mode.clone()
+                    } else {
+                        closest_shape(d, width / height)
+                    };
+                    let (mode_width, mode_height) = mode.size();
+                    let rotated = if lead.transform.is_rotated() {
+                        mode_height
+                    } else {
+                        mode_width
+                    };
+                    (d.name.clone(), mode, rotated / width)
+                })
+                .collect()
+        }
+    };
+
+    // 2. same settings, same origin
+    for (name, mode, scale) in modes {
+        if let Some(display) = displays.get_mut(&name) {
+            display.now_mode = mode;
+            display.scale = scale;
+            display.transform = lead.transform;
+            display.enable = true;
+            display.position = (0, 0);
+        }
+    }
+
+    // 3. the rest in a row after the mirrored ones
+    let mut x = displays
+        .values()
+        .filter(|d| names.contains(&d.name.as_str()))
+        .map(|d| d.logical_size().0)
+        .fold(0.0, f64::max);
+    let mut rest: Vec<&mut Display> = displays
+        .values_mut()
+        .filter(|d| d.enable && !names.contains(&d.name.as_str()))
+        .collect();
+    rest.sort_by_key(|d| d.position);
+    for display in rest {
+        display.position = (x.round() as i32, 0);
+        x += display.logical_size().0;
+    }
+    true
+}
+
+/// the highest refresh of `resolution`
+fn fastest(display: &Display, resolution: &str) -> Resolution {
+    display
+        .mode
+        .iter()
+        .filter(|m| !m.custom && m.resolution == resolution)
+        .max_by(|a, b| a.refresh.total_cmp(&b.refresh))
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// the mode closest to `aspect`, the largest of equally close ones
+fn closest_shape(display: &Display, aspect: f64) -> Resolution {
+    let distance = |m: &Resolution| {
+        let (w, h) = m.size();
+        if h > 0.0 {
+            (w / h - aspect).abs()
+        } else {
+            f64::MAX
+        }
+    };
+    display
+        .mode
+        .iter()
+        .filter(|m| !m.custom)
+        .min_by(|a, b| {
+            distance(a)
+                .total_cmp(&distance(b))
+                .then(area(b).total_cmp(&area(a)))
+                .then(b.refresh.total_cmp(&a.refresh))
+        })
+        .cloned()
+        .unwrap_or_else(|| display.now_mode.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(name: &str, mode: &str, position: (i32, i32), scale: f64) -> Display {
+        Display {
+            name: name.to_string(),
+            enable: true,
+            now_mode: Resolution {
+                resolution: mode.to_string(),
+                refresh: 60.0,
+                custom: false,
+            },
+            position,
+            scale,
+            ..Default::default()
+        }
+    }
+
+    fn with_modes(mut display: Display, modes: &[(&str, f64)]) -> Display {
+        display.mode = modes
+            .iter()
+            .map(|(resolution, refresh)| Resolution {
+                resolution: resolution.to_string(),
+                refresh: *refresh,
+                custom: false,
+            })
+            .collect();
+        display
+    }
+
+    fn layout(displays: Vec<Display>) -> BTreeMap<String, Display> {
+        displays.into_iter().map(|d| (d.name.clone(), d)).collect()
+    }
+
+    fn messages(displays: &BTreeMap<String, Display>) -> Vec<String> {
+        validate(displays.values())
+            .into_iter()
+            .map(|issue| issue.message)
+            .collect()
+    }
+
+    #[test]
+    fn side_by_side_is_fine() {
+        let displays = layout(vec![
+            display("eDP-1", "1920x1080", (0, 0), 1.25),
+            display("HDMI-A-1", "3840x2160", (1536, 0), 2.0),
+        ]);
+        assert!(validate(displays.values()).is_empty());
+    }
+
+    #[test]
+    fn left_of_origin_is_fine() {
+        let displays = layout(vec![
+            display("DP-1", "1920x1080", (-1920, -216), 1.0),
+            display("eDP-1", "1920x1080", (0, 0), 1.25),
+        ]);
+        assert!(validate(displays.values()).is_empty());
+    }
+
+    #[test]
+    fn overlap_blocks_and_gap_warns() {
+        let displays = layout(vec![
+            display("eDP-1", "1920x1080", (0, 0), 1.0),
+            display("HDMI-A-1", "1920x1080", (1000, 0), 1.0),
+            display("DP-1", "1920x1080", (5000, 0), 1.0),
+        ]);
+        let issues = validate(displays.values());
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert_eq!(
+            messages(&displays),
+            vec![
+                "HDMI-A-1 overlaps eDP-1",
+                "gap between DP-1 and eDP-1, the pointer can't reach it",
+            ]
+        );
+    }
+
+    #[test]
+    fn same_origin_of_other_sizes_overlaps() {
+        let displays = layout(vec![
+            display("eDP-1", "1920x1080", (0, 0), 1.0),
+            display("HDMI-A-1", "3840x2160", (0, 0), 1.0),
+        ]);
+        assert_eq!(messages(&displays), vec!["HDMI-A-1 overlaps eDP-1"]);
+    }
+
+    #[test]
+    fn all_off_is_an_error_moved_layout_a_warning() {
+        let mut displays = layout(vec![display("eDP-1", "1920x1080", (5000, 0), 1.0)]);
+        assert_eq!(messages(&displays), vec!["layout does not start at 0,0"]);
+        displays.get_mut("eDP-1").unwrap().enable = false;
+        assert_eq!(messages(&displays), vec!["all displays are disabled"]);
+    }
+
+    #[test]
+    fn auto_arrange_fixes_layout() {
+        let mut displays = layout(vec![
+            display("eDP-1", "1920x1080", (3000, 500), 1.25),
+            display("HDMI-A-1", "3840x2160", (0, 200), 2.0),
+            display("DP-1", "1920x1080", (1000, 0), 1.0),
+        ]);
+        assert!(!validate(displays.values()).is_empty());
+        auto_arrange(&mut displays);
+        assert_eq!(displays["HDMI-A-1"].position, (0, 0));
+        assert_eq!(displays["DP-1"].position, (1920, 0));
+        assert_eq!(displays["eDP-1"].position, (3840, 0));
+        assert!(validate(displays.values()).is_empty());
+    }
+
+    #[test]
+    fn mirror_picks_largest_common_mode() {
+        let laptop = with_modes(
+            display("eDP-1", "1920x1080", (0, 0), 1.25),
+            &[("1920x1080", 60.02), ("1280x720", 60.0)],
+        );
+        let projector = with_modes(
+            display("HDMI-A-1", "1024x768", (1536, 0), 1.0),
+            &[("1024x768", 60.0), ("1280x720", 50.0), ("1280x720", 60.0)],
+        );
+        let side = display("DP-1", "1920x1080", (2560, 0), 1.0);
+        let mut displays = layout(vec![laptop, projector, side]);
+        assert!(!mirror(&mut displays, &["eDP-1", "DP-2"]));
+        assert!(mirror(&mut displays, &["eDP-1", "HDMI-A-1"]));
+        for name in ["eDP-1", "HDMI-A-1"] {
+            let d = &displays[name];
+            assert_eq!(
+                (d.now_mode.resolution.as_str(), d.now_mode.refresh),
+                ("1280x720", 60.0)
+            );
+            assert_eq!((d.position, d.scale), ((0, 0), 1.25));
+        }
+        assert_eq!(displays["DP-1"].position, (1024, 0));
+        assert!(validate(displays.values()).is_empty());
+
+        auto_arrange(&mut displays);
+        assert_ne!(displays["eDP-1"].position, displays["HDMI-A-1"].position);
+        assert!(validate(displays.values()).is_empty());
+    }
+
+    #[test]
+    fn mirror_without_common_mode_scales_to_lead() {
+        let laptop = with_modes(
+            display("eDP-1", "2880x1800", (0, 0), 2.0),
+            &[("2880x1800", 60.0)],
+        );
+        let projector = with_modes(
+            display("HDMI-A-1", "1024x768", (1440, 0), 1.0),
+            &[("1024x768", 60.0), ("1680x1050", 60.0), ("1920x1080", 60.0)],
+        );
+        let mut displays = layout(vec![laptop, projector]);
+        assert!(mirror(&mut displays, &["eDP-1", "HDMI-A-1"]));
+        let projector = &displays["HDMI-A-1"];
+        assert_eq!(projector.now_mode.resolution, "1680x1050");
+        let (width, height) = projector.logical_size();
+        assert!((width - 1440.0).abs() < 0.01 && (height - 900.0).abs() < 0.01);
+        assert!(validate(displays.values()).is_empty());
+    }
+}
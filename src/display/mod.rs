@@ -1 +1,18 @@
+pub mod autolayout;
+pub mod backend;
+pub mod canvas;
+pub mod custom_mode;
+pub mod cvt;
 pub mod display;
+pub mod edid;
+pub mod hotplug;
+pub mod hyprland;
+pub mod identify;
+pub mod kanshi;
+pub mod layout;
+pub mod scale;
+pub mod sway;
+pub mod wayfire;
+pub mod wayland;
+pub mod wlr_output;
+pub mod wlr_randr;
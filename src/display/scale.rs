@@ -0,0 +1,124 @@
+//! Fractional scale advice: logical size, blur warnings and scales that
+//! suit the pixel density of the monitor.
+
+use super::display::Display;
+
+/// density scale 1 is made for, dots per inch
+pub const BASE_DPI: f64 = 96.0;
+/// scales offered as recommendations
+const CANDIDATES: [f64; 8] = [1.0, 1.25, 1.5, 1.75, 2.0, 2.25, 2.5, 3.0];
+/// how many recommendations to offer
+const RECOMMENDED: usize = 3;
+
+impl Display {
+    /// `(width, height)` in mm of `physical_size`, e.g. `290x170 mm`
+    pub fn physical_mm(&self) -> Option<(f64, f64)> {
+        let (w, h) = self
+            .physical_size
+            .trim()
+            .trim_end_matches("mm")
+            .trim()
+            .split_once('x')?;
+        let (w, h): (f64, f64) = (w.parse().ok()?, h.parse().ok()?);
+        (w > 0.0 && h > 0.0).then_some((w, h))
+    }
+
+    /// pixels per inch of the current mode, `None` without a physical size
+    pub fn dpi(&self) -> Option<f64> {
+        let (width, _) = self.mode_size();
+        let (mm, _) = self.physical_mm()?;
+        (width > 0.0).then(|| width / (mm / 25.4))
+    }
+
+    /// scale that makes things as large as on a [`BASE_DPI`] monitor
+    pub fn ideal_scale(&self) -> Option<f64> {
+        self.dpi().map(|dpi| dpi / BASE_DPI)
+    }
+
+    /// Scales close to [`Display::ideal_scale`] giving whole logical
+    /// pixels, smallest first.
+    pub fn recommended_scales(&self) -> Vec<f64> {
+        let ideal = match self.ideal_scale() {
+            Some(ideal) => ideal,
+            None => return Vec::new(),
+        };
+        let size = self.mode_size();
+        let mut scales: Vec<f64> = CANDIDATES
+            .iter()
+            .copied()
+            .filter(|s| whole(size, *s))
+            .collect();
+        scales.sort_by(|a, b| (a - ideal).abs().total_cmp(&(b - ideal).abs()));
+        scales.truncate(RECOMMENDED);
+        scales.sort_by(f64::total_cmp);
+        scales
+    }
+
+    /// what may look wrong at the current scale
+    pub fn scale_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let size = self.mode_size();
+        if size == (0.0, 0.0) || self.scale <= 0.0 {
+            return warnings;
+        }
+        if !whole(size, self.scale) {
+            let (width, height) = (size.0 / self.scale, size.1 / self.scale);
+            warnings.push(format!(
+                "{}x{} at scale {} is {:.2}x{:.2} logical pixels, edges of windows may blur",
+                size.0, size.1, self.scale, width, height
+            ));
+        }
+        if self.scale.fract() != 0.0 {
+            warnings.push(
+                "XWayland apps are drawn at scale 1 and stretched, they will look blurry"
+                    .to_string(),
+            );
+        }
+        warnings
+    }
+}
+
+/// `size` divided by `scale` is whole pixels
+fn whole(size: (f64, f64), scale: f64) -> bool {
+    let is_whole = |n: f64| (n / scale - (n / scale).round()).abs() < 1e-6;
+    is_whole(size.0) && is_whole(size.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(size: &str, mode: &str) -> Display {
+        let mut display = Display {
+            physical_size: size.to_string(),
+            scale: 1.0,
+            ..Default::default()
+        };
+        display.set_custom_mode(&format!("{}@60", mode)).unwrap();
+        display
+    }
+
+    #[test]
+    fn recommend_whole_scales_near_the_density() {
+        let laptop = display(" 290x170 mm", "1920x1080");
+        assert!((laptop.dpi().unwrap() - 168.2).abs() < 0.1);
+        assert_eq!(laptop.recommended_scales(), vec![1.25, 1.5, 2.0]);
+        let dell = display("600x340 mm", "3840x2160");
+        assert_eq!(dell.recommended_scales(), vec![1.25, 1.5, 2.0]);
+        let projector = display("0x0 mm", "1920x1080");
+        assert_eq!(projector.dpi(), None);
+        assert!(projector.recommended_scales().is_empty());
+    }
+
+    #[test]
+    fn warn_about_blurry_scales() {
+        let mut laptop = display("290x170 mm", "1920x1080");
+        assert!(laptop.scale_warnings().is_empty());
+        laptop.set_scale(1.5);
+        assert_eq!(laptop.scale_warnings().len(), 1);
+        laptop.set_scale(1.3);
+        let warnings = laptop.scale_warnings();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("1476.92x830.77"));
+    }
+}
@@ -0,0 +1,240 @@
+use serde::Deserialize;
+
+use super::backend::DisplayBackend;
+use super::display::{Display, DisplayState, Resolution, Transform};
+use crate::error::error::{Error, Result};
+use crate::runner::runner::{Cmd, Runner};
+
+/// Env var sway sets for its IPC socket
+pub const SWAYSOCK_ENV: &str = "SWAYSOCK";
+
+/// Backend for sway, through `swaymsg`
+pub struct Sway {
+    runner: Runner,
+}
+
+#[derive(Deserialize)]
+struct Output {
+    name: String,
+    #[serde(default)]
+    make: String,
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    serial: String,
+    #[serde(default)]
+    active: bool,
+    #[serde(default)]
+    rect: Rect,
+    /// missing or -1 for disabled outputs
+    #[serde(default)]
+    scale: Option<f64>,
+    #[serde(default)]
+    transform: Option<String>,
+    #[serde(default)]
+    adaptive_sync_status: Option<String>,
+    /// picture on, missing before sway 1.8
+    #[serde(default)]
+    power: Option<bool>,
+    #[serde(default)]
+    modes: Vec<Mode>,
+    #[serde(default)]
+    current_mode: Option<Mode>,
+}
+
+#[derive(Deserialize, Default)]
+struct Rect {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Deserialize)]
+struct Mode {
+    width: u32,
+    height: u32,
+    /// mHz
+    refresh: u32,
+}
+
+impl Mode {
+    fn resolution(&self) -> Resolution {
+        Resolution {
+            resolution: format!("{}x{}", self.width, self.height),
+            refresh: self.refresh as f64 / 1000.0,
+            custom: false,
+        }
+    }
+}
+
+impl Output {
+    fn display(self) -> Result<Display> {
+        let transform = self.transform.as_deref().unwrap_or("normal");
+        let description = [self.make, self.model, self.serial]
+            .into_iter()
+            .filter(|s| !s.is_empty() && s != "Unknown")
+            .collect::<Vec<_>>()
+            .join(" ");
+        // sway doesn't tell the preferred mode
+        let display = Display {
+            description,
+            enable: self.active,
+            mode: self.modes.iter().map(Mode::resolution).collect(),
+            now_mode: self
+                .current_mode
+                .as_ref()
+                .map(Mode::resolution)
+                .unwrap_or_default(),
+            position: (self.rect.x, self.rect.y),
+            transform: Transform::from_str(transform)
+                .ok_or_else(|| Error::parse("transform", transform))?,
+            scale: self.scale.filter(|s| *s > 0.0).unwrap_or(1.0),
+            adaptive_sync: self.adaptive_sync_status.map(|s| s == "enabled"),
+            power: self.power,
+            name: self.name,
+            ..Default::default()
+        };
+        Ok(display.with_mode(None))
+    }
+}
+
+impl Sway {
+    pub fn new(runner: Runner) -> Self {
+        Self { runner }
+    }
+
+    /// `swaymsg output` command setting `state`
+    fn cmd(state: &DisplayState) -> Cmd {
+        let cmd = Cmd::new("swaymsg").arg("output").arg(&state.name);
+        if !state.enable {
+            return cmd.arg("disable");
+        }
+        let cmd = cmd.arg("enable").arg("mode");
+        let cmd = if state.custom {
+            cmd.arg("--custom")
+        } else {
+            cmd
+        };
+        let cmd = cmd
+            .arg(format!("{}@{}Hz", state.mode, state.refresh))
+            .arg("position")
+            .arg(state.position.0.to_string())
+            .arg(state.position.1.to_string())
+            .arg("scale")
+            .arg(state.scale.to_string())
+            .arg("transform")
+            .arg(&state.transform);
+        match state.adaptive_sync {
+            Some(on) => cmd.arg("adaptive_sync").arg(if on { "on" } else { "off" }),
+            None => cmd,
+        }
+    }
+}
+
+/// outputs of `swaymsg -t get_outputs`
+pub fn parse(json: &str) -> Result<Vec<Display>> {
+    let outputs: Vec<Output> =
+        serde_json::from_str(json).map_err(|e| Error::parse("sway outputs", e.to_string()))?;
+    outputs.into_iter().map(Output::display).collect()
+}
+
+impl DisplayBackend for Sway {
+    fn name(&self) -> &'static str {
+        "sway"
+    }
+
+    fn outputs(&self) -> Result<Vec<Display>> {
+        let json = self
+            .runner
+            .stdout(&Cmd::new("swaymsg").args(["-r", "-t", "get_outputs"]))?;
+        parse(&json)
+    }
+
+    /// sway can't check a configuration without applying it
+    fn test(&self, _states: &[DisplayState]) -> Result<()> {
+        Ok(())
+    }
+
+    fn apply(&self, states: &[DisplayState]) -> Result<()> {
+        for state in states {
+            self.runner.stdout(&Self::cmd(state))?;
+        }
+        Ok(())
+    }
+
+    fn set_power(&self, name: &str, on: bool) -> Result<()> {
+        let power = if on { "on" } else { "off" };
+        self.runner
+            .stdout(&Cmd::new("swaymsg").args(["output", name, "power", power]))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::backend::Backend;
+    use crate::display::display::Displays;
+    use crate::runner::runner::FakeRunner;
+    use crate::settings::settings::Settings;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn fake() -> Arc<FakeRunner> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/commands");
+        Arc::new(FakeRunner::from_dir(&dir).unwrap())
+    }
+
+    #[test]
+    fn parse_get_outputs() {
+        let fake = fake();
+        let outputs = Sway::new(Runner::new(fake)).outputs().unwrap();
+        assert_eq!(outputs.len(), 3);
+        let edp = &outputs[0];
+        assert_eq!(edp.monitor(), "Sharp Corporation 0x1453");
+        assert_eq!(edp.now_mode.resolution, "1920x1080");
+        assert_eq!(edp.now_mode.refresh, 60.02);
+        assert_eq!(edp.mode.len(), 3);
+        assert_eq!(edp.logical_size(), (1536.0, 864.0));
+        assert_eq!(edp.adaptive_sync, Some(false));
+        assert_eq!(edp.power, Some(true));
+        let hdmi = &outputs[1];
+        assert_eq!(hdmi.monitor(), "Dell Inc. DELL U2720Q 8K2JR83");
+        assert_eq!(hdmi.position, (1536, 0));
+        let dp = &outputs[2];
+        assert!(!dp.enable);
+        assert_eq!(dp.now_mode.resolution, "2560x1440");
+        assert_eq!(dp.now_mode.refresh, 143.998);
+        assert_eq!(dp.scale, 1.0);
+        assert_eq!(dp.adaptive_sync, None);
+        assert_eq!(dp.power, Some(false));
+        assert!(parse("{}").is_err());
+    }
+
+    #[test]
+    fn apply_runs_swaymsg_output() {
+        let fake = fake();
+        let mut displays =
+            Displays::with_backend(Backend::new(Sway::new(Runner::new(fake.clone()))));
+        displays.init().unwrap();
+        let hdmi = displays.get_mut("HDMI-A-1").unwrap();
+        hdmi.set_scale(1.5);
+        hdmi.set_adaptive_sync(false).unwrap();
+        hdmi.apply().unwrap();
+        displays.get_mut("DP-2").unwrap().apply().unwrap();
+        let calls = fake.calls();
+        assert_eq!(
+            calls[1],
+            "swaymsg output HDMI-A-1 enable mode 3840x2160@60Hz position 1536 0 scale 1.5 transform normal adaptive_sync off"
+        );
+        assert_eq!(calls[2], "swaymsg output DP-2 disable");
+        let dp = displays.get_mut("DP-2").unwrap();
+        dp.set_enable(true);
+        dp.apply().unwrap();
+        assert_eq!(
+            fake.calls()[3],
+            "swaymsg output DP-2 enable mode 2560x1440@143.998Hz position 0 0 scale 1 transform normal"
+        );
+        displays.get_mut("eDP-1").unwrap().set_power(false).unwrap();
+        assert_eq!(fake.calls()[4], "swaymsg output eDP-1 power off");
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::display::DisplayState;
+use crate::error::error::{Error, Result};
+use crate::settings::settings::config_home;
+
+/// Env var wayfire itself reads its config path from
+pub const WAYFIRE_CONFIG_ENV: &str = "WAYFIRE_CONFIG_FILE";
+
+/// Env var wayfire sets for its IPC socket
+pub const WAYFIRE_SOCKET_ENV: &str = "WAYFIRE_SOCKET";
+
+/// `true` in a wayfire session, by its socket or the desktop name
+pub fn running() -> bool {
+    let var = |env: &str| std::env::var(env).unwrap_or_default();
+    !var(WAYFIRE_SOCKET_ENV).is_empty()
+        || var("XDG_CURRENT_DESKTOP")
+            .split(':')
+            .any(|d| d.eq_ignore_ascii_case("wayfire"))
+}
+
+/// `$WAYFIRE_CONFIG_FILE`, default `~/.config/wayfire.ini`
+pub fn config_path() -> PathBuf {
+    match std::env::var_os(WAYFIRE_CONFIG_ENV) {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => config_home().join("wayfire.ini"),
+    }
+}
+
+/// Ini file edited in place, other lines are kept as they are
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Ini {
+    lines: Vec<String>,
+}
+
+impl Ini {
+    pub fn parse(s: &str) -> Self {
+        Self {
+            lines: s.lines().map(str::to_string).collect(),
+        }
+    }
+
+    /// the file at `path`, empty if it doesn't exist yet
+    pub fn read(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        fs::read_to_string(path)
+            .map(|s| Self::parse(&s))
+            .map_err(|source| Error::File {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_string()).map_err(|source| Error::File {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// names of all sections, in file order
+    pub fn sections(&self) -> Vec<&str> {
+        self.lines.iter().filter_map(|l| section(l)).collect()
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        let (start, end) = self.range(section)?;
+        self.lines[start..end]
+            .iter()
+            .filter_map(|l| entry(l))
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v)
+    }
+
+    /// Set `key`, in place if it exists, else after the last entry of
+    /// `section`; a missing section is added at the end.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let line = format!("{} = {}", key, value);
+
+        // 1. new section
+        let (start, end) = match self.range(section) {
+            Some(range) => range,
+            None => {
+                if self.lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                    self.lines.push(String::new());
+                }
+                self.lines.push(format!("[{}]", section));
+                self.lines.push(line);
+                return;
+            }
+        };
+
+        // 2. existing key
+        if let Some(i) =
+            (start..end).find(|i| entry(&self.lines[*i]).is_some_and(|(k, _)| k == key))
+        {
+            self.lines[i] = line;
+            return;
+        }
+
+        // 3. new key, before trailing blank lines and comments
+        let last = (start..end)
+            .rev()
+            .find(|i| entry(&self.lines[*i]).is_some())
+            .unwrap_or(start - 1);
+        self.lines.insert(last + 1, line);
+    }
+
+    /// lines of the entries of `section`, after its header
+    fn range(&self, name: &str) -> Option<(usize, usize)> {
+        let start = self.lines.iter().position(|l| section(l) == Some(name))? + 1;
+        let end = self.lines[start..]
+            .iter()
+            .position(|l| section(l).is_some())
+            .map_or(self.lines.len(), |i| start + i);
+        Some((start, end))
+    }
+}
+
+impl std::fmt::Display for Ini {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// `name` of a `[name]` line
+fn section(line: &str) -> Option<&str> {
+    let line = line.trim();
+    line.strip_prefix('[')?.strip_suffix(']').map(str::trim)
+}
+
+/// `(key, value)` of a `key = value` line
+fn entry(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.starts_with('#') || line.starts_with('[') {
+        return None;
+    }
+    let (key, value) = line.split_once('=')?;
+    Some((key.trim(), value.trim()))
+}
+
+/// Output settings of a `[output:NAME]` section, as written by the user
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WayfireOutput {
+    pub name: String,
+    /// `off`, `auto`, or `WxH@MHZ` with the refresh in mHz
+    pub mode: Option<String>,
+    /// `X,Y` or `auto`
+    pub position: Option<String>,
+    pub transform: Option<String>,
+    pub scale: Option<f64>,
+    /// adaptive sync
+    pub vrr: Option<bool>,
+}
+
+impl WayfireOutput {
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(mode) = &self.mode {
+            parts.push(mode_hz(mode));
+        }
+        if let Some(position) = &self.position {
+            parts.push(format!("+{}", position));
+        }
+        if let Some(scale) = self.scale {
+            parts.push(format!("scale {}", scale));
+        }
+        parts.extend(self.transform.clone());
+        if self.vrr == Some(true) {
+            parts.push("vrr".to_string());
+        }
+        if parts.is_empty() {
+            "defaults".to_string()
+        } else {
+            parts.join(" ")
+        }
+    }
+}
+
+/// `WxH@60020` as `WxH@60.02`, wayfire takes small refresh values as Hz
+fn mode_hz(mode: &str) -> String {
+    match mode
+        .split_once('@')
+        .map(|(res, r)| (res, r.trim().parse::<f64>()))
+    {
+        Some((res, Ok(refresh))) if refresh >= 1000.0 => format!("{}@{}", res, refresh / 1000.0),
+        _ => mode.to_string(),
+    }
+}
+
+/// wayfire's name of a transform, `90_flipped` for `flipped-90`
+fn wayfire_transform(transform: &str) -> String {
+    match transform.strip_prefix("flipped-") {
+        Some(degrees) => format!("{}_flipped", degrees),
+        None => transform.to_string(),
+    }
+}
+
+/// the transform named `transform` by wayfire, `flipped-90` for `90_flipped`
+fn transform_name(transform: &str) -> String {
+    match transform.strip_suffix("_flipped") {
+        Some(degrees) => format!("flipped-{}", degrees),
+        None => transform.to_string(),
+    }
+}
+
+/// outputs configured in `ini`, by connector name
+pub fn outputs(ini: &Ini) -> BTreeMap<String, WayfireOutput> {
+    ini.sections()
+        .into_iter()
+        .filter_map(|s| s.strip_prefix("output:"))
+        .map(|name| {
+            let section = format!("output:{}", name);
+            let get = |key| ini.get(&section, key).map(str::to_string);
+            let output = WayfireOutput {
+                name: name.to_string(),
+                mode: get("mode"),
+                position: get("position"),
+                transform: get("transform").map(|t| transform_name(&t)),
+                scale: ini.get(&section, "scale").and_then(|s| s.parse().ok()),
+                vrr: ini.get(&section, "vrr").and_then(|s| s.parse().ok()),
+            };
+            (name.to_string(), output)
+        })
+        .collect()
+}
+
+/// Write `states` into their `[output:NAME]` sections.
+pub fn set_outputs(ini: &mut Ini, states: &[DisplayState]) {
+    for state in states {
+        let section = format!("output:{}", state.name);
+        let mode = if state.enable {
+            format!("{}@{}", state.mode, (state.refresh * 1000.0).round())
+        } else {
+            "off".to_string()
+        };
+        ini.set(&section, "mode", &mode);
+        ini.set(
+            &section,
+            "position",
+            &format!("{},{}", state.position.0, state.position.1),
+        );
+        ini.set(&section, "transform", &wayfire_transform(&state.transform));
+        ini.set(&section, "scale", &state.scale.to_string());
+        if let Some(vrr) = state.adaptive_sync {
+            ini.set(&section, "vrr", &vrr.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+# wayfire config
+[core]
+plugins = alpha animate
+xwayland = true
+
+[output:eDP-1]
+# laptop panel
+mode = 1920x1080@60000
+scale = 1.000000
+
+# keyboard
+[input]
+xkb_layout = us
+";
+
+    fn state(name: &str, enable: bool) -> DisplayState {
+        DisplayState {
+            name: name.to_string(),
+            enable,
+            mode: "1920x1080".to_string(),
+            refresh: 60.02,
+            position: (0, 0),
+            transform: "normal".to_string(),
+            scale: 1.25,
+            custom: false,
+            adaptive_sync: None,
+        }
+    }
+
+    #[test]
+    fn set_outputs_keeps_other_lines() {
+        let mut ini = Ini::parse(CONFIG);
+        let hdmi = DisplayState {
+            position: (-1920, 0),
+            ..state("HDMI-A-1", false)
+        };
+        set_outputs(&mut ini, &[state("eDP-1", true), hdmi]);
+        assert_eq!(
+            ini.to_string(),
+            "\
+# wayfire config
+[core]
+plugins = alpha animate
+xwayland = true
+
+[output:eDP-1]
+# laptop panel
+mode = 1920x1080@60020
+scale = 1.25
+position = 0,0
+transform = normal
+
+# keyboard
+[input]
+xkb_layout = us
+
+[output:HDMI-A-1]
+mode = off
+position = -1920,0
+transform = normal
+scale = 1.25
+"
+        );
+        assert_eq!(ini.get("core", "xwayland"), Some("true"));
+    }
+
+    #[test]
+    fn outputs_read_back() {
+        let mut ini = Ini::parse(CONFIG);
+        let before = outputs(&ini);
+        assert_eq!(before["eDP-1"].summary(), "1920x1080@60 scale 1");
+        set_outputs(&mut ini, &[state("eDP-1", true)]);
+        let saved = Ini::parse(&ini.to_string());
+        assert_eq!(
+            outputs(&saved)["eDP-1"],
+            WayfireOutput {
+                name: "eDP-1".to_string(),
+                mode: Some("1920x1080@60020".to_string()),
+                position: Some("0,0".to_string()),
+                transform: Some("normal".to_string()),
+                scale: Some(1.25),
+                vrr: None,
+            }
+        );
+        assert_eq!(
+            outputs(&saved)["eDP-1"].summary(),
+            "1920x1080@60.02 +0,0 scale 1.25 normal"
+        );
+    }
+
+    #[test]
+    fn flipped_transforms_use_wayfire_names() {
+        let mut ini = Ini::default();
+        let hdmi = DisplayState {
+            transform: "flipped-90".to_string(),
+            ..state("HDMI-A-1", true)
+        };
+        set_outputs(&mut ini, &[hdmi, state("eDP-1", true)]);
+        assert_eq!(ini.get("output:HDMI-A-1", "transform"), Some("90_flipped"));
+        let saved = outputs(&Ini::parse(&ini.to_string()));
+        assert_eq!(saved["HDMI-A-1"].transform.as_deref(), Some("flipped-90"));
+        assert_eq!(saved["eDP-1"].transform.as_deref(), Some("normal"));
+        let flipped = Ini::parse("[output:DP-1]\ntransform = flipped\n");
+        assert_eq!(
+            outputs(&flipped)["DP-1"].transform.as_deref(),
+            Some("flipped")
+        );
+    }
+}
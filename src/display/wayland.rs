@@ -0,0 +1,114 @@
+//! What every wayland client here needs: outputs, roundtrips and shared
+//! memory files.
+
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{Seek, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use wayland_client::protocol::wl_display::WlDisplay;
+use wayland_client::protocol::wl_output::{self, WlOutput};
+use wayland_client::{Attached, EventQueue, GlobalEvent, GlobalManager, Main};
+
+use crate::error::error::{Error, Result};
+
+/// A `wl_output`, known by its connector name, or by its position on
+/// compositors without `wl_output.name`
+pub struct Output {
+    pub proxy: Main<WlOutput>,
+    pub name: Option<String>,
+    pub position: (i32, i32),
+}
+
+impl Output {
+    /// `true` if this is connector `name`, which is at `position`
+    pub fn is(&self, name: &str, position: Option<(i32, i32)>) -> bool {
+        match &self.name {
+            Some(n) => n == name,
+            None => Some(self.position) == position,
+        }
+    }
+}
+
+/// Globals of `display`, with every `wl_output` bound into the list,
+/// plugged ones too; their names come with the next roundtrip.
+pub fn globals(display: &Attached<WlDisplay>) -> (GlobalManager, Rc<RefCell<Vec<Output>>>) {
+    let outputs: Rc<RefCell<Vec<Output>>> = Rc::default();
+    let found = outputs.clone();
+    let globals = GlobalManager::new_with_cb(display, move |event, registry, _| {
+        if let GlobalEvent::New {
+            id,
+            interface,
+            version,
+        } = event
+        {
+            if interface == "wl_output" {
+                let proxy = registry.bind::<WlOutput>(version.min(4), id);
+                let index = found.borrow().len();
+                let events = found.clone();
+                proxy.quick_assign(move |_, event, _| {
+                    let mut outputs = events.borrow_mut();
+                    match event {
+                        wl_output::Event::Name { name } => outputs[index].name = Some(name),
+                        wl_output::Event::Geometry { x, y, .. } => outputs[index].position = (x, y),
+                        _ => {}
+                    }
+                });
+                found.borrow_mut().push(Output {
+                    proxy,
+                    name: None,
+                    position: (0, 0),
+                });
+            }
+        }
+    });
+    (globals, outputs)
+}
+
+pub fn roundtrip(queue: &mut EventQueue) -> Result<()> {
+    queue
+        .sync_roundtrip(&mut (), |_, _, _| {})
+        .map_err(|e| Error::Wayland(e.to_string()))?;
+    Ok(())
+}
+
+/// Unlinked file in `$XDG_RUNTIME_DIR` holding `data`, read from its start,
+/// to share with the compositor.
+pub fn shm_file(data: &[u8]) -> Result<File> {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, PathBuf::from);
+    // one name per file, for threads drawing at the same time
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let path = dir.join(format!("rsettings-shm-{}-{}", std::process::id(), n));
+    let file_error = |source| Error::File {
+        path: path.clone(),
+        source,
+    };
+    let mut file = File::options()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(file_error)?;
+    let _ = fs::remove_file(&path);
+    file.write_all(data).map_err(file_error)?;
+    file.rewind().map_err(file_error)?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn shm_files_are_not_shared() {
+        let (mut one, mut two) = (shm_file(b"one").unwrap(), shm_file(b"two").unwrap());
+        let (mut a, mut b) = (String::new(), String::new());
+        one.read_to_string(&mut a).unwrap();
+        two.read_to_string(&mut b).unwrap();
+        assert_eq!((a.as_str(), b.as_str()), ("one", "two"));
+    }
+}
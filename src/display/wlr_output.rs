@@ -0,0 +1,903 @@
+use std::cell::RefCell;
+use std::os::unix::io::IntoRawFd;
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use wayland_client::protocol::wl_output::Transform as WlTransform;
+use wayland_client::{Display as WlDisplay, EventQueue, Main};
+use wayland_protocols::wlr::unstable::output_management::v1::client::{
+    zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1, zwlr_output_configuration_v1,
+    zwlr_output_head_v1, zwlr_output_head_v1::ZwlrOutputHeadV1, zwlr_output_manager_v1,
+    zwlr_output_manager_v1::ZwlrOutputManagerV1, zwlr_output_mode_v1,
+    zwlr_output_mode_v1::ZwlrOutputModeV1,
+};
+use wayland_protocols::wlr::unstable::output_power_management::v1::client::{
+    zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1,
+    zwlr_output_power_v1::{self, ZwlrOutputPowerV1},
+};
+
+use super::backend::DisplayBackend;
+use super::display::{Display, DisplayState, Resolution, Transform};
+use super::wayland::{self, roundtrip, Output};
+use super::wlr_randr::WlrRandr;
+use crate::error::error::{Error, Result};
+use crate::runner::runner::{Cmd, Runner};
+
+/// Backend talking `zwlr_output_manager_v1` to the compositor, and
+/// `zwlr_output_power_manager_v1` to switch pictures off
+///
+/// Version 2 of the protocol, all our bindings know, has no adaptive
+/// sync; with a runner it goes through `wlr-randr`, which speaks version
+/// 4. Without one, displays don't offer it and applying leaves it as it is.
+#[derive(Clone, Default)]
+pub struct WlrOutput {
+    /// socket of the compositor, default from `$WAYLAND_DISPLAY`
+    socket: Option<PathBuf>,
+    /// runs `wlr-randr` for adaptive sync
+    runner: Option<Runner>,
+}
+
+struct Mode {
+    proxy: ZwlrOutputModeV1,
+    width: i32,
+    height: i32,
+    /// mHz
+    refresh: i32,
+}
+
+struct Head {
+    proxy: ZwlrOutputHeadV1,
+    name: String,
+    description: String,
+    physical_size: (i32, i32),
+    /// ids of the modes of this head
+    modes: Vec<u32>,
+    current_mode: Option<u32>,
+    enabled: bool,
+    position: (i32, i32),
+    transform: WlTransform,
+    scale: f64,
+}
+
+/// everything the compositor told us, filled by the event callbacks
+#[derive(Default)]
+struct State {
+    heads: Vec<Head>,
+    modes: Vec<Mode>,
+    /// serial of the last complete configuration
+    serial: Option<u32>,
+    /// answer to a configuration
+    result: Option<Result<()>>,
+}
+
+/// power mode an output reported, `None` until it did or if it failed
+type Power = Rc<RefCell<Option<bool>>>;
+
+struct Connection {
+    _display: WlDisplay,
+    queue: EventQueue,
+    manager: Main<ZwlrOutputManagerV1>,
+    state: Rc<RefCell<State>>,
+    outputs: Rc<RefCell<Vec<Output>>>,
+    /// `None` if the compositor can't switch outputs off
+    power_manager: Option<Main<ZwlrOutputPowerManagerV1>>,
+}
+
+impl Connection {
+    /// power control of the output of head `name`
+    fn output_power(&self, name: &str) -> Result<(Main<ZwlrOutputPowerV1>, Power)> {
+        let manager = self
+            .power_manager
+            .as_ref()
+            .ok_or_else(|| Error::NotFound("zwlr_output_power_manager_v1".to_string()))?;
+        let state = self.state.borrow();
+        let position = state
+            .heads
+            .iter()
+            .find(|h| h.name == name)
+            .map(|h| h.position);
+        let outputs = self.outputs.borrow();
+        let output = outputs
+            .iter()
+            .find(|o| o.is(name, position))
+            .ok_or_else(|| Error::NotFound(format!("wl_output of {}", name)))?;
+        let power = manager.get_output_power(&output.proxy);
+        let mode: Power = Rc::default();
+        let events = mode.clone();
+        power.quick_assign(move |_, event, _| match event {
+            zwlr_output_power_v1::Event::Mode { mode } => {
+                *events.borrow_mut() = Some(mode == zwlr_output_power_v1::Mode::On)
+            }
+            // another client controls it, or the output is gone
+            zwlr_output_power_v1::Event::Failed => *events.borrow_mut() = None,
+            _ => {}
+        });
+        Ok((power, mode))
+    }
+}
+
+impl WlrOutput {
+    #[cfg(test)]
+    pub fn new<P: Into<PathBuf>>(socket: P) -> Self {
+        Self {
+            socket: Some(socket.into()),
+            runner: None,
+        }
+    }
+
+    /// adaptive sync through `wlr-randr` run by `runner`
+    pub fn with_runner(mut self, runner: Runner) -> Self {
+        self.runner = Some(runner);
+        self
+    }
+
+    /// `true` if a compositor is running and supports output management
+    pub fn available(&self) -> bool {
+        self.connect().is_ok()
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        // 1. connect
+        let display = match &self.socket {
+            Some(path) => {
+                let stream = UnixStream::connect(path).map_err(|source| Error::File {
+                    path: path.clone(),
+                    source,
+                })?;
+                // SAFETY: the fd is a connected unix socket we give away
+                unsafe { WlDisplay::from_fd(stream.into_raw_fd()) }
+            }
+            None => WlDisplay::connect_to_env(),
+        }
+        .map_err(|e| Error::Wayland(e.to_string()))?;
+        let mut queue = display.create_event_queue();
+        let attached = (*display).clone().attach(queue.token());
+        let (globals, outputs) = wayland::globals(&attached);
+        roundtrip(&mut queue)?;
+
+        // 2. bind the output manager
+        let manager = globals
+            .instantiate_range::<ZwlrOutputManagerV1>(1, 2)
+            .map_err(|_| Error::NotFound("zwlr_output_manager_v1".to_string()))?;
+        let power_manager = globals
+            .instantiate_exact::<ZwlrOutputPowerManagerV1>(1)
+            .ok();
+        let state = Rc::new(RefCell::new(State::default()));
+        let events = state.clone();
+        manager.quick_assign(move |_, event, _| manager_event(&events, event));
+
+        // 3. wait for the whole configuration
+        roundtrip(&mut queue)?;
+        if state.borrow().serial.is_none() {
+            return Err(Error::Wayland(
+                "no output configuration received".to_string(),
+            ));
+        }
+        Ok(Connection {
+            _display: display,
+            queue,
+            manager,
+            state,
+            outputs,
+            power_manager,
+        })
+    }
+
+    /// send `states` as one configuration, other heads keep their settings
+    fn configure(&self, states: &[DisplayState], test: bool) -> Result<()> {
+        let mut conn = self.connect()?;
+        let config = {
+            let state = conn.state.borrow();
+            let config = conn
+                .manager
+                .create_configuration(state.serial.unwrap_or_default());
+            for head in &state.heads {
+                match states.iter().find(|s| s.name == head.name) {
+                    Some(s) if s.enable => {
+                        let config_head = config.enable_head(&head.proxy);
+                        set_head(&config_head, &state, head, s)?;
+                    }
+                    Some(_) => config.disable_head(&head.proxy),
+                    None if head.enabled => {
+                        let config_head = config.enable_head(&head.proxy);
+                        keep_head(&config_head, &state, head);
+                    }
+                    None => config.disable_head(&head.proxy),
+                }
+            }
+            config
+        };
+        let events = conn.state.clone();
+        config.quick_assign(move |_, event, _| {
+            let result = match event {
+                zwlr_output_configuration_v1::Event::Succeeded => Ok(()),
+                zwlr_output_configuration_v1::Event::Failed => Err(Error::Wayland(
+                    "the compositor rejected the configuration".to_string(),
+                )),
+                zwlr_output_configuration_v1::Event::Cancelled => Err(Error::Wayland(
+                    "outputs changed meanwhile, try again".to_string(),
+                )),
+                _ => return,
+            };
+            events.borrow_mut().result = Some(result);
+        });
+        if test {
+            config.test();
+        } else {
+            config.apply();
+        }
+        let result = loop {
+            if let Some(result) = conn.state.borrow_mut().result.take() {
+                break result;
+            }
+            conn.queue
+                .dispatch(&mut (), |_, _, _| {})
+                .map_err(|e| Error::Wayland(e.to_string()))?;
+        };
+        config.destroy();
+        roundtrip(&mut conn.queue)?;
+        result
+    }
+}
+
+impl DisplayBackend for WlrOutput {
+    fn name(&self) -> &'static str {
+        "wayland"
+    }
+
+    fn outputs(&self) -> Result<Vec<Display>> {
+        // 1. the heads, with the power mode of their outputs
+        let mut conn = self.connect()?;
+        let names: Vec<String> = conn
+            .state
+            .borrow()
+            .heads
+            .iter()
+            .map(|h| h.name.clone())
+            .collect();
+        let powers: Vec<_> = names
+            .iter()
+            .map(|name| conn.output_power(name).ok())
+            .collect();
+        roundtrip(&mut conn.queue)?;
+        let mut displays = Vec::new();
+        for (head, power) in conn.state.borrow().heads.iter().zip(powers) {
+            let mut display = display(&conn.state.borrow(), head);
+            if let Some((proxy, mode)) = power {
+                display.power = *mode.borrow();
+                proxy.destroy();
+            }
+            displays.push(display);
+        }
+
+        // 2. adaptive sync from wlr-randr, if it runs
+        if let Some(runner) = &self.runner {
+            let randr = WlrRandr::new(runner.clone()).outputs().unwrap_or_default();
+            for display in &mut displays {
+                if let Some(other) = randr.iter().find(|o| o.name == display.name) {
+                    display.adaptive_sync = other.adaptive_sync;
+                }
+            }
+        }
+        Ok(displays)
+    }
+
+    fn test(&self, states: &[DisplayState]) -> Result<()> {
+        self.configure(states, true)
+    }
+
+    fn apply(&self, states: &[DisplayState]) -> Result<()> {
+        self.configure(states, false)?;
+        let runner = match &self.runner {
+            Some(runner) => runner,
+            None => return Ok(()),
+        };
+        for state in states.iter().filter(|s| s.enable) {
+            if let Some(on) = state.adaptive_sync {
+                let value = if on { "enabled" } else { "disabled" };
+                runner.stdout(
+                    &Cmd::new("wlr-randr")
+                        .args(["--output", state.name.as_str()])
+                        .args(["--adaptive-sync", value]),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// the compositor answers with the mode it set, or `failed`
+    fn set_power(&self, name: &str, on: bool) -> Result<()> {
+        let mut conn = self.connect()?;
+        let (power, mode) = conn.output_power(name)?;
+        roundtrip(&mut conn.queue)?;
+        power.set_mode(if on {
+            zwlr_output_power_v1::Mode::On
+        } else {
+            zwlr_output_power_v1::Mode::Off
+        });
+        roundtrip(&mut conn.queue)?;
+        let set = *mode.borrow() == Some(on);
+        power.destroy();
+        roundtrip(&mut conn.queue)?;
+        if set {
+            Ok(())
+        } else {
+            Err(Error::Wayland(format!(
+                "the compositor could not switch {} {}",
+                name,
+                if on { "on" } else { "off" }
+            )))
+        }
+    }
+
+    /// every change ends with a `done` event of a new serial
+    fn watch(&self, changed: &mut dyn FnMut() -> bool) -> Result<()> {
+        let mut conn = self.connect()?;
+        let mut serial = conn.state.borrow().serial;
+        loop {
+            conn.queue
+                .dispatch(&mut (), |_, _, _| {})
+                .map_err(|e| Error::Wayland(e.to_string()))?;
+            let now = conn.state.borrow().serial;
+            if now != serial {
+                serial = now;
+                if !changed() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn manager_event(state: &Rc<RefCell<State>>, event: zwlr_output_manager_v1::Event) {
+    match event {
+        zwlr_output_manager_v1::Event::Head { head } => {
+            let id = head.as_ref().id();
+            state.borrow_mut().heads.push(Head {
+                proxy: head.detach(),
+                name: String::new(),
+                description: String::new(),
+                physical_size: (0, 0),
+                modes: Vec::new(),
+                current_mode: None,
+                enabled: false,
+                position: (0, 0),
+                transform: WlTransform::Normal,
+                scale: 1.0,
+            });
+            let events = state.clone();
+            head.quick_assign(move |_, event, _| head_event(&events, id, event));
+        }
+        zwlr_output_manager_v1::Event::Done { serial } => state.borrow_mut().serial = Some(serial),
+        _ => {}
+    }
+}
+
+fn head_event(state: &Rc<RefCell<State>>, id: u32, event: zwlr_output_head_v1::Event) {
+    if let zwlr_output_head_v1::Event::Mode { mode } = event {
+        let mode_id = mode.as_ref().id();
+        let mut s = state.borrow_mut();
+        s.modes.push(Mode {
+            proxy: mode.detach(),
+            width: 0,
+            height: 0,
+            refresh: 0,
+        });
+        if let Some(head) = s.heads.iter_mut().find(|h| h.proxy.as_ref().id() == id) {
+            head.modes.push(mode_id);
+        }
+        let events = state.clone();
+        mode.quick_assign(move |_, event, _| mode_event(&events, mode_id, event));
+        return;
+    }
+
+    let mut s = state.borrow_mut();
+    if let zwlr_output_head_v1::Event::Finished = event {
+        s.heads.retain(|h| h.proxy.as_ref().id() != id);
+        return;
+    }
+    let head = match s.heads.iter_mut().find(|h| h.proxy.as_ref().id() == id) {
+        Some(head) => head,
+        None => return,
+    };
+    match event {
+        zwlr_output_head_v1::Event::Name { name } => head.name = name,
+        zwlr_output_head_v1::Event::Description { description } => head.description = description,
+        zwlr_output_head_v1::Event::PhysicalSize { width, height } => {
+            head.physical_size = (width, height)
+        }
+        zwlr_output_head_v1::Event::Enabled { enabled } => head.enabled = enabled != 0,
+        zwlr_output_head_v1::Event::CurrentMode { mode } => {
+            head.current_mode = Some(mode.as_ref().id())
+        }
+        zwlr_output_head_v1::Event::Position { x, y } => head.position = (x, y),
+        zwlr_output_head_v1::Event::Transform { transform } => head.transform = transform,
+        zwlr_output_head_v1::Event::Scale { scale } => head.scale = scale,
+        _ => {}
+    }
+}
+
+fn mode_event(state: &Rc<RefCell<State>>, id: u32, event: zwlr_output_mode_v1::Event) {
+    let mut s = state.borrow_mut();
+    if let zwlr_output_mode_v1::Event::Finished = event {
+        s.modes.retain(|m| m.proxy.as_ref().id() != id);
+        return;
+    }
+    let mode = match s.modes.iter_mut().find(|m| m.proxy.as_ref().id() == id) {
+        Some(mode) => mode,
+        None => return,
+    };
+    match event {
+        zwlr_output_mode_v1::Event::Size { width, height } => {
+            mode.width = width;
+            mode.height = height;
+        }
+        zwlr_output_mode_v1::Event::Refresh { refresh } => mode.refresh = refresh,
+        _ => {}
+    }
+}
+
+fn find_mode(state: &State, id: u32) -> Option<&Mode> {
+    state.modes.iter().find(|m| m.proxy.as_ref().id() == id)
+}
+
+fn resolution(mode: &Mode) -> Resolution {
+    Resolution {
+        resolution: format!("{}x{}", mode.width, mode.height),
+        refresh: mode.refresh as f64 / 1000.0,
+        custom: false,
+    }
+}
+
+fn display(state: &State, head: &Head) -> Display {
+    let modes = head.modes.iter().filter_map(|id| find_mode(state, *id));
+    Display {
+        name: head.name.clone(),
+        description: head.description.clone(),
+        enable: head.enabled,
+        physical_size: format!("{}x{} mm", head.physical_size.0, head.physical_size.1),
+        mode: modes.map(resolution).collect(),
+        now_mode: head
+            .current_mode
+            .and_then(|id| find_mode(state, id))
+            .map(resolution)
+            .unwrap_or_default(),
+        position: head.position,
+        transform: Transform::from_str(transform_name(head.transform)).unwrap_or_default(),
+        scale: head.scale,
+        ..Default::default()
+    }
+}
+
+/// configure `head` as `s` says
+fn set_head(
+    config_head: &ZwlrOutputConfigurationHeadV1,
+    state: &State,
+    head: &Head,
+    s: &DisplayState,
+) -> Result<()> {
+    // 1. the custom mode asked for, else the advertised one with the closest refresh
+    let refresh = (s.refresh * 1000.0).round() as i32;
+    if s.custom {
+        let (width, height) = s
+            .mode
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+            .ok_or_else(|| Error::parse("mode", s.mode.as_str()))?;
+        config_head.set_custom_mode(width, height, refresh);
+    } else if s.mode.is_empty() {
+        // outputs without modes have none to set
+        if let Some(mode) = head.current_mode.and_then(|id| find_mode(state, id)) {
+            config_head.set_mode(&mode.proxy);
+        }
+    } else {
+        let mode = head
+            .modes
+            .iter()
+            .filter_map(|id| find_mode(state, *id))
+            .filter(|m| format!("{}x{}", m.width, m.height) == s.mode)
+            .min_by_key(|m| (m.refresh - refresh).abs())
+            .ok_or_else(|| Error::NotFound(format!("mode {} of {}", s.mode, head.name)))?;
+        config_head.set_mode(&mode.proxy);
+    }
+    // 2. placement
+    config_head.set_position(s.position.0, s.position.1);
+    config_head.set_transform(wl_transform(&s.transform)?);
+    config_head.set_scale(s.scale);
+    Ok(())
+}
+
+/// configure `head` as it is now
+fn keep_head(config_head: &ZwlrOutputConfigurationHeadV1, state: &State, head: &Head) {
+    if let Some(mode) = head.current_mode.and_then(|id| find_mode(state, id)) {
+        config_head.set_mode(&mode.proxy);
+    }
+    config_head.set_position(head.position.0, head.position.1);
+    config_head.set_transform(head.transform);
+    config_head.set_scale(head.scale);
+}
+
+fn transform_name(transform: WlTransform) -> &'static str {
+    match transform {
+        WlTransform::_90 => "90",
+        WlTransform::_180 => "180",
+        WlTransform::_270 => "270",
+        WlTransform::Flipped => "flipped",
+        WlTransform::Flipped90 => "flipped-90",
+        WlTransform::Flipped180 => "flipped-180",
+        WlTransform::Flipped270 => "flipped-270",
+        _ => "normal",
+    }
+}
+
+fn wl_transform(name: &str) -> Result<WlTransform> {
+    Ok(match name {
+        "normal" => WlTransform::Normal,
+        "90" => WlTransform::_90,
+        "180" => WlTransform::_180,
+        "270" => WlTransform::_270,
+        "flipped" => WlTransform::Flipped,
+        "flipped-90" => WlTransform::Flipped90,
+        "flipped-180" => WlTransform::Flipped180,
+        "flipped-270" => WlTransform::Flipped270,
+        _ => return Err(Error::parse("transform", name)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    //! A tiny headless compositor speaking just enough of the wayland wire
+    //! protocol to offer `zwlr_output_manager_v1` with two heads, their
+    //! `wl_output`s and `zwlr_output_power_manager_v1`.
+
+    use super::*;
+    use crate::runner::runner::FakeRunner;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    enum Arg<'a> {
+        Uint(u32),
+        Int(i32),
+        Str(&'a str),
+    }
+
+    enum Object {
+        Display,
+        Registry,
+        Callback,
+        Manager,
+        Config,
+        /// configuration of the named head
+        ConfigHead(String),
+        PowerManager,
+        /// power control of the named output
+        Power(String),
+    }
+
+    struct Client {
+        stream: UnixStream,
+        objects: HashMap<u32, Object>,
+        /// server side ids of heads and modes, and client side ids of
+        /// outputs, with their names
+        names: HashMap<u32, String>,
+        /// outputs with their picture off, the same for every client
+        off: Arc<Mutex<Vec<String>>>,
+        next_id: u32,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Client {
+        fn send(&mut self, id: u32, opcode: u32, args: &[Arg]) {
+            let mut body = Vec::new();
+            for arg in args {
+                match arg {
+                    Arg::Uint(v) => body.extend(v.to_ne_bytes()),
+                    Arg::Int(v) => body.extend(v.to_ne_bytes()),
+                    Arg::Str(s) => {
+                        body.extend((s.len() as u32 + 1).to_ne_bytes());
+                        body.extend(s.as_bytes());
+                        body.push(0);
+                        while body.len() % 4 != 0 {
+                            body.push(0);
+                        }
+                    }
+                }
+            }
+            let mut msg = Vec::new();
+            msg.extend(id.to_ne_bytes());
+            msg.extend((((body.len() as u32 + 8) << 16) | opcode).to_ne_bytes());
+            msg.extend(body);
+            self.stream.write_all(&msg).unwrap();
+        }
+
+        fn new_id(&mut self, name: String) -> u32 {
+            let id = self.next_id;
+            self.next_id += 1;
+            self.names.insert(id, name);
+            id
+        }
+
+        fn log(&self, request: String) {
+            self.requests.lock().unwrap().push(request);
+        }
+
+        fn send_heads(&mut self, manager: u32) {
+            let heads = [
+                (
+                    "eDP-1",
+                    "Sharp Corporation 0x14F9 (eDP-1)",
+                    (290, 170),
+                    vec![(1920, 1080, 60020), (1280, 720, 60000)],
+                    (0, 0),
+                    320,
+                ),
+                (
+                    "HDMI-A-1",
+                    "Dell Inc. DELL U2720Q (HDMI-A-1)",
+                    (600, 340),
+                    vec![(3840, 2160, 60000), (2560, 1440, 59951)],
+                    (1536, 0),
+                    512,
+                ),
+            ];
+            for (name, description, size, modes, position, scale) in heads {
+                let head = self.new_id(name.to_string());
+                self.send(manager, 0, &[Arg::Uint(head)]);
+                self.send(head, 0, &[Arg::Str(name)]);
+                self.send(head, 1, &[Arg::Str(description)]);
+                self.send(head, 2, &[Arg::Int(size.0), Arg::Int(size.1)]);
+                let mut current = 0;
+                for (width, height, refresh) in modes {
+                    let mode = self.new_id(format!("{}x{}@{}", width, height, refresh));
+                    current = if current == 0 { mode } else { current };
+                    self.send(head, 3, &[Arg::Uint(mode)]);
+                    self.send(mode, 0, &[Arg::Int(width), Arg::Int(height)]);
+                    self.send(mode, 1, &[Arg::Int(refresh)]);
+                }
+                self.send(head, 4, &[Arg::Int(1)]);
+                self.send(head, 5, &[Arg::Uint(current)]);
+                self.send(head, 6, &[Arg::Int(position.0), Arg::Int(position.1)]);
+                self.send(head, 7, &[Arg::Int(0)]);
+                self.send(head, 8, &[Arg::Int(scale)]);
+            }
+            self.send(manager, 1, &[Arg::Uint(7)]);
+        }
+
+        fn handle(&mut self, id: u32, opcode: u32, args: &[u32]) {
+            let name = |id: &u32| self.names.get(id).cloned().unwrap_or_default();
+            match (self.objects.get(&id), opcode) {
+                // wl_display.sync, get_registry
+                (Some(Object::Display), 0) => {
+                    self.objects.insert(args[0], Object::Callback);
+                    self.send(args[0], 0, &[Arg::Uint(0)]);
+                    self.send(1, 1, &[Arg::Uint(args[0])]);
+                }
+                (Some(Object::Display), 1) => {
+                    self.objects.insert(args[0], Object::Registry);
+                    let globals = [
+                        (1, "zwlr_output_manager_v1", 2),
+                        (2, "wl_output", 4),
+                        (3, "wl_output", 4),
+                        (4, "zwlr_output_power_manager_v1", 1),
+                    ];
+                    for (global, interface, version) in globals {
+                        let global = [Arg::Uint(global), Arg::Str(interface), Arg::Uint(version)];
+                        self.send(args[0], 0, &global);
+                    }
+                }
+                // wl_registry.bind, the new id is the last argument
+                (Some(Object::Registry), 0) => {
+                    let new = *args.last().unwrap();
+                    match args[0] {
+                        1 => {
+                            self.objects.insert(new, Object::Manager);
+                            self.send_heads(new);
+                        }
+                        // wl_output.name, done
+                        2 | 3 => {
+                            let output = if args[0] == 2 { "eDP-1" } else { "HDMI-A-1" };
+                            self.names.insert(new, output.to_string());
+                            self.send(new, 4, &[Arg::Str(output)]);
+                            self.send(new, 2, &[]);
+                        }
+                        _ => {
+                            self.objects.insert(new, Object::PowerManager);
+                        }
+                    }
+                }
+                // get_output_power, answered with the mode
+                (Some(Object::PowerManager), 0) => {
+                    let output = name(&args[1]);
+                    let on = !self.off.lock().unwrap().contains(&output);
+                    self.objects.insert(args[0], Object::Power(output));
+                    self.send(args[0], 0, &[Arg::Uint(on as u32)]);
+                }
+                // set_mode
+                (Some(Object::Power(output)), 0) => {
+                    let output = output.clone();
+                    self.log(format!("{} power {}", output, args[0]));
+                    let mut off = self.off.lock().unwrap();
+                    off.retain(|o| *o != output);
+                    if args[0] == 0 {
+                        off.push(output);
+                    }
+                    drop(off);
+                    self.send(id, 0, &[Arg::Uint(args[0])]);
+                }
+                // create_configuration
+                (Some(Object::Manager), 0) => {
+                    self.objects.insert(args[0], Object::Config);
+                }
+                // enable_head, disable_head, apply, test, destroy
+                (Some(Object::Config), 0) => {
+                    let head = name(&args[1]);
+                    self.log(format!("enable {}", head));
+                    self.objects.insert(args[0], Object::ConfigHead(head));
+                }
+                (Some(Object::Config), 1) => self.log(format!("disable {}", name(&args[0]))),
+                (Some(Object::Config), 2 | 3) => {
+                    self.log(if opcode == 2 { "apply" } else { "test" }.to_string());
+                    self.send(id, 0, &[]);
+                }
+                (Some(Object::Config), 4) => self.send(1, 1, &[Arg::Uint(id)]),
+                (Some(Object::ConfigHead(head)), _) => {
+                    let request = match opcode {
+                        0 => format!("mode {}", name(&args[0])),
+                        1 => format!("custom {}x{}@{}", args[0], args[1], args[2]),
+                        2 => format!("position {},{}", args[0], args[1]),
+                        3 => format!("transform {}", args[0]),
+                        _ => format!("scale {}", args[0] as f64 / 256.0),
+                    };
+                    self.log(format!("{} {}", head, request));
+                }
+                _ => {}
+            }
+        }
+
+        fn serve(mut self) {
+            let mut header = [0u8; 8];
+            while self.stream.read_exact(&mut header).is_ok() {
+                let word = |b: &[u8]| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]);
+                let id = word(&header[..4]);
+                let size = (word(&header[4..]) >> 16) as usize;
+                let opcode = word(&header[4..]) & 0xffff;
+                let mut body = vec![0u8; size - 8];
+                if self.stream.read_exact(&mut body).is_err() {
+                    return;
+                }
+                // only the bind request has a string, skip it
+                let args: Vec<u32> = body.chunks(4).map(word).collect();
+                self.handle(id, opcode, &args);
+            }
+        }
+    }
+
+    /// start the compositor, returns its socket and the requests it receives
+    fn compositor(name: &str) -> (PathBuf, Arc<Mutex<Vec<String>>>) {
+        let path = std::env::temp_dir().join(format!("rsettings-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = requests.clone();
+        let off = Arc::new(Mutex::new(Vec::new()));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let client = Client {
+                    stream: stream.unwrap(),
+                    objects: HashMap::from([(1, Object::Display)]),
+                    names: HashMap::new(),
+                    off: off.clone(),
+                    next_id: 0xff00_0000,
+                    requests: log.clone(),
+                };
+                thread::spawn(move || client.serve());
+            }
+        });
+        (path, requests)
+    }
+
+    #[test]
+    fn outputs_from_compositor() {
+        let (path, _) = compositor("wlr_outputs");
+        let outputs = WlrOutput::new(path).outputs().unwrap();
+        assert_eq!(outputs.len(), 2);
+        let edp = &outputs[0];
+        assert_eq!(edp.name, "eDP-1");
+        assert_eq!(edp.description, "Sharp Corporation 0x14F9 (eDP-1)");
+        assert_eq!(edp.physical_size, "290x170 mm");
+        assert!(edp.enable);
+        assert_eq!(edp.mode.len(), 2);
+        assert_eq!(edp.now_mode.resolution, "1920x1080");
+        assert_eq!(edp.now_mode.refresh, 60.02);
+        assert_eq!(edp.scale, 1.25);
+        assert!(edp.transform == Transform::Normal);
+        assert_eq!(outputs[1].position, (1536, 0));
+        assert_eq!(edp.power, Some(true));
+        assert_eq!(edp.adaptive_sync, None);
+    }
+
+    #[test]
+    fn power_switches_the_named_output() {
+        let (path, requests) = compositor("wlr_power");
+        let backend = WlrOutput::new(path);
+        backend.set_power("HDMI-A-1", false).unwrap();
+        assert_eq!(backend.outputs().unwrap()[1].power, Some(false));
+        backend.set_power("HDMI-A-1", true).unwrap();
+        assert!(backend.set_power("DP-2", false).is_err());
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec!["HDMI-A-1 power 0", "HDMI-A-1 power 1"]
+        );
+    }
+
+    #[test]
+    fn adaptive_sync_goes_through_wlr_randr() {
+        let (path, _) = compositor("wlr_adaptive_sync");
+        let randr = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/wlr-randr/0.4.txt");
+        let fake = FakeRunner::new().with_output("wlr-randr", &fs::read_to_string(randr).unwrap());
+        let fake = Arc::new(fake);
+        let backend = WlrOutput::new(path).with_runner(Runner::new(fake.clone()));
+        let outputs = backend.outputs().unwrap();
+        assert_eq!(outputs[0].adaptive_sync, Some(false));
+        let mut hdmi = outputs[1].state();
+        assert_eq!(hdmi.adaptive_sync, Some(true));
+        hdmi.adaptive_sync = Some(false);
+        backend.apply(&[hdmi]).unwrap();
+        assert_eq!(
+            fake.calls().last().unwrap(),
+            "wlr-randr --output HDMI-A-1 --adaptive-sync disabled"
+        );
+    }
+
+    #[test]
+    fn configuration_covers_every_head() {
+        let (path, requests) = compositor("wlr_configure");
+        let backend = WlrOutput::new(path);
+        let mut hdmi = backend.outputs().unwrap()[1].state();
+        hdmi.mode = "2560x1440".to_string();
+        hdmi.refresh = 59.95;
+        hdmi.scale = 1.5;
+        backend.test(&[hdmi.clone()]).unwrap();
+        hdmi.mode = "1920x1080".to_string();
+        // not advertised, and not asked for as a custom mode
+        assert!(matches!(
+            backend.apply(&[hdmi.clone()]),
+            Err(Error::NotFound(_))
+        ));
+        hdmi.custom = true;
+        backend.apply(&[hdmi]).unwrap();
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![
+                "enable eDP-1",
+                "eDP-1 mode 1920x1080@60020",
+                "eDP-1 position 0,0",
+                "eDP-1 transform 0",
+                "eDP-1 scale 1.25",
+                "enable HDMI-A-1",
+                "HDMI-A-1 mode 2560x1440@59951",
+                "HDMI-A-1 position 1536,0",
+                "HDMI-A-1 transform 0",
+                "HDMI-A-1 scale 1.5",
+                "test",
+                "enable eDP-1",
+                "eDP-1 mode 1920x1080@60020",
+                "eDP-1 position 0,0",
+                "eDP-1 transform 0",
+                "eDP-1 scale 1.25",
+                "enable HDMI-A-1",
+                "HDMI-A-1 custom 1920x1080@59950",
+                "HDMI-A-1 position 1536,0",
+                "HDMI-A-1 transform 0",
+                "HDMI-A-1 scale 1.5",
+                "apply",
+            ]
+        );
+    }
+}
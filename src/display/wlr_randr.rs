@@ -0,0 +1,376 @@
+//! The `wlr-randr` tool: a backend running it and parsers of what it
+//! prints, `--json` of 0.4 and later or the text of every version.
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+use serde::Deserialize;
+
+use super::backend::DisplayBackend;
+use super::display::{Display, DisplayState, Resolution, Transform};
+use crate::error::error::{Error, Result};
+use crate::runner::runner::{Cmd, Runner};
+
+/// Fallback backend running the `wlr-randr` tool
+pub struct WlrRandr {
+    runner: Runner,
+    /// whether `wlr-randr --json` works, once tried
+    json: OnceLock<bool>,
+}
+
+impl WlrRandr {
+    pub fn new(runner: Runner) -> Self {
+        Self {
+            runner,
+            json: OnceLock::new(),
+        }
+    }
+
+    fn cmd(state: &DisplayState) -> Cmd {
+        let enable = if state.enable { "--on" } else { "--off" };
+        let mode = if state.custom {
+            "--custom-mode"
+        } else {
+            "--mode"
+        };
+        let adaptive_sync = state.adaptive_sync.map(|on| {
+            let value = if on { "enabled" } else { "disabled" };
+            ["--adaptive-sync", value]
+        });
+        // outputs without modes, e.g. headless ones, have none to set
+        let mode = (!state.mode.is_empty()).then(|| {
+            [
+                mode.to_string(),
+                format!("{}@{}", state.mode, state.refresh),
+            ]
+        });
+        Cmd::new("wlr-randr")
+            .arg("--output")
+            .arg(&state.name)
+            .args(mode.into_iter().flatten())
+            .arg("--scale")
+            .arg(state.scale.to_string())
+            .arg("--transform")
+            .arg(&state.transform)
+            .arg(enable)
+            .arg("--pos")
+            .arg(format!("{},{}", state.position.0, state.position.1))
+            .args(adaptive_sync.into_iter().flatten())
+    }
+}
+
+impl DisplayBackend for WlrRandr {
+    fn name(&self) -> &'static str {
+        "wlr-randr"
+    }
+
+    /// `--json` if this `wlr-randr` has it, older ones fail or print usage
+    fn outputs(&self) -> Result<Vec<Display>> {
+        if *self.json.get().unwrap_or(&true) {
+            let json = self
+                .runner
+                .stdout(&Cmd::new("wlr-randr").arg("--json"))
+                .ok()
+                .filter(|out| out.trim_start().starts_with('['));
+            let _ = self.json.set(json.is_some());
+            if let Some(json) = json {
+                return parse_json(&json);
+            }
+        }
+        parse(&self.runner.stdout(&Cmd::new("wlr-randr"))?)
+    }
+
+    /// wlr-randr can't check a configuration without applying it
+    fn test(&self, _states: &[DisplayState]) -> Result<()> {
+        Ok(())
+    }
+
+    fn apply(&self, states: &[DisplayState]) -> Result<()> {
+        for state in states {
+            self.runner.stdout(&Self::cmd(state))?;
+        }
+        Ok(())
+    }
+}
+
+/// Outputs listed by `wlr-randr`.
+///
+/// Only the output lines and their `Enabled:` line are required; other
+/// `Key: value` lines may come in any order or not at all, and unknown
+/// ones, e.g. `Make:` or `Serial:`, are skipped.
+pub fn parse(out: &str) -> Result<Vec<Display>> {
+    let mode_re = Regex::new(r"^(\d+x\d+) px, ([\d.]+) Hz(?: \((.*)\))?$").unwrap();
+    let mut outputs: Vec<(Display, Option<Resolution>, bool)> = Vec::new();
+    for line in out.lines().filter(|l| !l.trim().is_empty()) {
+        // 1. an unindented line starts an output: `NAME "DESCRIPTION"`
+        if !line.starts_with(char::is_whitespace) {
+            let (name, description) = line.split_once(' ').unwrap_or((line, ""));
+            let display = Display {
+                name: name.to_string(),
+                description: description.trim().trim_matches('"').to_string(),
+                scale: 1.0,
+                ..Default::default()
+            };
+            outputs.push((display, None, false));
+            continue;
+        }
+        let (display, preferred, enabled) = outputs
+            .last_mut()
+            .ok_or_else(|| Error::parse("wlr-randr output", line))?;
+        let line = line.trim();
+
+        // 2. modes, `WxH px, HZ Hz (preferred, current)`
+        if let Some(caps) = mode_re.captures(line) {
+            let mode = Resolution {
+                resolution: caps[1].to_string(),
+                refresh: caps[2].parse().map_err(|_| Error::parse("mode", line))?,
+                custom: false,
+            };
+            let flags = caps.get(3).map_or("", |m| m.as_str());
+            if flags.contains("current") {
+                display.now_mode = mode.clone();
+            }
+            if flags.contains("preferred") {
+                *preferred = Some(mode.clone());
+            }
+            display.mode.push(mode);
+            continue;
+        }
+
+        // 3. `Key: value` lines
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key, value.trim()),
+            None => continue,
+        };
+        match key {
+            "Physical size" => display.physical_size = value.to_string(),
+            "Enabled" => {
+                display.enable = value == "yes";
+                *enabled = true;
+            }
+            "Position" => {
+                display.position = value
+                    .split_once(',')
+                    .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+                    .ok_or_else(|| Error::parse("position", line))?
+            }
+            "Transform" => {
+                display.transform =
+                    Transform::from_str(value).ok_or_else(|| Error::parse("transform", line))?
+            }
+            "Scale" => display.scale = value.parse().map_err(|_| Error::parse("scale", line))?,
+            "Adaptive Sync" => display.adaptive_sync = Some(value == "enabled"),
+            _ => {}
+        }
+    }
+
+    // 4. every output says if it is enabled, else the output was cut
+    outputs
+        .into_iter()
+        .map(|(display, preferred, enabled)| {
+            if !enabled {
+                return Err(Error::parse("wlr-randr output", display.name));
+            }
+            Ok(display.with_mode(preferred))
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct Output {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    physical_size: Option<Size>,
+    enabled: bool,
+    #[serde(default)]
+    modes: Vec<Mode>,
+    /// missing for disabled outputs, as the rest below
+    #[serde(default)]
+    position: Option<Position>,
+    #[serde(default)]
+    transform: Option<String>,
+    #[serde(default)]
+    scale: Option<f64>,
+    #[serde(default)]
+    adaptive_sync: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct Size {
+    width: i32,
+    height: i32,
+}
+
+#[derive(Deserialize)]
+struct Position {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Deserialize)]
+struct Mode {
+    width: u32,
+    height: u32,
+    refresh: f64,
+    #[serde(default)]
+    preferred: bool,
+    #[serde(default)]
+    current: bool,
+}
+
+impl Mode {
+    fn resolution(&self) -> Resolution {
+        Resolution {
+            resolution: format!("{}x{}", self.width, self.height),
+            // json-c prints all digits of the double, `60.020000000000003`
+            refresh: (self.refresh * 1000.0).round() / 1000.0,
+            custom: false,
+        }
+    }
+}
+
+impl Output {
+    fn display(self) -> Result<Display> {
+        let transform = self.transform.as_deref().unwrap_or("normal");
+        let current = self.modes.iter().find(|m| m.current);
+        let preferred = self.modes.iter().find(|m| m.preferred);
+        let display = Display {
+            description: self.description.unwrap_or_default(),
+            enable: self.enabled,
+            physical_size: self
+                .physical_size
+                .map(|s| format!("{}x{} mm", s.width, s.height))
+                .unwrap_or_default(),
+            mode: self.modes.iter().map(Mode::resolution).collect(),
+            now_mode: current.map(Mode::resolution).unwrap_or_default(),
+            position: self.position.map(|p| (p.x, p.y)).unwrap_or_default(),
+            transform: Transform::from_str(transform)
+                .ok_or_else(|| Error::parse("transform", transform))?,
+            scale: self.scale.filter(|s| *s > 0.0).unwrap_or(1.0),
+            adaptive_sync: self.adaptive_sync,
+            name: self.name,
+            ..Default::default()
+        };
+        Ok(display.with_mode(preferred.map(Mode::resolution)))
+    }
+}
+
+/// outputs of `wlr-randr --json`
+pub fn parse_json(json: &str) -> Result<Vec<Display>> {
+    let outputs: Vec<Output> =
+        serde_json::from_str(json).map_err(|e| Error::parse("wlr-randr json", e.to_string()))?;
+    outputs.into_iter().map(Output::display).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::runner::FakeRunner;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    fn fixture(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/wlr-randr")
+            .join(name);
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    /// what a panel shows of `displays`, to compare parsers
+    fn summary(displays: &[Display]) -> Vec<String> {
+        displays
+            .iter()
+            .map(|d| {
+                let modes: Vec<String> = d
+                    .mode
+                    .iter()
+                    .map(|m| format!("{}@{}", m.resolution, m.refresh))
+                    .collect();
+                format!(
+                    "{:?} {} {} {:?} {:?}",
+                    d.state(),
+                    d.monitor(),
+                    d.physical_size,
+                    d.adaptive_sync,
+                    modes
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_recorded_layout_parses() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/wlr-randr");
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            let out = std::fs::read_to_string(&path).unwrap();
+            let displays = match path.extension().and_then(|e| e.to_str()) {
+                Some("json") => parse_json(&out),
+                _ => parse(&out),
+            };
+            let displays = displays.unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            assert!(!displays.is_empty(), "{}", path.display());
+        }
+        // the same outputs, either way
+        let text = parse(&fixture("0.4.txt")).unwrap();
+        assert_eq!(
+            summary(&text),
+            summary(&parse_json(&fixture("0.4.json")).unwrap())
+        );
+        let old = parse(&fixture("0.2.txt")).unwrap();
+        assert_eq!(
+            summary(&old)[0],
+            summary(&text[..1])[0].replace("Some(false)", "None")
+        );
+    }
+
+    #[test]
+    fn newer_lines_and_disabled_outputs() {
+        let displays = parse(&fixture("0.4.txt")).unwrap();
+        let hdmi = &displays[1];
+        assert_eq!(hdmi.monitor(), "Dell Inc. DELL U2720Q 8K2JR83");
+        assert_eq!(hdmi.transform, Transform::Roate90);
+        assert_eq!(hdmi.adaptive_sync, Some(true));
+        let dp = &displays[2];
+        assert!(!dp.enable);
+        assert_eq!(dp.now_mode.refresh, 143.998);
+        assert_eq!(dp.scale, 1.0);
+        let headless = &parse(&fixture("headless.txt")).unwrap()[0];
+        assert!(headless.mode.is_empty());
+        assert_eq!(headless.position, (-1920, 0));
+        assert_eq!(headless.physical_mm(), None);
+        // details before any output
+        assert!(parse("  Enabled: yes\n").is_err());
+    }
+
+    #[test]
+    fn negative_positions_are_kept() {
+        let displays = parse(&fixture("left-of-origin.txt")).unwrap();
+        assert_eq!(displays[0].position, (-1920, -216));
+        assert_eq!(
+            WlrRandr::cmd(&displays[0].state()).line(),
+            "wlr-randr --output DP-1 --mode 1920x1080@60 --scale 1 --transform normal --on --pos -1920,-216 --adaptive-sync disabled"
+        );
+    }
+
+    #[test]
+    fn outputs_prefer_json_once_supported() {
+        let fake =
+            Arc::new(FakeRunner::new().with_output("wlr-randr --json", &fixture("0.4.json")));
+        let backend = WlrRandr::new(Runner::new(fake.clone()));
+        assert_eq!(backend.outputs().unwrap().len(), 3);
+        backend.outputs().unwrap();
+        assert_eq!(fake.calls(), vec!["wlr-randr --json"; 2]);
+
+        let fake = Arc::new(FakeRunner::new().with_output("wlr-randr", &fixture("0.2.txt")));
+        let backend = WlrRandr::new(Runner::new(fake.clone()));
+        assert_eq!(backend.outputs().unwrap().len(), 2);
+        backend.outputs().unwrap();
+        assert_eq!(
+            fake.calls(),
+            vec!["wlr-randr --json", "wlr-randr", "wlr-randr"]
+        );
+    }
+}
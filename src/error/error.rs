@@ -0,0 +1,97 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors of every panel, shown to the user instead of panicking
+#[derive(Debug)]
+pub enum Error {
+    /// reading or writing a file failed
+    File { path: PathBuf, source: io::Error },
+    /// a command could not be started, e.g. it is not installed
+    Spawn { cmd: String, source: io::Error },
+    /// a command ran but exited with failure
+    Command { cmd: String, stderr: String },
+    /// output of a command or content of a file is not what we expect
+    Parse { what: &'static str, input: String },
+    /// a device, theme or option does not exist
+    NotFound(String),
+    /// an image can't be loaded
+    Image(image::ImageError),
+    /// wrong command line arguments
+    Usage(String),
+    /// displays can't be applied as arranged
+    Layout(String),
+    /// the wayland compositor refused a request or dropped the connection
+    Wayland(String),
+    /// a part left out, e.g. a panel of a profile that can't be read
+    Skipped {
+        what: &'static str,
+        source: Box<Error>,
+    },
+    /// several failures at once
+    Many(Vec<Error>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::File { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Spawn { cmd, source } => write!(f, "can't execute `{}`: {}", cmd, source),
+            Error::Command { cmd, stderr } => {
+                write!(f, "`{}` failed", cmd)?;
+                if !stderr.trim().is_empty() {
+                    write!(f, ": {}", stderr.trim())?;
+                }
+                Ok(())
+            }
+            Error::Parse { what, input } => write!(f, "can't parse {}: {:?}", what, input),
+            Error::NotFound(what) => write!(f, "{} not found", what),
+            Error::Image(e) => write!(f, "can't load image: {}", e),
+            Error::Usage(msg) => write!(f, "{}", msg),
+            Error::Layout(msg) => write!(f, "bad display layout: {}", msg),
+            Error::Wayland(msg) => write!(f, "wayland: {}", msg),
+            Error::Skipped { what, source } => write!(f, "skipped {}: {}", what, source),
+            Error::Many(errors) => {
+                let errors: Vec<String> = errors.iter().map(Error::to_string).collect();
+                write!(f, "{}", errors.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::File { source, .. } | Error::Spawn { source, .. } => Some(source),
+            Error::Image(e) => Some(e),
+            Error::Skipped { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+
+impl Error {
+    /// one error of all `errors`, `None` if there are none
+    pub fn many(mut errors: Vec<Error>) -> Option<Self> {
+        match errors.len() {
+            0 => None,
+            1 => errors.pop(),
+            _ => Some(Error::Many(errors)),
+        }
+    }
+
+    pub fn parse<S: Into<String>>(what: &'static str, input: S) -> Self {
+        Error::Parse {
+            what,
+            input: input.into(),
+        }
+    }
+}
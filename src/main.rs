@@ -1,19 +1,33 @@
+#![allow(clippy::module_inception)]
+
 mod appearance;
+mod cli;
 mod display;
+mod error;
 mod network;
+mod nightlight;
 mod power;
+mod profiles;
+mod runner;
 mod settings;
+mod sysfs;
 mod tools;
 
 use crate::egui::{FontData, FontDefinitions, FontFamily};
 use appearance::appearance::Appearance;
-use eframe::epaint::Vec2;
+use eframe::epaint::{Color32, Vec2};
 use eframe::{egui, epi, NativeOptions};
+use error::error::Error;
+use runner::runner::Runner;
 use std::collections::BTreeMap;
+use sysfs::sysfs::SysFs;
 
+#[derive(Default)]
 struct MySettings {
     now: u8,
     labels: BTreeMap<u8, Box<dyn settings::settings::Settings>>,
+    /// latest failure of each panel, shown as a banner above it
+    errors: BTreeMap<u8, Error>,
 }
 
 impl epi::App for MySettings {
@@ -44,12 +58,31 @@ impl epi::App for MySettings {
             egui::ScrollArea::new([true, true]).show(ui, |ui| {
                 if self.now != 0 {
                     let f = self.labels.get_mut(&self.now).unwrap();
-                    if !f.is_init() {
-                        f.init();
+                    if !f.is_init() && !self.errors.contains_key(&self.now) {
+                        if let Err(e) = f.init() {
+                            self.errors.insert(self.now, e);
+                        }
                     }
                     ui.heading(f.heading());
                     ui.separator();
-                    f.show(ui);
+                    if let Some(e) = self.errors.get(&self.now) {
+                        let mut dismiss = false;
+                        ui.horizontal(|ui| {
+                            ui.colored_label(Color32::RED, e.to_string());
+                            let button = if f.is_init() { "Dismiss" } else { "Retry" };
+                            dismiss = ui.button(button).clicked();
+                        });
+                        if dismiss {
+                            self.errors.remove(&self.now);
+                        }
+                        ui.separator();
+                    }
+                    if f.is_init() {
+                        f.show(ui);
+                    }
+                    if let Some(e) = f.take_error() {
+                        self.errors.insert(self.now, e);
+                    }
                 }
             });
         });
@@ -63,9 +96,13 @@ impl epi::App for MySettings {
                             if ui.button("Quit").clicked() {
                                 frame.quit();
                             }
-                            if ui.button("Apply").clicked() {
-                                let f = self.labels.get_mut(&self.now).unwrap();
-                                f.apply();
+                            let f = self.labels.get_mut(&self.now).unwrap();
+                            let apply = egui::Button::new("Apply");
+                            if ui.add_enabled(f.can_apply(), apply).clicked() {
+                                match f.apply() {
+                                    Ok(()) => self.errors.remove(&self.now),
+                                    Err(e) => self.errors.insert(self.now, e),
+                                };
                             }
                         });
                     });
@@ -73,6 +110,11 @@ impl epi::App for MySettings {
             });
     }
 
+    /// drop the panels now, reverting unconfirmed display changes
+    fn on_exit(&mut self) {
+        self.labels.clear();
+    }
+
     fn setup(
         &mut self,
         ctx: &egui::Context,
@@ -94,30 +136,34 @@ impl epi::App for MySettings {
         ctx.set_fonts(fonts);
         ctx.set_pixels_per_point(2.5);
         ctx.set_visuals(egui::Visuals::light());
+        let runner = Runner::from_env().unwrap_or_else(|e| {
+            eprintln!("rsettings: {}", e);
+            std::process::exit(1);
+        });
         // 1. add displays
-        let displays = display::display::Displays::default();
+        let sysfs = SysFs::from_env();
+        let displays = display::display::Displays::new(runner.clone())
+            .with_sysfs(sysfs.clone())
+            .with_files(display::display::DisplayFiles::from_env());
         self.add_label(1, Box::new(displays));
         // 2. add appearance
-        let appearance = Appearance::default();
+        let appearance = Appearance::new(runner.clone());
         self.add_label(2, Box::new(appearance));
         // 3. add power manager
-        let power = power::power::Power::default();
+        let power = power::power::Power::new(sysfs.clone());
         self.add_label(3, Box::new(power));
         // 4. add network
-        let network = network::network::Network::default();
+        let network = network::network::Network::new(runner.clone());
         self.add_label(4, Box::new(network));
         // 5. add tools
-        let tools = tools::tools::Tools::default();
+        let tools = tools::tools::Tools::new(runner.clone());
         self.add_label(5, Box::new(tools));
-    }
-}
-
-impl Default for MySettings {
-    fn default() -> Self {
-        Self {
-            now: 0,
-            labels: BTreeMap::<u8, Box<dyn settings::settings::Settings>>::new(),
-        }
+        // 6. add profiles
+        let profiles = profiles::profiles::Profiles::new(runner.clone(), sysfs);
+        self.add_label(6, Box::new(profiles));
+        // 7. add night light
+        let night_light = nightlight::nightlight::NightLight::new(runner);
+        self.add_label(7, Box::new(night_light));
     }
 }
 
@@ -128,10 +174,18 @@ impl MySettings {
 }
 
 fn main() {
+    // headless mode when a command is given
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::cli::run(&args));
+    }
+
     let app = MySettings::default();
-    let mut native_options = NativeOptions::default();
-    native_options.initial_window_size = Some(Vec2::new(800.0, 600.0));
-    native_options.min_window_size = Some(Vec2::new(400.0, 300.0));
-    native_options.resizable = true;
+    let native_options = NativeOptions {
+        initial_window_size: Some(Vec2::new(800.0, 600.0)),
+        min_window_size: Some(Vec2::new(400.0, 300.0)),
+        resizable: true,
+        ..Default::default()
+    };
     eframe::run_native(Box::new(app), native_options)
 }
@@ -1,9 +1,11 @@
+use crate::error::error::{Error, Result};
+use crate::runner::runner::{Cmd, Runner};
 use crate::settings::settings::Settings;
 
 use eframe::egui::{self, Spinner};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashSet,
-    process::Command,
     sync::{
         mpsc::{channel, Receiver, Sender},
         Arc, Mutex,
@@ -17,9 +19,11 @@ pub struct Network {
     known_wifis: HashSet<String>,
     current_wifi_id: u8,
     scanwifiing: Arc<Mutex<bool>>,
-    tx: Sender<Vec<Wifi>>,
-    rx: Receiver<Vec<Wifi>>,
+    tx: Sender<Result<Vec<Wifi>>>,
+    rx: Receiver<Result<Vec<Wifi>>>,
     init: bool,
+    runner: Runner,
+    error: Option<Error>,
 }
 
 #[derive(Default)]
@@ -29,10 +33,11 @@ struct Device {
 }
 
 #[derive(Default)]
-struct Wifi {
+pub struct Wifi {
     id: u8,
     bssid: String,
     ssid: String,
+    current: bool,
     mode: String,
     chan: u8,
     rate: String,
@@ -41,6 +46,13 @@ struct Wifi {
     security: String,
 }
 
+/// Saved network configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NetworkState {
+    /// wifi to connect to
+    pub wifi: Option<String>,
+}
+
 impl Default for Network {
     fn default() -> Self {
         let (tx, rx) = channel();
@@ -53,23 +65,26 @@ impl Default for Network {
             init: false,
             tx,
             rx,
+            runner: Runner::default(),
+            error: None,
         }
     }
 }
 
 impl Settings for Network {
-    fn init(&mut self) {
+    fn init(&mut self) -> Result<()> {
         // 1. get devices
-        self.get_devices();
+        self.get_devices()?;
 
         // 2. scan wifi and get wifi info
         self.scan_wifi();
 
         // 3. get known_wifis
-        self.get_known_wifi();
+        self.get_known_wifi()?;
 
         // 4. change init status
         self.init = true;
+        Ok(())
     }
 
     fn is_init(&self) -> bool {
@@ -80,27 +95,24 @@ impl Settings for Network {
         "Network"
     }
 
-    fn apply(&mut self) {
+    fn apply(&mut self) -> Result<()> {
         // 1. apply devices
         for device in self.devices.iter() {
-            let mut connect = "disconnect";
-            if device.status {
-                connect = "connect";
-            }
-            Command::new("nmcli")
-                .args(["device", connect, device.device.as_str()])
-                .spawn()
-                .unwrap();
+            let connect = if device.status {
+                "connect"
+            } else {
+                "disconnect"
+            };
+            let cmd = Cmd::new("nmcli").args(["device", connect, device.device.as_str()]);
+            self.runner.start(&cmd)?;
         }
         // 2. apply wifi
         for wifi in self.live_wifis.iter() {
             if self.current_wifi_id == wifi.id {
-                Command::new("nmcli")
-                    .args(["dev", "wifi", "connect", wifi.ssid.as_str()])
-                    .spawn()
-                    .unwrap();
+                self.runner.start(&Self::connect_cmd(&wifi.ssid))?;
             }
         }
+        Ok(())
     }
 
     fn show(&mut self, ui: &mut eframe::egui::Ui) {
@@ -122,8 +134,10 @@ impl Settings for Network {
                         self.scan_wifi();
                     }
                     ui.end_row();
-                    if let Ok(wifis) = self.rx.try_recv() {
-                        self.live_wifis = wifis;
+                    match self.rx.try_recv() {
+                        Ok(Ok(wifis)) => self.live_wifis = wifis,
+                        Ok(Err(e)) => self.error = Some(e),
+                        Err(_) => {}
                     }
                     egui::Grid::new("wifi").num_columns(6).show(ui, |ui| {
                         for wifi in self.live_wifis.iter_mut() {
@@ -137,23 +151,81 @@ impl Settings for Network {
             }
         });
     }
+
+    fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
 }
 
 impl Network {
-    fn get_devices(&mut self) {
-        let output = Command::new("nmcli")
-            .args(["-t", "d"])
-            .output()
-            .expect("execute nmcli error");
-        let output = String::from_utf8(output.stdout).unwrap();
-        let mut outs = output.lines();
-        while let Some(line) = outs.next() {
-            let mut datas = line.split(':');
-            let mut device = Device::default();
-            device.device = datas.next().unwrap().to_string();
-            device.status = true;
-            self.devices.push(device);
+    pub fn new(runner: Runner) -> Self {
+        Self {
+            runner,
+            ..Default::default()
+        }
+    }
+
+    /// names of all network devices
+    pub fn device_names(&mut self) -> Result<Vec<String>> {
+        self.get_devices()?;
+        Ok(self.devices.iter().map(|d| d.device.clone()).collect())
+    }
+
+    /// scan wifi and wait for the result
+    pub fn wifi_list(&self) -> Result<Vec<Wifi>> {
+        let output = self
+            .runner
+            .stdout(&Cmd::new("nmcli").args(["-t", "device", "wifi", "list"]))?;
+        Self::parser_wifis(&output)
+    }
+
+    /// connect to wifi `ssid` and wait until nmcli is done
+    pub fn connect(&self, ssid: &str) -> Result<()> {
+        self.runner.stdout(&Self::connect_cmd(ssid))?;
+        Ok(())
+    }
+
+    /// currently connected wifi
+    pub fn state(&self) -> Result<NetworkState> {
+        let wifi = self
+            .wifi_list()?
+            .into_iter()
+            .find(|wifi| wifi.current)
+            .map(|wifi| wifi.ssid);
+        Ok(NetworkState { wifi })
+    }
+
+    /// connect to the wifi of `state` unless already connected
+    pub fn restore(&self, state: &NetworkState) -> Result<()> {
+        if let Some(ssid) = &state.wifi {
+            if self.state()?.wifi.as_ref() != Some(ssid) {
+                self.connect(ssid)?;
+            }
         }
+        Ok(())
+    }
+
+    fn connect_cmd(ssid: &str) -> Cmd {
+        Cmd::new("nmcli").args(["dev", "wifi", "connect", ssid])
+    }
+
+    fn get_devices(&mut self) -> Result<()> {
+        let output = self.runner.stdout(&Cmd::new("nmcli").args(["-t", "d"]))?;
+        self.devices = Self::parser_devices(&output);
+        Ok(())
+    }
+
+    fn parser_devices(output: &str) -> Vec<Device> {
+        let mut devices = Vec::new();
+        for line in output.lines() {
+            if let Some(device) = line.split(':').next() {
+                devices.push(Device {
+                    device: device.to_string(),
+                    status: true,
+                });
+            }
+        }
+        devices
     }
 
     fn scan_wifi(&self) {
@@ -162,64 +234,71 @@ impl Network {
         drop(scanwifiing);
         let scanwifiing = self.scanwifiing.clone();
         let tx = self.tx.clone();
+        let runner = self.runner.clone();
         thread::spawn(move || {
             // 1. scan wifi
             println!("scan wifi");
-            let output = Command::new("nmcli")
-                .args(["-t", "device", "wifi", "list"])
-                .output()
-                .unwrap();
-            let stdout = String::from_utf8(output.stdout).unwrap();
-            let mut lines = stdout.lines();
+            let output = runner.stdout(&Cmd::new("nmcli").args(["-t", "device", "wifi", "list"]));
 
             // 2. parser wifi
-            let mut id = 0;
-            let mut wifis = Vec::new();
-            while let Some(line) = lines.next() {
-                let mut wifi = Wifi::default();
-                // a. need to deal with bssid separately
-                let line = line.replace("\\:", "-");
-                let mut data = line.split(':');
-                let current = data.next().unwrap();
-                wifi.bssid = data.next().unwrap().replace('-', ":");
-                wifi.ssid = data.next().unwrap().to_string();
-                if wifi.ssid.is_empty() {
-                    continue;
-                }
-                wifi.mode = data.next().unwrap().to_string();
-                wifi.chan = data.next().unwrap().parse().unwrap();
-                wifi.rate = data.next().unwrap().to_string();
-                wifi.signal = data.next().unwrap().parse().unwrap();
-                wifi.bras = data.next().unwrap().to_string();
-                wifi.security = data.next().unwrap().to_string();
-                wifi.id = id;
-
-                // b. if current wifi
-                if current == "*" {
-                    wifi.ssid.push_str(" (*)");
-                }
-                wifis.push(wifi);
-                id += 1;
-            }
+            let wifis = output.and_then(|output| Self::parser_wifis(&output));
             let mut scanwifiing = scanwifiing.lock().unwrap();
             *scanwifiing = false;
             drop(scanwifiing);
-            tx.send(wifis).unwrap();
+            let _ = tx.send(wifis);
             println!("end scan wifi");
         });
     }
 
-    fn get_known_wifi(&mut self) {
-        let output = Command::new("nmcli")
-            .args(["-t", "connection", "show"])
-            .output()
-            .expect("nmcli execute error");
-        let output = String::from_utf8(output.stdout).unwrap();
-        let mut lines = output.lines();
-        while let Some(line) = lines.next() {
-            let mut data = line.split(";");
-            self.known_wifis.insert(data.next().unwrap().to_string());
+    fn parser_wifis(output: &str) -> Result<Vec<Wifi>> {
+        let mut id = 0;
+        let mut wifis = Vec::new();
+        for raw in output.lines() {
+            // a. need to deal with bssid separately
+            let line = raw.replace("\\:", "-");
+            let mut data = line.split(':');
+            let mut next = || data.next().ok_or_else(|| Error::parse("wifi", raw));
+            let current = next()? == "*";
+            let bssid = next()?.replace('-', ":");
+            let ssid = next()?.to_string();
+            if ssid.is_empty() {
+                continue;
+            }
+            wifis.push(Wifi {
+                id,
+                bssid,
+                ssid,
+                current,
+                mode: next()?.to_string(),
+                chan: next()?
+                    .parse()
+                    .map_err(|_| Error::parse("wifi channel", raw))?,
+                rate: next()?.to_string(),
+                signal: next()?
+                    .parse()
+                    .map_err(|_| Error::parse("wifi signal", raw))?,
+                bras: next()?.to_string(),
+                security: next()?.to_string(),
+            });
+            id += 1;
         }
+        Ok(wifis)
+    }
+
+    fn get_known_wifi(&mut self) -> Result<()> {
+        let output = self
+            .runner
+            .stdout(&Cmd::new("nmcli").args(["-t", "connection", "show"]))?;
+        self.known_wifis = Self::parser_known_wifis(&output);
+        Ok(())
+    }
+
+    fn parser_known_wifis(output: &str) -> HashSet<String> {
+        output
+            .lines()
+            .filter_map(|line| line.split(':').next())
+            .map(|name| name.to_string())
+            .collect()
     }
 }
 
@@ -233,13 +312,70 @@ impl Device {
 }
 
 impl Wifi {
+    /// one line summary, e.g. `HomeNet (*) 82% WPA2`
+    pub fn summary(&self) -> String {
+        format!("{} {}% {}", self.label(), self.signal, self.security)
+    }
+
+    /// ssid, marked with `(*)` if connected
+    fn label(&self) -> String {
+        if self.current {
+            format!("{} (*)", self.ssid)
+        } else {
+            self.ssid.clone()
+        }
+    }
+
     fn show(&mut self, ui: &mut eframe::egui::Ui, id: &mut u8) {
         ui.radio_value(id, self.id, "");
-        ui.label(&self.ssid);
+        ui.label(self.label()).on_hover_text(&self.bssid);
         ui.label(&self.mode);
         ui.label(self.chan.to_string().as_str());
         ui.label(&self.rate);
-        ui.label(self.signal.to_string().as_str());
+        ui.label(self.signal.to_string().as_str())
+            .on_hover_text(&self.bras);
         ui.label(&self.security);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/commands")
+            .join(name);
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn parser_nmcli() {
+        let devices = Network::parser_devices(&fixture("nmcli_-t_d"));
+        let names: Vec<&str> = devices.iter().map(|d| d.device.as_str()).collect();
+        assert_eq!(names, vec!["wlan0", "enp3s0", "lo"]);
+
+        let wifis = Network::parser_wifis(&fixture("nmcli_-t_device_wifi_list")).unwrap();
+        assert_eq!(wifis.len(), 2);
+        assert_eq!(wifis[0].ssid, "HomeNet");
+        assert!(wifis[0].current);
+        assert_eq!(wifis[0].summary(), "HomeNet (*) 82% WPA2");
+        assert_eq!(wifis[0].bssid, "AA:BB:CC:DD:EE:01");
+        assert_eq!(wifis[0].signal, 82);
+        assert_eq!(wifis[1].security, "WPA2 WPA3");
+
+        let known = Network::parser_known_wifis(&fixture("nmcli_-t_connection_show"));
+        assert!(known.contains("HomeNet"));
+        assert!(known.contains("Wired connection 1"));
+    }
+
+    #[test]
+    fn parser_wifis_rejects_bad_signal() {
+        let out = "*:AA\\:BB\\:CC\\:DD\\:EE\\:01:HomeNet:Infra:6:270 Mbit/s:strong:_:WPA2\n";
+        assert!(matches!(
+            Network::parser_wifis(out),
+            Err(Error::Parse { .. })
+        ));
+    }
+}
@@ -0,0 +1,170 @@
+use std::cell::RefCell;
+use std::os::unix::io::AsRawFd;
+use std::rc::{Rc, Weak};
+
+use wayland_client::{Display as WlDisplay, EventQueue, Main};
+use wayland_protocols::wlr::unstable::gamma_control::v1::client::{
+    zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1,
+    zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+};
+
+use crate::display::wayland::{self, roundtrip, shm_file, Output};
+use crate::error::error::{Error, Result};
+
+/// temperature that leaves colors as they are, K
+pub const NEUTRAL: u32 = 6500;
+
+/// gamma control of one output
+struct Control {
+    proxy: Main<ZwlrGammaControlV1>,
+    /// entries of each ramp, once the compositor told
+    size: Rc<RefCell<Option<u32>>>,
+}
+
+/// Gamma tables of all outputs, held by the `wlr-gamma-control` protocol.
+///
+/// The compositor restores the tables when this is dropped.
+pub struct Gamma {
+    _display: WlDisplay,
+    queue: EventQueue,
+    manager: Main<ZwlrGammaControlManagerV1>,
+    outputs: Rc<RefCell<Vec<Output>>>,
+    /// control of the output at the same index, `None` until asked or failed
+    controls: Rc<RefCell<Vec<Option<Control>>>>,
+}
+
+impl Gamma {
+    pub fn connect() -> Result<Self> {
+        // 1. connect, binding outputs as they come
+        let display = WlDisplay::connect_to_env().map_err(|e| Error::Wayland(e.to_string()))?;
+        let mut queue = display.create_event_queue();
+        let attached = (*display).clone().attach(queue.token());
+        let (globals, outputs) = wayland::globals(&attached);
+        roundtrip(&mut queue)?;
+
+        // 2. bind the gamma manager
+        let manager = globals
+            .instantiate_exact::<ZwlrGammaControlManagerV1>(1)
+            .map_err(|_| Error::NotFound("zwlr_gamma_control_manager_v1".to_string()))?;
+        Ok(Self {
+            _display: display,
+            queue,
+            manager,
+            outputs,
+            controls: Rc::default(),
+        })
+    }
+
+    /// Tint every output as `temperature` K, plugged ones included.
+    pub fn set(&mut self, temperature: u32) -> Result<()> {
+        // 1. a gamma control for new outputs
+        roundtrip(&mut self.queue)?;
+        {
+            let outputs = self.outputs.borrow();
+            let mut controls = self.controls.borrow_mut();
+            controls.resize_with(outputs.len(), || None);
+            for (index, output) in outputs.iter().enumerate() {
+                if controls[index].is_some() {
+                    continue;
+                }
+                let proxy = self.manager.get_gamma_control(&output.proxy);
+                let size: Rc<RefCell<Option<u32>>> = Rc::default();
+                let events = size.clone();
+                let all = Rc::downgrade(&self.controls);
+                proxy.quick_assign(move |proxy, event, _| match event {
+                    zwlr_gamma_control_v1::Event::GammaSize { size } => {
+                        *events.borrow_mut() = Some(size)
+                    }
+                    // another client holds the output, or it is gone: try
+                    // again on the next call
+                    zwlr_gamma_control_v1::Event::Failed => forget(&all, index, &proxy),
+                    _ => {}
+                });
+                controls[index] = Some(Control { proxy, size });
+            }
+        }
+        roundtrip(&mut self.queue)?;
+
+        // 2. the same ramps for all, files open until sent
+        let white = whitepoint(temperature);
+        let mut files = Vec::new();
+        for control in self.controls.borrow().iter().flatten() {
+            let size = match *control.size.borrow() {
+                Some(size) => size as usize,
+                None => continue,
+            };
+            let file = shm_file(&ramps(size, white))?;
+            control.proxy.set_gamma(file.as_raw_fd());
+            files.push(file);
+        }
+        roundtrip(&mut self.queue)
+    }
+}
+
+/// destroy `proxy` and clear it from `controls`
+fn forget(
+    controls: &Weak<RefCell<Vec<Option<Control>>>>,
+    index: usize,
+    proxy: &Main<ZwlrGammaControlV1>,
+) {
+    if let Some(controls) = controls.upgrade() {
+        if let Some(control) = controls.borrow_mut().get_mut(index) {
+            *control = None;
+        }
+    }
+    proxy.destroy();
+}
+
+/// Red, green and blue of a black body at `temperature` K, relative to
+/// [`NEUTRAL`]; after Tanner Helland's fit of the CIE tables.
+pub fn whitepoint(temperature: u32) -> [f64; 3] {
+    let rgb = |temperature: u32| {
+        let t = temperature.clamp(1000, 40_000) as f64 / 100.0;
+        let red = if t <= 66.0 {
+            255.0
+        } else {
+            329.698_727_446 * (t - 60.0).powf(-0.133_204_759_2)
+        };
+        let green = if t <= 66.0 {
+            99.470_802_586_1 * t.ln() - 161.119_568_166_1
+        } else {
+            288.122_169_528_3 * (t - 60.0).powf(-0.075_514_849_2)
+        };
+        let blue = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.517_731_223_1 * (t - 10.0).ln() - 305.044_792_730_7
+        };
+        [red, green, blue].map(|c| c.clamp(0.0, 255.0))
+    };
+    let (color, neutral) = (rgb(temperature), rgb(NEUTRAL));
+    [0, 1, 2].map(|i| (color[i] / neutral[i]).min(1.0))
+}
+
+/// red, green and blue ramps of `size` entries, native endian as the protocol wants
+fn ramps(size: usize, white: [f64; 3]) -> Vec<u8> {
+    let last = size.saturating_sub(1).max(1) as f64;
+    white
+        .iter()
+        .flat_map(|c| (0..size).map(move |i| (i as f64 / last * c * u16::MAX as f64) as u16))
+        .flat_map(u16::to_ne_bytes)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warm_whitepoint_dims_blue() {
+        assert_eq!(whitepoint(NEUTRAL), [1.0, 1.0, 1.0]);
+        let [red, green, blue] = whitepoint(3400);
+        assert_eq!(red, 1.0);
+        assert!(green < 0.8 && blue < green);
+        let bytes = ramps(256, whitepoint(NEUTRAL));
+        assert_eq!(bytes.len(), 3 * 256 * 2);
+        assert_eq!(u16::from_ne_bytes([bytes[510], bytes[511]]), u16::MAX);
+    }
+}
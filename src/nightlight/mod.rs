@@ -0,0 +1,3 @@
+pub mod gamma;
+pub mod nightlight;
+pub mod sun;
@@ -0,0 +1,595 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use eframe::egui::{ComboBox, DragValue, Grid, Slider, TextEdit, Ui};
+use serde::{Deserialize, Serialize};
+
+use super::gamma::{Gamma, NEUTRAL};
+use super::sun::{sun, Sun};
+use crate::error::error::{Error, Result};
+use crate::runner::runner::{Cmd, Process, Runner};
+use crate::settings::settings::{config_home, Settings};
+
+/// minutes to fade between day and night
+const TRANSITION: i64 = 30;
+const DAY: i64 = 24 * 60;
+
+/// When the night light is on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Off,
+    /// always at the night temperature
+    Manual,
+    /// from `start` to `end`
+    Schedule,
+    /// from sunset to sunrise at `latitude`, `longitude`
+    Sun,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 4] = [Mode::Off, Mode::Manual, Mode::Schedule, Mode::Sun];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Mode::Off => "off",
+            Mode::Manual => "manual",
+            Mode::Schedule => "schedule",
+            Mode::Sun => "sun",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Mode::Off => "Off",
+            Mode::Manual => "Always on",
+            Mode::Schedule => "Schedule",
+            Mode::Sun => "Sunset to sunrise",
+        }
+    }
+}
+
+/// Night light settings, one toml file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub mode: Mode,
+    /// at night, K
+    pub temperature: u32,
+    /// during the day, K
+    pub day_temperature: u32,
+    /// `HH:MM` local time, for [`Mode::Schedule`]
+    pub start: String,
+    pub end: String,
+    /// degrees north, for [`Mode::Sun`]
+    pub latitude: f64,
+    /// degrees east
+    pub longitude: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Off,
+            temperature: 4000,
+            day_temperature: NEUTRAL,
+            start: "20:00".to_string(),
+            end: "07:00".to_string(),
+            latitude: 0.0,
+            longitude: 0.0,
+        }
+    }
+}
+
+/// Night of one day, local minutes since midnight
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Night {
+    Between { start: i64, end: i64 },
+    Always,
+    Never,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/rsettings/nightlight.toml`, default `~/.config/rsettings/nightlight.toml`
+    pub fn path() -> PathBuf {
+        config_home().join("rsettings/nightlight.toml")
+    }
+
+    /// the config at `path`, defaults if it doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let s = fs::read_to_string(path).map_err(|source| Error::File {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        toml::from_str(&s).map_err(|e| Error::parse("night light", e.to_string()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        self.check()?;
+        let s = toml::to_string(self).map_err(|e| Error::parse("night light", e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|source| Error::File {
+                path: dir.to_path_buf(),
+                source,
+            })?;
+        }
+        fs::write(path, s).map_err(|source| Error::File {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// `start` and `end` are times and the position is on earth
+    pub fn check(&self) -> Result<()> {
+        minutes(&self.start)?;
+        minutes(&self.end)?;
+        if self.latitude.abs() > 90.0 || self.longitude.abs() > 180.0 {
+            return Err(Error::parse(
+                "position",
+                format!("{}, {}", self.latitude, self.longitude),
+            ));
+        }
+        Ok(())
+    }
+
+    /// night of the local day `day`, `offset` seconds east of UTC
+    fn night(&self, day: i64, offset: i64) -> Result<Night> {
+        let local = |unix: i64| (unix + offset).rem_euclid(86_400) / 60;
+        Ok(match self.mode {
+            Mode::Off => Night::Never,
+            Mode::Manual => Night::Always,
+            Mode::Schedule => Night::Between {
+                start: minutes(&self.start)?,
+                end: minutes(&self.end)?,
+            },
+            Mode::Sun => match sun(day, self.latitude, self.longitude) {
+                Sun::Rises { sunrise, sunset } => Night::Between {
+                    start: local(sunset),
+                    end: local(sunrise),
+                },
+                Sun::AlwaysUp => Night::Never,
+                Sun::AlwaysDown => Night::Always,
+            },
+        })
+    }
+
+    /// Temperature at unix time `now`, local time `offset` seconds east of UTC.
+    ///
+    /// It fades from day to night in [`TRANSITION`] minutes after the
+    /// start of the night, and back after its end.
+    pub fn temperature_at(&self, now: i64, offset: i64) -> Result<u32> {
+        let local = now + offset;
+        let minute = local.rem_euclid(86_400) / 60;
+        let night = match self.night(local.div_euclid(86_400), offset)? {
+            Night::Never if self.mode == Mode::Off => return Ok(NEUTRAL),
+            Night::Never => 0.0,
+            Night::Always => 1.0,
+            Night::Between { start, end } => {
+                let since = |t: i64| (minute - t).rem_euclid(DAY);
+                let fade = |m: i64| (m as f64 / TRANSITION as f64).min(1.0);
+                if since(start) < (end - start).rem_euclid(DAY) {
+                    fade(since(start))
+                } else {
+                    1.0 - fade(since(end))
+                }
+            }
+        };
+        let (day, temperature) = (self.day_temperature as f64, self.temperature as f64);
+        Ok((day + (temperature - day) * night).round() as u32)
+    }
+
+    /// `wlsunset` doing the same, for compositors without gamma control
+    pub fn wlsunset(&self) -> Result<Option<Cmd>> {
+        self.check()?;
+        let cmd = Cmd::new("wlsunset");
+        let cmd = match self.mode {
+            Mode::Off => return Ok(None),
+            // night all day but a minute; without times it wants a
+            // location, and the day temperature must be higher
+            Mode::Manual => cmd
+                .arg("-t")
+                .arg(self.temperature.to_string())
+                .arg("-T")
+                .arg((self.temperature + 1).to_string())
+                .arg("-S")
+                .arg("23:59")
+                .arg("-s")
+                .arg("00:00")
+                .arg("-d")
+                .arg("60"),
+            Mode::Schedule => cmd
+                .arg("-t")
+                .arg(self.temperature.to_string())
+                .arg("-T")
+                .arg(self.day_temperature.to_string())
+                .arg("-S")
+                .arg(&self.end)
+                .arg("-s")
+                .arg(&self.start)
+                .arg("-d")
+                .arg((TRANSITION * 60).to_string()),
+            Mode::Sun => cmd
+                .arg("-t")
+                .arg(self.temperature.to_string())
+                .arg("-T")
+                .arg(self.day_temperature.to_string())
+                .arg("-l")
+                .arg(self.latitude.to_string())
+                .arg("-L")
+                .arg(self.longitude.to_string()),
+        };
+        Ok(Some(cmd))
+    }
+}
+
+/// minutes since midnight of `HH:MM`
+fn minutes(time: &str) -> Result<i64> {
+    let bad = || Error::parse("time", time);
+    let (h, m) = time.trim().split_once(':').ok_or_else(bad)?;
+    let (h, m): (i64, i64) = (h.parse().map_err(|_| bad())?, m.parse().map_err(|_| bad())?);
+    if !(0..24).contains(&h) || !(0..60).contains(&m) {
+        return Err(bad());
+    }
+    Ok(h * 60 + m)
+}
+
+/// `HH:MM` of local minutes since midnight
+fn clock(minutes: i64) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// seconds east of UTC of the local time, as `date` tells
+pub fn utc_offset(runner: &Runner) -> Result<i64> {
+    let out = runner.stdout(&Cmd::new("date").arg("+%z"))?;
+    let out = out.trim();
+    let bad = || Error::parse("utc offset", out);
+    let (sign, digits) = match out.split_at_checked(1).ok_or_else(bad)? {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return Err(bad()),
+    };
+    if digits.len() != 4 {
+        return Err(bad());
+    }
+    let (h, m): (i64, i64) = (
+        digits[..2].parse().map_err(|_| bad())?,
+        digits[2..].parse().map_err(|_| bad())?,
+    );
+    Ok(sign * (h * 3600 + m * 60))
+}
+
+/// seconds since the unix epoch
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Keeps the screens tinted as the config says, with gamma control or wlsunset
+pub struct Service {
+    runner: Runner,
+    path: PathBuf,
+    /// the compositor has gamma control, so wlsunset isn't needed
+    gamma_control: bool,
+    /// held while tinting only, so others may take the outputs meanwhile
+    gamma: Option<Gamma>,
+    /// wlsunset and the config it was started for
+    wlsunset: Option<Box<dyn Process>>,
+    started: Option<Config>,
+}
+
+impl Service {
+    /// Use gamma control if the compositor has it, else `wlsunset`.
+    ///
+    /// Fake runners always get `wlsunset`, so tests don't touch the screen.
+    pub fn new(runner: Runner, path: PathBuf) -> Self {
+        let gamma = if runner.is_fake() {
+            None
+        } else {
+            Gamma::connect().ok()
+        };
+        Self {
+            runner,
+            path,
+            gamma_control: gamma.is_some(),
+            gamma,
+            wlsunset: None,
+            started: None,
+        }
+    }
+
+    /// Follow the config once at unix time `now`.
+    pub fn step(&mut self, now: i64) -> Result<()> {
+        let config = Config::load(&self.path)?;
+
+        // 1. tint ourselves, outputs plugged meanwhile too; when off, the
+        // compositor restores the tables once gamma control is dropped
+        if self.gamma_control {
+            if config.mode == Mode::Off {
+                self.gamma = None;
+                return Ok(());
+            }
+            let gamma = match &mut self.gamma {
+                Some(gamma) => gamma,
+                None => self.gamma.insert(Gamma::connect()?),
+            };
+            let offset = utc_offset(&self.runner)?;
+            return gamma.set(config.temperature_at(now, offset)?);
+        }
+
+        // 2. restart wlsunset when the config changed
+        if self.started.as_ref() == Some(&config) {
+            return Ok(());
+        }
+        if let Some(mut wlsunset) = self.wlsunset.take() {
+            // it may be gone already
+            let _ = wlsunset.kill();
+        }
+        if let Some(cmd) = config.wlsunset()? {
+            self.wlsunset = Some(self.runner.start(&cmd)?);
+        }
+        self.started = Some(config);
+        Ok(())
+    }
+
+    /// Step every `interval` forever, failures are reported and retried.
+    pub fn run(&mut self, interval: Duration) {
+        loop {
+            if let Err(e) = self.step(now()) {
+                eprintln!("rsettings: {}", e);
+                self.started = None;
+            }
+            thread::sleep(interval);
+        }
+    }
+}
+
+/// Night light panel, editing the config the service follows
+#[derive(Default)]
+pub struct NightLight {
+    config: Config,
+    runner: Runner,
+    /// seconds east of UTC
+    offset: i64,
+    init: bool,
+}
+
+impl NightLight {
+    pub fn new(runner: Runner) -> Self {
+        Self {
+            runner,
+            ..Default::default()
+        }
+    }
+
+    pub fn load(runner: Runner) -> Result<Self> {
+        let mut night_light = Self::new(runner);
+        night_light.init()?;
+        Ok(night_light)
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// temperature the screens have now
+    pub fn temperature(&self) -> Result<u32> {
+        self.config.temperature_at(now(), self.offset)
+    }
+
+    /// today's sunset and sunrise, local `HH:MM`
+    fn sun_times(&self) -> Option<(String, String)> {
+        match self.config.night(self.today(), self.offset) {
+            Ok(Night::Between { start, end }) => Some((clock(start), clock(end))),
+            _ => None,
+        }
+    }
+
+    fn today(&self) -> i64 {
+        (now() + self.offset).div_euclid(86_400)
+    }
+}
+
+impl Settings for NightLight {
+    fn init(&mut self) -> Result<()> {
+        self.config = Config::load(&Config::path())?;
+        self.offset = utc_offset(&self.runner)?;
+        self.init = true;
+        Ok(())
+    }
+
+    fn is_init(&self) -> bool {
+        self.init
+    }
+
+    fn name(&self) -> &str {
+        "Night Light"
+    }
+
+    fn heading(&self) -> &str {
+        "Warm colors at night"
+    }
+
+    fn show(&mut self, ui: &mut Ui) {
+        Grid::new("night_light_grid")
+            .num_columns(2)
+            .spacing([100.0, 8.0])
+            .striped(true)
+            .show(ui, |ui| {
+                ui.label("Night Light");
+                ComboBox::from_label("")
+                    .selected_text(self.config.mode.label())
+                    .show_ui(ui, |ui| {
+                        for mode in Mode::ALL {
+                            ui.selectable_value(&mut self.config.mode, mode, mode.label());
+                        }
+                    });
+                ui.end_row();
+                if self.config.mode == Mode::Off {
+                    return;
+                }
+                ui.label("Temperature");
+                ui.add(Slider::new(&mut self.config.temperature, 1000..=NEUTRAL).suffix(" K"));
+                ui.end_row();
+                match self.config.mode {
+                    Mode::Schedule => {
+                        ui.label("From");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                TextEdit::singleline(&mut self.config.start).desired_width(60.0),
+                            );
+                            ui.label("to");
+                            ui.add(TextEdit::singleline(&mut self.config.end).desired_width(60.0));
+                        });
+                        ui.end_row();
+                    }
+                    Mode::Sun => {
+                        ui.label("Position");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                DragValue::new(&mut self.config.latitude)
+                                    .clamp_range(-90.0..=90.0)
+                                    .speed(0.1)
+                                    .suffix("° N"),
+                            );
+                            ui.add(
+                                DragValue::new(&mut self.config.longitude)
+                                    .clamp_range(-180.0..=180.0)
+                                    .speed(0.1)
+                                    .suffix("° E"),
+                            );
+                        });
+                        ui.end_row();
+                        ui.label("Today");
+                        match self.sun_times() {
+                            Some((sunset, sunrise)) => {
+                                ui.label(format!("sunset {}, sunrise {}", sunset, sunrise))
+                            }
+                            None => ui.label("no sunset today"),
+                        };
+                        ui.end_row();
+                    }
+                    _ => {}
+                }
+                ui.label("Now");
+                match self.temperature() {
+                    Ok(temperature) => ui.label(format!("{} K", temperature)),
+                    Err(e) => ui.label(e.to_string()),
+                };
+                ui.end_row();
+            });
+        ui.add_space(8.0);
+        ui.label("Applied by `rsettings nightlight daemon`, start it with the session.");
+    }
+
+    fn apply(&mut self) -> Result<()> {
+        self.config.save(&Config::path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::runner::FakeRunner;
+    use std::sync::Arc;
+
+    /// 2024-06-21 00:00 UTC
+    const SOLSTICE: i64 = 19_895 * 86_400;
+    const CEST: i64 = 2 * 3600;
+
+    /// unix time of local `HH:MM` on the solstice
+    fn at(time: &str) -> i64 {
+        SOLSTICE + minutes(time).unwrap() * 60 - CEST
+    }
+
+    fn schedule() -> Config {
+        Config {
+            mode: Mode::Schedule,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn schedule_fades_across_midnight() {
+        let config = schedule();
+        let temperature = |time| config.temperature_at(at(time), CEST).unwrap();
+        assert_eq!(temperature("19:00"), 6500);
+        assert_eq!(temperature("20:15"), 5250);
+        assert_eq!(temperature("23:00"), 4000);
+        assert_eq!(temperature("03:00"), 4000);
+        assert_eq!(temperature("07:15"), 5250);
+        assert_eq!(temperature("12:00"), 6500);
+        let off = Config::default();
+        assert_eq!(off.temperature_at(at("23:00"), CEST).unwrap(), NEUTRAL);
+        let bad = Config {
+            start: "25:00".to_string(),
+            ..schedule()
+        };
+        assert!(bad.temperature_at(at("23:00"), CEST).is_err());
+    }
+
+    #[test]
+    fn sun_mode_follows_sunset() {
+        let berlin = Config {
+            mode: Mode::Sun,
+            latitude: 52.52,
+            longitude: 13.405,
+            ..Default::default()
+        };
+        let temperature = |time| berlin.temperature_at(at(time), CEST).unwrap();
+        assert_eq!(temperature("12:00"), 6500);
+        assert_eq!(temperature("23:30"), 4000);
+        assert_eq!(temperature("04:00"), 4000);
+        let svalbard = Config {
+            latitude: 78.22,
+            ..berlin
+        };
+        assert_eq!(svalbard.temperature_at(at("23:30"), CEST).unwrap(), 6500);
+    }
+
+    #[test]
+    fn service_restarts_wlsunset_on_change() {
+        let path = std::env::temp_dir()
+            .join(format!("rsettings-{}-nightlight", std::process::id()))
+            .join("nightlight.toml");
+        schedule().save(&path).unwrap();
+        let fake = Arc::new(FakeRunner::new());
+        let mut service = Service::new(Runner::new(fake.clone()), path.clone());
+        service.step(SOLSTICE).unwrap();
+        service.step(SOLSTICE).unwrap();
+        Config::default().save(&path).unwrap();
+        service.step(SOLSTICE).unwrap();
+        assert_eq!(
+            fake.calls(),
+            vec!["wlsunset -t 4000 -T 6500 -S 07:00 -s 20:00 -d 1800"]
+        );
+        assert!(service.wlsunset.is_none());
+    }
+
+    #[test]
+    fn manual_wlsunset_needs_no_location() {
+        let manual = Config {
+            mode: Mode::Manual,
+            ..Default::default()
+        };
+        assert_eq!(
+            manual.wlsunset().unwrap().unwrap().line(),
+            "wlsunset -t 4000 -T 4001 -S 23:59 -s 00:00 -d 60"
+        );
+        assert!(Config::default().wlsunset().unwrap().is_none());
+    }
+
+    #[test]
+    fn utc_offset_from_date() {
+        let offset = |out| utc_offset(&Runner::new(FakeRunner::new().with_output("date +%z", out)));
+        assert_eq!(offset("+0530\n").unwrap(), 19_800);
+        assert_eq!(offset("-0300\n").unwrap(), -10_800);
+        assert!(offset("").is_err());
+    }
+}
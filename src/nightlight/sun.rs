@@ -0,0 +1,86 @@
+//! Sunrise and sunset from the position on earth, after the sunrise
+//! equation; good to a few minutes, no network needed.
+
+use std::f64::consts::PI;
+
+/// julian date of the unix epoch
+const UNIX_EPOCH_JD: f64 = 2_440_587.5;
+/// julian date of 2000-01-01 12:00
+const J2000: f64 = 2_451_545.0;
+/// tilt of the earth axis, degrees
+const OBLIQUITY: f64 = 23.4397;
+/// sun below the horizon at sunrise, refraction and disk, degrees
+const HORIZON: f64 = -0.833;
+
+/// The sun of one day
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sun {
+    /// unix times of sunrise and sunset
+    Rises { sunrise: i64, sunset: i64 },
+    /// above the horizon all day
+    AlwaysUp,
+    /// below the horizon all day
+    AlwaysDown,
+}
+
+/// Sun of the day `day` (days since 1970-01-01) at `latitude` and
+/// `longitude`, degrees north and east.
+pub fn sun(day: i64, latitude: f64, longitude: f64) -> Sun {
+    let rad = |deg: f64| deg * PI / 180.0;
+    let deg = |rad: f64| rad * 180.0 / PI;
+
+    // 1. mean solar noon, days since J2000
+    let n = (day as f64 + 0.5 + UNIX_EPOCH_JD - J2000).round();
+    let noon = n - longitude / 360.0;
+
+    // 2. where the sun is on its ecliptic
+    let anomaly = (357.5291 + 0.985_600_28 * noon).rem_euclid(360.0);
+    let center = 1.9148 * rad(anomaly).sin()
+        + 0.02 * rad(2.0 * anomaly).sin()
+        + 0.0003 * rad(3.0 * anomaly).sin();
+    let ecliptic = (anomaly + center + 180.0 + 102.9372).rem_euclid(360.0);
+    let transit = J2000 + noon + 0.0053 * rad(anomaly).sin() - 0.0069 * rad(2.0 * ecliptic).sin();
+
+    // 3. hour angle of the horizon
+    let declination = (rad(ecliptic).sin() * rad(OBLIQUITY).sin()).asin();
+    let cos_hour = (rad(HORIZON).sin() - rad(latitude).sin() * declination.sin())
+        / (rad(latitude).cos() * declination.cos());
+    if cos_hour > 1.0 {
+        return Sun::AlwaysDown;
+    }
+    if cos_hour < -1.0 {
+        return Sun::AlwaysUp;
+    }
+    let hour = deg(cos_hour.acos()) / 360.0;
+    let unix = |jd: f64| ((jd - UNIX_EPOCH_JD) * 86_400.0).round() as i64;
+    Sun::Rises {
+        sunrise: unix(transit - hour),
+        sunset: unix(transit + hour),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-06-21
+    const SOLSTICE: i64 = 19_895;
+
+    #[test]
+    fn berlin_at_midsummer() {
+        let (sunrise, sunset) = match sun(SOLSTICE, 52.52, 13.405) {
+            Sun::Rises { sunrise, sunset } => (sunrise, sunset),
+            other => panic!("{:?}", other),
+        };
+        // 02:43 and 19:33 UTC
+        let day = SOLSTICE * 86_400;
+        assert!((sunrise - (day + 2 * 3600 + 43 * 60)).abs() < 180);
+        assert!((sunset - (day + 19 * 3600 + 33 * 60)).abs() < 180);
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        assert_eq!(sun(SOLSTICE, 78.22, 15.65), Sun::AlwaysUp);
+        assert_eq!(sun(SOLSTICE, -78.0, 0.0), Sun::AlwaysDown);
+    }
+}
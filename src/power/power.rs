@@ -1,20 +1,29 @@
-use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use eframe::egui::{ComboBox, Grid, Slider, TextBuffer, Ui};
+use eframe::egui::{ComboBox, Grid, Slider, Ui};
+use serde::{Deserialize, Serialize};
 
+use crate::error::error::{Error, Result};
 use crate::settings::settings::Settings;
+use crate::sysfs::sysfs::SysFs;
+
+const BACKLIGHT: &str = "/sys/class/backlight";
+const CONSERVATION_MODE: &str =
+    "/sys/bus/platform/drivers/ideapad_acpi/VPC2004:00/conservation_mode";
+const SCALING_GOVERNOR: &str = "/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor";
+const CPUS: &str = "/sys/devices/system/cpu";
 
 #[derive(Default)]
 pub struct Power {
-    brightness: BrightNess,
+    brightness: Option<BrightNess>,
     lenovo: LenovoBattery,
     cpufreq: CPUFreq,
     init: bool,
+    sysfs: SysFs,
 }
 
 ///cpu freq
-#[derive(PartialEq)]
+#[derive(PartialEq, Default)]
 enum CPUFreq {
     Performance,
     Powersave,
@@ -22,22 +31,17 @@ enum CPUFreq {
     Ondemand,
     Conservative,
     Schedutil,
+    #[default]
     Unknown,
 }
 
-impl Default for CPUFreq {
-    fn default() -> Self {
-        Self::Unknown
-    }
-}
-
 impl CPUFreq {
     fn as_str(&self) -> &str {
         match self {
             Self::Performance => "performance",
             Self::Powersave => "powersave",
             Self::Userspace => "userspace",
-            Self::Ondemand => "Ondemand",
+            Self::Ondemand => "ondemand",
             Self::Conservative => "conservative",
             Self::Schedutil => "schedutil",
             Self::Unknown => "unknown",
@@ -56,10 +60,27 @@ impl CPUFreq {
         }
     }
 
-    fn new() -> Self {
-        let s =
-            fs::read_to_string("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor").unwrap();
-        Self::from_str(s.trim().as_str())
+    fn new(sysfs: &SysFs) -> Result<Self> {
+        // no cpufreq driver, e.g. in virtual machines
+        if !sysfs.exists(SCALING_GOVERNOR) {
+            return Ok(Self::Unknown);
+        }
+        let s = sysfs.read_to_string(SCALING_GOVERNOR)?;
+        Ok(Self::from_str(s.trim()))
+    }
+
+    /// write the governor of every cpu, if it changed
+    fn apply(&self, sysfs: &SysFs) -> Result<()> {
+        if *self == Self::Unknown || Self::new(sysfs)? == *self {
+            return Ok(());
+        }
+        for cpu in sysfs.read_dir(CPUS)? {
+            let governor = cpu.join("cpufreq/scaling_governor");
+            if sysfs.exists(&governor) {
+                sysfs.write(governor, self.as_str())?;
+            }
+        }
+        Ok(())
     }
 
     fn show_ui(&mut self, ui: &mut Ui) {
@@ -84,27 +105,24 @@ struct LenovoBattery {
     lenovo: bool,
 }
 impl LenovoBattery {
-    fn _is_lenovo() -> bool {
-        let path = Path::new("/sys/bus/platform/drivers/ideapad_acpi/VPC2004:00/conservation_mode");
-        path.exists()
+    fn _is_lenovo(sysfs: &SysFs) -> bool {
+        sysfs.exists(CONSERVATION_MODE)
     }
 
-    fn new() -> Self {
+    fn new(sysfs: &SysFs) -> Result<Self> {
         let mut saving = false;
-        let lenovo = LenovoBattery::_is_lenovo();
+        let lenovo = LenovoBattery::_is_lenovo(sysfs);
         if lenovo {
-            let status: u8 = fs::read_to_string(
-                "/sys/bus/platform/drivers/ideapad_acpi/VPC2004:00/conservation_mode",
-            )
-            .unwrap()
-            .trim()
-            .parse()
-            .unwrap();
+            let status = sysfs.read_to_string(CONSERVATION_MODE)?;
+            let status: u8 = status
+                .trim()
+                .parse()
+                .map_err(|_| Error::parse("conservation mode", status.trim()))?;
             if status == 1 {
                 saving = true;
             }
         }
-        Self { saving, lenovo }
+        Ok(Self { saving, lenovo })
     }
 
     fn show_ui(&mut self, ui: &mut Ui) {
@@ -122,24 +140,26 @@ impl LenovoBattery {
 
 #[derive(Debug, Default)]
 struct BrightNess {
-    max_brightness: u8,
+    max_brightness: u32,
     percent: u8,
-    bright_device: String,
+    bright_device: PathBuf,
+    sysfs: SysFs,
 }
 
 impl Settings for Power {
-    fn init(&mut self) {
-        let brightness = BrightNess::new();
-        let lenovo = LenovoBattery::new();
-        let cpufreq = CPUFreq::new();
+    fn init(&mut self) -> Result<()> {
+        let brightness = BrightNess::new(&self.sysfs)?;
+        let lenovo = LenovoBattery::new(&self.sysfs)?;
+        let cpufreq = CPUFreq::new(&self.sysfs)?;
         self.brightness = brightness;
         self.lenovo = lenovo;
         self.cpufreq = cpufreq;
         self.init = true;
+        Ok(())
     }
 
     fn is_init(&self) -> bool {
-        self.init   
+        self.init
     }
 
     fn name(&self) -> &str {
@@ -152,56 +172,134 @@ impl Settings for Power {
             .spacing([100.0, 8.0])
             .striped(true)
             .show(ui, |ui| {
-                self.brightness.show(ui);
+                if let Some(brightness) = &mut self.brightness {
+                    brightness.show(ui);
+                }
                 self.lenovo.show_ui(ui);
                 self.cpufreq.show_ui(ui);
             });
     }
 
-    fn apply(&mut self) {
+    fn apply(&mut self) -> Result<()> {
         println!("Power apply");
-        self.brightness.apply();
+        if let Some(brightness) = &mut self.brightness {
+            brightness.apply()?;
+        }
+        self.cpufreq.apply(&self.sysfs)
+    }
+}
+
+impl Power {
+    pub fn new(sysfs: SysFs) -> Self {
+        Self {
+            sysfs,
+            ..Default::default()
+        }
+    }
+
+    /// current power state read from sysfs
+    pub fn load(sysfs: SysFs) -> Result<Self> {
+        let mut power = Self::new(sysfs);
+        power.init()?;
+        Ok(power)
+    }
+
+    /// brightness in percent, `None` without backlight
+    pub fn brightness(&self) -> Option<u8> {
+        self.brightness.as_ref().map(|b| b.percent)
+    }
+
+    /// `percent` from 1, the screen stays readable, to 100
+    pub fn set_brightness(&mut self, percent: u8) -> Result<()> {
+        if !(1..=100).contains(&percent) {
+            return Err(Error::Usage(format!(
+                "brightness must be 1 to 100%, not {}%",
+                percent
+            )));
+        }
+        let brightness = self
+            .brightness
+            .as_mut()
+            .ok_or_else(|| Error::NotFound("backlight".to_string()))?;
+        brightness.percent = percent;
+        Ok(())
+    }
+
+    pub fn governor(&self) -> &str {
+        self.cpufreq.as_str()
     }
+
+    /// `None` if not a lenovo laptop
+    pub fn conservation_mode(&self) -> Option<bool> {
+        self.lenovo.lenovo.then_some(self.lenovo.saving)
+    }
+
+    pub fn state(&self) -> PowerState {
+        PowerState {
+            brightness: self.brightness(),
+            governor: self.governor().to_string(),
+        }
+    }
+
+    /// set brightness and governor of `state`, without backlight brightness is ignored
+    pub fn restore(&mut self, state: &PowerState) -> Result<()> {
+        if let (Some(percent), Some(_)) = (state.brightness, &self.brightness) {
+            self.set_brightness(percent)?;
+        }
+        self.cpufreq = CPUFreq::from_str(&state.governor);
+        Ok(())
+    }
+}
+
+/// Saved power configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PowerState {
+    /// brightness in percent
+    pub brightness: Option<u8>,
+    /// cpu frequency governor
+    pub governor: String,
 }
 
 impl BrightNess {
-    fn new() -> Self {
+    /// `None` if there is no backlight, e.g. on desktops
+    fn new(sysfs: &SysFs) -> Result<Option<Self>> {
         // 1. get path
-        let mut brightness_path = String::new();
-        let entry = fs::read_dir("/sys/class/backlight/").unwrap();
-        for entry in entry {
-            let entry = entry.unwrap();
-            let path = entry.path();
-            if path.join("brightness").exists() {
-                brightness_path = path.as_os_str().to_str().unwrap().to_string();
-                break;
-            }
+        if !sysfs.exists(BACKLIGHT) {
+            return Ok(None);
         }
+        let bright_device = match sysfs
+            .read_dir(BACKLIGHT)?
+            .into_iter()
+            .find(|path| sysfs.exists(path.join("brightness")))
+        {
+            Some(path) => path,
+            None => return Ok(None),
+        };
         // 2. get max_brightness
-        let path = Path::new(&brightness_path);
-        let max_brightness = Self::get_num_from_file(&path.join("max_brightness"));
-        let brightness = Self::get_num_from_file(&path.join("brightness"));
+        let max_brightness = Self::get_num_from_file(sysfs, &bright_device.join("max_brightness"))?;
+        let brightness = Self::get_num_from_file(sysfs, &bright_device.join("brightness"))?;
         let percent = ((brightness as f64 / max_brightness as f64) * 100f64) as u8;
 
-        Self {
+        Ok(Some(Self {
             max_brightness,
             percent,
-            bright_device: brightness_path,
-        }
+            bright_device,
+            sysfs: sysfs.clone(),
+        }))
     }
 
-    fn get_num_from_file(path: &Path) -> u8 {
-        if !path.exists() {
-            return 0;
+    fn get_num_from_file(sysfs: &SysFs, path: &Path) -> Result<u32> {
+        if !sysfs.exists(path) {
+            return Ok(0);
         }
-        let data = fs::read_to_string(path).unwrap();
-        data.trim().parse().unwrap_or(0)
+        let data = sysfs.read_to_string(path)?;
+        Ok(data.trim().parse().unwrap_or(0))
     }
 }
 
 impl Settings for BrightNess {
-    fn init(&mut self) {
-        
+    fn init(&mut self) -> Result<()> {
+        Ok(())
     }
     fn name(&self) -> &str {
         "BrightNess"
@@ -213,10 +311,76 @@ impl Settings for BrightNess {
         ui.end_row();
     }
 
-    fn apply(&mut self) {
+    fn apply(&mut self) -> Result<()> {
         // 1. calculate brightness
-        let brightness = ((self.max_brightness as f64 / 100.0) * self.percent as f64) as u8;
-        let path = Path::new(&self.bright_device).join("brightness");
-        fs::write(path.as_os_str().to_str().unwrap(), &brightness.to_string()).unwrap();
+        let brightness = ((self.max_brightness as f64 / 100.0) * self.percent as f64) as u32;
+        let path = self.bright_device.join("brightness");
+        self.sysfs.write(path, &brightness.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysfs::sysfs::fixture_sysfs;
+
+    #[test]
+    fn power_reads_fixture_sysfs() {
+        let mut power = Power::new(fixture_sysfs("power_init"));
+        power.init().unwrap();
+        let brightness = power.brightness.as_ref().unwrap();
+        assert_eq!(brightness.max_brightness, 96000);
+        assert_eq!(brightness.percent, 50);
+        assert!(power.lenovo.lenovo);
+        assert!(power.lenovo.saving);
+        assert!(power.cpufreq == CPUFreq::Schedutil);
+    }
+
+    #[test]
+    fn brightness_apply_writes_sysfs() {
+        let sysfs = fixture_sysfs("brightness_apply");
+        let mut power = Power::new(sysfs.clone());
+        power.init().unwrap();
+        power.brightness.as_mut().unwrap().percent = 25;
+        power.apply().unwrap();
+        let brightness = sysfs
+            .read_to_string("/sys/class/backlight/intel_backlight/brightness")
+            .unwrap();
+        assert_eq!(brightness, "24000");
+    }
+
+    #[test]
+    fn power_without_backlight_or_cpufreq() {
+        let sysfs = fixture_sysfs("power_desktop");
+        std::fs::remove_dir_all(sysfs.path("/sys/class/backlight")).unwrap();
+        std::fs::remove_dir_all(sysfs.path("/sys/devices/system/cpu")).unwrap();
+        let mut power = Power::new(sysfs);
+        power.init().unwrap();
+        assert!(power.brightness.is_none());
+        assert!(power.cpufreq == CPUFreq::Unknown);
+        power.apply().unwrap();
+    }
+
+    #[test]
+    fn restore_writes_governor_of_every_cpu() {
+        let sysfs = fixture_sysfs("power_restore");
+        let mut power = Power::load(sysfs.clone()).unwrap();
+        let state = PowerState {
+            brightness: Some(10),
+            governor: "powersave".to_string(),
+        };
+        power.restore(&state).unwrap();
+        power.apply().unwrap();
+        let mut power = Power::load(sysfs).unwrap();
+        assert_eq!(power.state(), state);
+
+        for brightness in [0, 101] {
+            let bad = PowerState {
+                brightness: Some(brightness),
+                governor: "performance".to_string(),
+            };
+            assert!(matches!(power.restore(&bad), Err(Error::Usage(_))));
+            assert_eq!(power.state(), state);
+        }
     }
 }
@@ -0,0 +1,552 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use eframe::egui::{ComboBox, Grid, TextEdit, Ui};
+use serde::{Deserialize, Serialize};
+
+use crate::appearance::appearance::{Appearance, AppearanceState};
+use crate::display::display::{DisplayFiles, DisplayState, Displays};
+use crate::error::error::{Error, Result};
+use crate::network::network::{Network, NetworkState};
+use crate::power::power::{Power, PowerState};
+use crate::runner::runner::Runner;
+use crate::settings::settings::{config_home, Settings};
+use crate::sysfs::sysfs::SysFs;
+
+/// Snapshot of every panel, missing parts are left untouched on apply
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub displays: Vec<DisplayState>,
+    pub appearance: Option<AppearanceState>,
+    pub power: Option<PowerState>,
+    pub network: Option<NetworkState>,
+}
+
+impl Profile {
+    /// Current desktop state; panels that can't be read are left out and
+    /// returned as [`Error::Skipped`].
+    pub fn capture(runner: &Runner, sysfs: &SysFs, files: &DisplayFiles) -> (Self, Vec<Error>) {
+        fn skip<T>(skipped: &mut Vec<Error>, what: &'static str, state: Result<T>) -> Option<T> {
+            state
+                .map_err(|e| {
+                    skipped.push(Error::Skipped {
+                        what,
+                        source: Box::new(e),
+                    })
+                })
+                .ok()
+        }
+
+        let displays = Displays::load(runner.clone(), files).map(|d| d.state());
+        let appearance = Appearance::load(runner.clone()).map(|a| a.state());
+        let power = Power::load(sysfs.clone()).map(|p| p.state());
+        let network = Network::new(runner.clone()).state();
+        let mut skipped = Vec::new();
+        let profile = Self {
+            displays: skip(&mut skipped, "displays", displays).unwrap_or_default(),
+            appearance: skip(&mut skipped, "appearance", appearance),
+            power: skip(&mut skipped, "power", power),
+            network: skip(&mut skipped, "network", network),
+        };
+        (profile, skipped)
+    }
+
+    /// restore every part of the profile
+    pub fn apply(&self, runner: &Runner, sysfs: &SysFs, files: &DisplayFiles) -> Result<()> {
+        // 1. displays
+        if !self.displays.is_empty() {
+            let mut displays = Displays::load(runner.clone(), files)?;
+            displays.restore(&self.displays)?;
+            displays.apply_now()?;
+        }
+        // 2. appearance
+        if let Some(state) = &self.appearance {
+            let mut appearance = Appearance::load(runner.clone())?;
+            appearance.restore(state)?;
+            appearance.apply()?;
+        }
+        // 3. power
+        if let Some(state) = &self.power {
+            let mut power = Power::load(sysfs.clone())?;
+            power.restore(state)?;
+            power.apply()?;
+        }
+        // 4. network
+        if let Some(state) = &self.network {
+            Network::new(runner.clone()).restore(state)?;
+        }
+        Ok(())
+    }
+
+    /// what applying `self` would change on `current`, one line per setting
+    pub fn diff(&self, current: &Profile) -> Vec<String> {
+        let mut changes = Vec::new();
+        for display in &self.displays {
+            let now = match current.displays.iter().find(|d| d.name == display.name) {
+                Some(now) => now,
+                None => {
+                    changes.push(format!("{}: not connected", display.name));
+                    continue;
+                }
+            };
+            for ((what, from), (_, to)) in
+                display_fields(now).into_iter().zip(display_fields(display))
+            {
+                change(
+                    &mut changes,
+                    &format!("{} {}", display.name, what),
+                    Some(from),
+                    Some(to),
+                );
+            }
+        }
+        if let Some(appearance) = &self.appearance {
+            let now = current
+                .appearance
+                .as_ref()
+                .and_then(|a| a.gtk_theme.clone());
+            change(&mut changes, "gtk theme", now, appearance.gtk_theme.clone());
+        }
+        if let Some(power) = &self.power {
+            let now = current.power.as_ref();
+            let percent = |p: Option<u8>| p.map(|p| format!("{}%", p));
+            change(
+                &mut changes,
+                "brightness",
+                percent(now.and_then(|p| p.brightness)),
+                percent(power.brightness),
+            );
+            change(
+                &mut changes,
+                "cpu governor",
+                now.map(|p| p.governor.clone()),
+                Some(power.governor.clone()),
+            );
+        }
+        if let Some(network) = &self.network {
+            let now = current.network.as_ref().and_then(|n| n.wifi.clone());
+            change(&mut changes, "wifi", now, network.wifi.clone());
+        }
+        changes
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|e| Error::parse("profile", e.to_string()))
+    }
+
+    pub fn from_toml(s: &str) -> Result<Self> {
+        toml::from_str(s).map_err(|e| Error::parse("profile", e.to_string()))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| Error::parse("profile", e.to_string()))
+    }
+
+    pub fn from_json(s: &str) -> Result<Self> {
+        serde_json::from_str(s).map_err(|e| Error::parse("profile", e.to_string()))
+    }
+
+    /// read a `.json` or `.toml` file
+    pub fn read(path: &Path) -> Result<Self> {
+        let s = fs::read_to_string(path).map_err(|source| Error::File {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        if is_json(path) {
+            Self::from_json(&s)
+        } else {
+            Self::from_toml(&s)
+        }
+    }
+
+    /// write as json if `path` ends with `.json`, else as toml
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let s = if is_json(path) {
+            self.to_json()?
+        } else {
+            self.to_toml()?
+        };
+        fs::write(path, s).map_err(|source| Error::File {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+fn display_fields(state: &DisplayState) -> [(&'static str, String); 5] {
+    [
+        ("enable", state.enable.to_string()),
+        ("mode", format!("{}@{}", state.mode, state.refresh)),
+        (
+            "position",
+            format!("{},{}", state.position.0, state.position.1),
+        ),
+        ("transform", state.transform.clone()),
+        ("scale", state.scale.to_string()),
+    ]
+}
+
+/// push `what: from -> to` if the value changes
+fn change(changes: &mut Vec<String>, what: &str, from: Option<String>, to: Option<String>) {
+    if from != to {
+        let show = |v: Option<String>| v.unwrap_or_else(|| "none".to_string());
+        changes.push(format!("{}: {} -> {}", what, show(from), show(to)));
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+/// Directory of named profiles, one `NAME.toml` (or `NAME.json`) each
+#[derive(Debug, Clone)]
+pub struct ProfileStore {
+    dir: PathBuf,
+}
+
+impl ProfileStore {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// `$XDG_CONFIG_HOME/rsettings/profiles`, default `~/.config/rsettings/profiles`
+    pub fn from_env() -> Self {
+        Self::new(config_home().join("rsettings/profiles"))
+    }
+
+    /// names of all saved profiles, sorted
+    pub fn list(&self) -> Result<Vec<String>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let file_error = |source| Error::File {
+            path: self.dir.clone(),
+            source,
+        };
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.dir).map_err(file_error)? {
+            let path = entry.map_err(file_error)?.path();
+            let profile = path
+                .extension()
+                .is_some_and(|ext| ext == "toml" || ext == "json");
+            if let (true, Some(name)) = (profile, path.file_stem().and_then(|s| s.to_str())) {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    pub fn load(&self, name: &str) -> Result<Profile> {
+        let path = self.path(name)?;
+        if !path.exists() {
+            return Err(Error::NotFound(format!("profile {}", name)));
+        }
+        Profile::read(&path)
+    }
+
+    pub fn save(&self, name: &str, profile: &Profile) -> Result<()> {
+        let path = self.path(name)?;
+        fs::create_dir_all(&self.dir).map_err(|source| Error::File {
+            path: self.dir.clone(),
+            source,
+        })?;
+        profile.write(&path)
+    }
+
+    pub fn remove(&self, name: &str) -> Result<()> {
+        let path = self.path(name)?;
+        fs::remove_file(&path).map_err(|source| Error::File { path, source })
+    }
+
+    /// copy profile `name` to `path`, format by extension
+    pub fn export(&self, name: &str, path: &Path) -> Result<()> {
+        self.load(name)?.write(path)
+    }
+
+    /// save the profile file `path` as `name`, default its file name
+    pub fn import(&self, path: &Path, name: Option<&str>) -> Result<String> {
+        let profile = Profile::read(path)?;
+        let name = match name.or_else(|| path.file_stem().and_then(|s| s.to_str())) {
+            Some(name) => name.to_string(),
+            None => return Err(Error::parse("profile name", path.display().to_string())),
+        };
+        self.save(&name, &profile)?;
+        Ok(name)
+    }
+
+    /// file of profile `name`, an existing json one wins over toml
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.starts_with('.') || name.contains('/') {
+            return Err(Error::parse("profile name", name));
+        }
+        let json = self.dir.join(format!("{}.json", name));
+        if json.exists() {
+            return Ok(json);
+        }
+        Ok(self.dir.join(format!("{}.toml", name)))
+    }
+}
+
+pub struct Profiles {
+    names: Vec<String>,
+    now: String,
+    new_name: String,
+    /// desktop state the diff is computed against
+    current: Profile,
+    diff: Vec<String>,
+    init: bool,
+    store: ProfileStore,
+    /// wayfire.ini and custom modes of the displays part
+    files: DisplayFiles,
+    runner: Runner,
+    sysfs: SysFs,
+    error: Option<Error>,
+}
+
+impl Settings for Profiles {
+    fn init(&mut self) -> Result<()> {
+        self.names = self.store.list()?;
+        self.capture();
+        if !self.names.contains(&self.now) {
+            self.now = self.names.first().cloned().unwrap_or_default();
+        }
+        self.update_diff();
+        self.init = true;
+        Ok(())
+    }
+
+    fn is_init(&self) -> bool {
+        self.init
+    }
+
+    fn name(&self) -> &str {
+        "Profiles"
+    }
+
+    fn heading(&self) -> &str {
+        "Save and restore settings"
+    }
+
+    fn show(&mut self, ui: &mut Ui) {
+        Grid::new("profiles_grid")
+            .num_columns(2)
+            .spacing([100.0, 8.0])
+            .striped(true)
+            .show(ui, |ui| {
+                // 1. saved profiles
+                let selected = self.now.clone();
+                ui.label("Profile");
+                ui.horizontal(|ui| {
+                    ComboBox::from_label("")
+                        .selected_text(self.now.as_str())
+                        .show_ui(ui, |ui| {
+                            for name in &self.names {
+                                ui.selectable_value(&mut self.now, name.clone(), name.as_str());
+                            }
+                        });
+                    if ui.button("Delete").clicked() && !self.now.is_empty() {
+                        let removed = self.store.remove(&self.now);
+                        self.reload(removed);
+                    }
+                });
+                ui.end_row();
+                if selected != self.now {
+                    self.update_diff();
+                }
+                // 2. changes on apply
+                ui.label("Changes");
+                ui.vertical(|ui| {
+                    if self.diff.is_empty() {
+                        ui.label("none");
+                    }
+                    for line in &self.diff {
+                        ui.label(line);
+                    }
+                    if ui.button("Refresh").clicked() {
+                        self.capture();
+                        self.update_diff();
+                    }
+                });
+                ui.end_row();
+                // 3. snapshot
+                ui.label("Save current as");
+                ui.horizontal(|ui| {
+                    ui.add(TextEdit::singleline(&mut self.new_name).desired_width(120.0));
+                    if ui.button("Save").clicked() {
+                        self.capture();
+                        let saved = self.store.save(&self.new_name, &self.current);
+                        if saved.is_ok() {
+                            self.now = std::mem::take(&mut self.new_name);
+                        }
+                        self.reload(saved);
+                    }
+                });
+                ui.end_row();
+            });
+    }
+
+    fn apply(&mut self) -> Result<()> {
+        if self.now.is_empty() {
+            return Ok(());
+        }
+        let profile = self.store.load(&self.now)?;
+        profile.apply(&self.runner, &self.sysfs, &self.files)?;
+        self.capture();
+        self.update_diff();
+        Ok(())
+    }
+
+    fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
+}
+
+impl Profiles {
+    pub fn new(runner: Runner, sysfs: SysFs) -> Self {
+        Self {
+            names: Vec::new(),
+            now: String::new(),
+            new_name: String::new(),
+            current: Profile::default(),
+            diff: Vec::new(),
+            init: false,
+            store: ProfileStore::from_env(),
+            files: DisplayFiles::from_env(),
+            runner,
+            sysfs,
+            error: None,
+        }
+    }
+
+    /// read the desktop state, telling what was left out
+    fn capture(&mut self) {
+        let (current, skipped) = Profile::capture(&self.runner, &self.sysfs, &self.files);
+        self.current = current;
+        if let Some(e) = Error::many(skipped) {
+            self.error = Some(e);
+        }
+    }
+
+    /// re-read the list after `result` of a button
+    fn reload(&mut self, result: Result<()>) {
+        let names = result.and_then(|()| self.store.list());
+        match names {
+            Ok(names) => {
+                self.names = names;
+                if !self.names.contains(&self.now) {
+                    self.now = self.names.first().cloned().unwrap_or_default();
+                }
+                self.update_diff();
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    fn update_diff(&mut self) {
+        self.diff.clear();
+        if self.now.is_empty() {
+            return;
+        }
+        match self.store.load(&self.now) {
+            Ok(profile) => self.diff = profile.diff(&self.current),
+            Err(e) => self.error = Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::runner::FakeRunner;
+    use crate::sysfs::sysfs::fixture_sysfs;
+
+    fn fake() -> Runner {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/commands");
+        Runner::new(FakeRunner::from_dir(&dir).unwrap())
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rsettings-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn temp_store(name: &str) -> ProfileStore {
+        ProfileStore::new(temp_dir(name))
+    }
+
+    fn capture(name: &str) -> Profile {
+        let files = DisplayFiles::in_dir(&temp_dir(&format!("{}-files", name)));
+        let (profile, skipped) = Profile::capture(&fake(), &fixture_sysfs(name), &files);
+        assert!(skipped.is_empty());
+        profile
+    }
+
+    #[test]
+    fn capture_round_trips_through_toml_and_json() {
+        let profile = capture("profile_capture");
+        assert_eq!(profile.displays.len(), 2);
+        assert_eq!(profile.power.as_ref().unwrap().governor, "schedutil");
+        assert_eq!(
+            profile.network.as_ref().unwrap().wifi.as_deref(),
+            Some("HomeNet")
+        );
+        let toml = profile.to_toml().unwrap();
+        assert_eq!(Profile::from_toml(&toml).unwrap(), profile);
+        let json = profile.to_json().unwrap();
+        assert_eq!(Profile::from_json(&json).unwrap(), profile);
+    }
+
+    #[test]
+    fn diff_lists_changed_settings() {
+        let current = capture("profile_diff");
+        let mut docked = current.clone();
+        docked.displays[1].scale = 1.0;
+        docked.displays.push(DisplayState {
+            name: "DP-2".to_string(),
+            ..docked.displays[0].clone()
+        });
+        docked.power.as_mut().unwrap().brightness = Some(80);
+        docked.network = Some(NetworkState {
+            wifi: Some("Office".to_string()),
+        });
+        assert_eq!(
+            docked.diff(&current),
+            vec![
+                "eDP-1 scale: 1.25 -> 1",
+                "DP-2: not connected",
+                "brightness: 50% -> 80%",
+                "wifi: HomeNet -> Office",
+            ]
+        );
+        assert!(current.diff(&current).is_empty());
+    }
+
+    #[test]
+    fn store_saves_imports_and_exports() {
+        let store = temp_store("profile_store");
+        assert!(store.list().unwrap().is_empty());
+        let profile = Profile {
+            power: Some(PowerState {
+                brightness: Some(30),
+                governor: "powersave".to_string(),
+            }),
+            ..Default::default()
+        };
+        store.save("battery", &profile).unwrap();
+        assert_eq!(store.load("battery").unwrap(), profile);
+
+        let json = store.dir.join("shared.json");
+        store.export("battery", &json).unwrap();
+        assert_eq!(store.import(&json, None).unwrap(), "shared");
+        assert_eq!(store.list().unwrap(), vec!["battery", "shared"]);
+        assert_eq!(store.load("shared").unwrap(), profile);
+
+        store.remove("battery").unwrap();
+        assert!(matches!(store.load("battery"), Err(Error::NotFound(_))));
+        assert!(store.save("../escape", &profile).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::{Arc, Mutex};
+
+use crate::error::error::{Error, Result};
+
+/// Env var pointing to a directory of canned command outputs
+pub const FAKE_COMMANDS_ENV: &str = "RSETTINGS_FAKE_COMMANDS";
+
+/// A command line to be executed by a `CommandRunner`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cmd {
+    pub program: String,
+    pub args: Vec<String>,
+    pub envs: Vec<(String, String)>,
+}
+
+impl Cmd {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn arg<S: AsRef<str>>(mut self, arg: S) -> Self {
+        self.args.push(arg.as_ref().to_string());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for arg in args {
+            self.args.push(arg.as_ref().to_string());
+        }
+        self
+    }
+
+    pub fn env(mut self, key: &str, val: &str) -> Self {
+        self.envs.push((key.to_string(), val.to_string()));
+        self
+    }
+
+    /// program and args joined by spaces, used as key by `FakeRunner`
+    pub fn line(&self) -> String {
+        let mut line = self.program.clone();
+        for arg in &self.args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        line
+    }
+
+    fn to_command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+        for (key, val) in &self.envs {
+            cmd.env(key, val);
+        }
+        cmd
+    }
+}
+
+/// Result of a finished command
+#[derive(Debug, Clone, Default)]
+pub struct CmdOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// A spawned, still running command
+pub trait Process: Send {
+    fn kill(&mut self) -> io::Result<()>;
+}
+
+impl Process for Child {
+    fn kill(&mut self) -> io::Result<()> {
+        Child::kill(self)?;
+        self.wait()?;
+        Ok(())
+    }
+}
+
+/// Everything the panels execute goes through this trait
+pub trait CommandRunner: Send + Sync {
+    /// run the command to completion and collect its output
+    fn output(&self, cmd: &Cmd) -> io::Result<CmdOutput>;
+    /// start the command in background
+    fn spawn(&self, cmd: &Cmd) -> io::Result<Box<dyn Process>>;
+    /// `true` if nothing really runs, e.g. in tests
+    fn is_fake(&self) -> bool {
+        false
+    }
+}
+
+impl<R: CommandRunner + ?Sized> CommandRunner for Arc<R> {
+    fn output(&self, cmd: &Cmd) -> io::Result<CmdOutput> {
+        (**self).output(cmd)
+    }
+
+    fn spawn(&self, cmd: &Cmd) -> io::Result<Box<dyn Process>> {
+        (**self).spawn(cmd)
+    }
+
+    fn is_fake(&self) -> bool {
+        (**self).is_fake()
+    }
+}
+
+/// Runs commands on the real system
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn output(&self, cmd: &Cmd) -> io::Result<CmdOutput> {
+        let output = cmd.to_command().output()?;
+        Ok(CmdOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+
+    fn spawn(&self, cmd: &Cmd) -> io::Result<Box<dyn Process>> {
+        let child = cmd.to_command().spawn()?;
+        Ok(Box::new(child))
+    }
+}
+
+/// Replies with canned outputs and records every command it was asked to run.
+///
+/// Unknown commands succeed with empty output.
+#[derive(Default)]
+pub struct FakeRunner {
+    outputs: HashMap<String, CmdOutput>,
+    calls: Mutex<Vec<String>>,
+}
+
+struct FakeProcess;
+
+impl Process for FakeProcess {
+    fn kill(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FakeRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// reply `stdout` to the command line `line`
+    pub fn with_output(mut self, line: &str, stdout: &str) -> Self {
+        let output = CmdOutput {
+            success: true,
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+        };
+        self.outputs.insert(line.to_string(), output);
+        self
+    }
+
+    /// Load canned outputs from `dir`.
+    ///
+    /// Every file is the stdout of one command line, with spaces of the
+    /// command line replaced by `_` in the file name, e.g. `nmcli_-t_d`.
+    /// A real `_`, `%` or `/` is written `%5F`, `%25` or `%2F`.
+    pub fn from_dir(dir: &Path) -> io::Result<Self> {
+        let mut runner = Self::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let line = match path.file_name().and_then(|f| f.to_str()) {
+                Some(name) => Self::command_line(name).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("bad escape in {}", path.display()),
+                    )
+                })?,
+                None => continue,
+            };
+            let stdout = fs::read_to_string(&path)?;
+            runner = runner.with_output(&line, &stdout);
+        }
+        Ok(runner)
+    }
+
+    /// command line of the fixture file `name`, `None` if an escape is bad
+    fn command_line(name: &str) -> Option<String> {
+        let mut bytes = Vec::new();
+        let mut rest = name.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            rest = tail;
+            match byte {
+                b'_' => bytes.push(b' '),
+                b'%' => {
+                    let hex = std::str::from_utf8(rest.get(..2)?).ok()?;
+                    bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                    rest = &rest[2..];
+                }
+                byte => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).ok()
+    }
+
+    /// command lines executed so far
+    #[cfg(test)]
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn record(&self, cmd: &Cmd) -> String {
+        let line = cmd.line();
+        self.calls.lock().unwrap().push(line.clone());
+        line
+    }
+}
+
+impl CommandRunner for FakeRunner {
+    fn output(&self, cmd: &Cmd) -> io::Result<CmdOutput> {
+        let line = self.record(cmd);
+        Ok(self.outputs.get(&line).cloned().unwrap_or(CmdOutput {
+            success: true,
+            ..Default::default()
+        }))
+    }
+
+    fn spawn(&self, cmd: &Cmd) -> io::Result<Box<dyn Process>> {
+        self.record(cmd);
+        Ok(Box::new(FakeProcess))
+    }
+
+    fn is_fake(&self) -> bool {
+        true
+    }
+}
+
+/// Shared handle to the `CommandRunner` injected into every panel
+#[derive(Clone)]
+pub struct Runner(Arc<dyn CommandRunner>);
+
+impl Default for Runner {
+    fn default() -> Self {
+        Self::new(SystemRunner)
+    }
+}
+
+impl Deref for Runner {
+    type Target = dyn CommandRunner;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+impl Runner {
+    pub fn new<R: CommandRunner + 'static>(runner: R) -> Self {
+        Self(Arc::new(runner))
+    }
+
+    /// `FakeRunner` loaded from `$RSETTINGS_FAKE_COMMANDS` if set, else the real system
+    pub fn from_env() -> Result<Self> {
+        match std::env::var_os(FAKE_COMMANDS_ENV) {
+            Some(dir) => {
+                let dir = PathBuf::from(dir);
+                let fake = FakeRunner::from_dir(&dir)
+                    .map_err(|source| Error::File { path: dir, source })?;
+                Ok(Self::new(fake))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// run `cmd` and return its stdout, failing if it exits with error
+    pub fn stdout(&self, cmd: &Cmd) -> Result<String> {
+        let output = self.output(cmd).map_err(|source| Error::Spawn {
+            cmd: cmd.line(),
+            source,
+        })?;
+        if !output.success {
+            return Err(Error::Command {
+                cmd: cmd.line(),
+                stderr: output.stderr,
+            });
+        }
+        Ok(output.stdout)
+    }
+
+    /// start `cmd` in background
+    pub fn start(&self, cmd: &Cmd) -> Result<Box<dyn Process>> {
+        self.spawn(cmd).map_err(|source| Error::Spawn {
+            cmd: cmd.line(),
+            source,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fake_runner_replies_and_records() {
+        let fake = Arc::new(FakeRunner::new().with_output("nmcli -t d", "wlan0:wifi\n"));
+        let runner = Runner::new(fake.clone());
+        let out = runner.output(&Cmd::new("nmcli").args(["-t", "d"])).unwrap();
+        assert!(out.success);
+        assert_eq!(out.stdout, "wlan0:wifi\n");
+        let out = runner.stdout(&Cmd::new("true")).unwrap();
+        assert!(out.is_empty());
+        assert_eq!(fake.calls(), vec!["nmcli -t d", "true"]);
+    }
+
+    #[test]
+    fn fake_runner_from_fixtures() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/commands");
+        let fake = FakeRunner::from_dir(&dir).unwrap();
+        let out = fake.output(&Cmd::new("wlr-randr")).unwrap();
+        assert!(out.stdout.starts_with("eDP-1"));
+        // escaped `%` and `_`
+        let out = fake.output(&Cmd::new("date").arg("+%z")).unwrap();
+        assert!(!out.stdout.is_empty());
+        let out = fake
+            .output(&Cmd::new("swaymsg").args(["-r", "-t", "get_outputs"]))
+            .unwrap();
+        assert!(out.stdout.starts_with('['));
+        assert_eq!(FakeRunner::command_line("a%5"), None);
+    }
+}
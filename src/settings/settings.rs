@@ -1,14 +1,52 @@
+use std::path::PathBuf;
+
 use crate::egui::Ui;
+use crate::error::error::{Error, Result};
 
 pub trait Settings {
     fn is_init(&self) -> bool {
-        return false;
+        false
     }
-    fn init(&mut self);
+    fn init(&mut self) -> Result<()>;
     fn show(&mut self, ui: &mut Ui);
     fn name(&self) -> &str;
     fn heading(&self) -> &str {
         self.name()
     }
-    fn apply(&mut self);
+    fn apply(&mut self) -> Result<()>;
+    /// `false` while `apply` has to wait, e.g. for a confirmation
+    fn can_apply(&self) -> bool {
+        true
+    }
+    /// error raised in background or by a button of `show`, polled every frame
+    fn take_error(&mut self) -> Option<Error> {
+        None
+    }
+}
+
+/// `$XDG_CONFIG_HOME`, or `~/.config` when unset
+pub fn config_home() -> PathBuf {
+    match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".config"),
+    }
+}
+
+/// `$XDG_DATA_HOME`, or `~/.local/share` when unset
+pub fn data_home() -> PathBuf {
+    match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".local/share"),
+    }
+}
+
+/// `$XDG_DATA_DIRS`, or `/usr/local/share` and `/usr/share` when unset
+pub fn data_dirs() -> Vec<PathBuf> {
+    match std::env::var("XDG_DATA_DIRS") {
+        Ok(dirs) if !dirs.is_empty() => std::env::split_paths(&dirs).collect(),
+        _ => vec![
+            PathBuf::from("/usr/local/share"),
+            PathBuf::from("/usr/share"),
+        ],
+    }
 }
@@ -0,0 +1,125 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::error::{Error, Result};
+
+/// Env var overriding the root of `/sys` and `/proc`
+pub const SYSFS_ROOT_ENV: &str = "RSETTINGS_SYSFS_ROOT";
+
+/// Handle to the kernel filesystems.
+///
+/// Paths are given as on the real system (`/sys/class/backlight`) and
+/// resolved below `root`, so a fake tree can stand in for the kernel.
+#[derive(Debug, Clone)]
+pub struct SysFs {
+    root: PathBuf,
+}
+
+impl Default for SysFs {
+    fn default() -> Self {
+        Self::new("/")
+    }
+}
+
+impl SysFs {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// root from `$RSETTINGS_SYSFS_ROOT`, else `/`
+    pub fn from_env() -> Self {
+        match std::env::var_os(SYSFS_ROOT_ENV) {
+            Some(root) => Self::new(root),
+            None => Self::default(),
+        }
+    }
+
+    /// real location of `path`
+    pub fn path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path = path.as_ref();
+        self.root.join(path.strip_prefix("/").unwrap_or(path))
+    }
+
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.path(path).exists()
+    }
+
+    pub fn read_to_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        let path = self.path(path);
+        fs::read_to_string(&path).map_err(|source| Error::File { path, source })
+    }
+
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let path = self.path(path);
+        fs::read(&path).map_err(|source| Error::File { path, source })
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P, contents: &str) -> Result<()> {
+        let path = self.path(path);
+        fs::write(&path, contents).map_err(|source| Error::File { path, source })
+    }
+
+    /// entries of directory `path`, as paths below `path` (not below root)
+    pub fn read_dir<P: AsRef<Path>>(&self, path: P) -> Result<Vec<PathBuf>> {
+        let path = path.as_ref();
+        let real = self.path(path);
+        let file_error = |source| Error::File {
+            path: real.clone(),
+            source,
+        };
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&real).map_err(file_error)? {
+            entries.push(path.join(entry.map_err(file_error)?.file_name()));
+        }
+        entries.sort();
+        Ok(entries)
+    }
+}
+
+/// Copy of the fixture tree `fixtures/sysfs` in a fresh temp dir, safe to write
+#[cfg(test)]
+pub fn fixture_sysfs(name: &str) -> SysFs {
+    fn copy_dir(from: &Path, to: &Path) {
+        fs::create_dir_all(to).unwrap();
+        for entry in fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            let target = to.join(entry.file_name());
+            if entry.path().is_dir() {
+                copy_dir(&entry.path(), &target);
+            } else {
+                fs::copy(entry.path(), target).unwrap();
+            }
+        }
+    }
+
+    let from = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/sysfs");
+    let to = std::env::temp_dir().join(format!("rsettings-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&to);
+    copy_dir(&from, &to);
+    SysFs::new(to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_resolve_below_root() {
+        let sysfs = SysFs::new("/tmp/root");
+        assert_eq!(
+            sysfs.path("/sys/class/backlight"),
+            PathBuf::from("/tmp/root/sys/class/backlight")
+        );
+        assert_eq!(SysFs::default().path("/sys"), PathBuf::from("/sys"));
+    }
+
+    #[test]
+    fn read_dir_keeps_virtual_paths() {
+        let sysfs = fixture_sysfs("read_dir");
+        let entries = sysfs.read_dir("/sys/class/backlight").unwrap();
+        assert_eq!(
+            entries,
+            vec![PathBuf::from("/sys/class/backlight/intel_backlight")]
+        );
+    }
+}
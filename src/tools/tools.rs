@@ -1,16 +1,16 @@
 use std::path::Path;
-use std::process::Command;
 use std::sync::Arc;
 use std::sync::Condvar;
 use std::sync::Mutex;
 use std::thread;
 use std::time::SystemTime;
 
+use crate::error::error::{Error, Result};
+use crate::runner::runner::{Cmd, Runner};
 use crate::settings::settings::Settings;
 use eframe::egui::widgets::Spinner;
 use eframe::egui::{self, Grid};
 use eframe::epaint::Vec2;
-use image;
 
 #[derive(Default)]
 pub struct Tools {
@@ -20,6 +20,8 @@ pub struct Tools {
     recordgifing: bool,
     shotcut_pics: Option<egui::TextureHandle>,
     init: bool,
+    runner: Runner,
+    error: Option<Error>,
 }
 
 impl Settings for Tools {
@@ -32,21 +34,21 @@ impl Settings for Tools {
             // pick color
             ui.label("Get pix color");
             if ui.button("pick pix color").clicked() {
-                self.pick_pix_color();
+                self.error = self.pick_pix_color().err();
             }
             ui.end_row();
             // shotcut
             ui.label("Shotcut");
             if ui.button("shotcut").clicked() {
-                self.shotcut(ui);
+                self.error = self.shotcut(ui).err();
             }
             ui.end_row();
             // recorder gif
             ui.label("Gif");
             if !self.recordgifing {
                 if ui.button("gif").clicked() {
-                    self.recordgifing = true;
-                    self.record_gif();
+                    self.error = self.record_gif().err();
+                    self.recordgifing = self.error.is_none();
                 }
             } else {
                 ui.add(Spinner::new());
@@ -60,8 +62,8 @@ impl Settings for Tools {
             ui.label("screnncast");
             if !self.screencasting {
                 if ui.button("screnncast").clicked() {
-                    self.screencast();
-                    self.screencasting = true;
+                    self.error = self.screencast().err();
+                    self.screencasting = self.error.is_none();
                 }
             } else {
                 ui.add(Spinner::new());
@@ -73,46 +75,67 @@ impl Settings for Tools {
         });
         if let Some(textcutre) = &self.shotcut_pics {
             ui.vertical_centered_justified(|ui| {
-            let mut r = 1.0;
-            let [x, y] = textcutre.size();
-            if x > 400 {
-                r = x as f32 / 400.0;
-            }
-            ui.centered_and_justified(|ui| {
-                ui.add(egui::Image::new(
-                    textcutre,
-                    Vec2::new(x as f32 / r, y as f32 / r),
-                ));
-            });
+                let mut r = 1.0;
+                let [x, y] = textcutre.size();
+                if x > 400 {
+                    r = x as f32 / 400.0;
+                }
+                ui.centered_and_justified(|ui| {
+                    ui.add(egui::Image::new(
+                        textcutre,
+                        Vec2::new(x as f32 / r, y as f32 / r),
+                    ));
+                });
             });
         }
     }
 
-    fn apply(&mut self) {
-        todo!()
+    fn apply(&mut self) -> Result<()> {
+        Ok(())
     }
-    fn init(&mut self) {
+    fn init(&mut self) -> Result<()> {
         self.init = true;
+        Ok(())
     }
 
     fn is_init(&self) -> bool {
         self.init
     }
+
+    fn take_error(&mut self) -> Option<Error> {
+        self.error.take()
+    }
 }
 
 impl Tools {
-    fn pick_pix_color(&self) {
-        let cmd_str = r#"grim -g "$(slurp -p)" -t ppm - | convert - -format '%[pixel:p{0,0}]' txt:- | tail -n 1 | cut -d ' ' -f 4 | wl-copy"#;
-        Command::new("zsh")
+    pub fn new(runner: Runner) -> Self {
+        Self {
+            runner,
+            ..Default::default()
+        }
+    }
+
+    fn shell(cmd_str: &str) -> Cmd {
+        Cmd::new("zsh")
             .env("XCURSOR_SIZE", "48")
             .arg("-c")
             .arg(cmd_str)
-            .output()
-            .unwrap();
     }
 
-    fn load_image_from_path(path: &std::path::Path) -> Result<egui::ColorImage, image::ImageError> {
-        let image = image::io::Reader::open(path)?.decode()?;
+    /// copy color of a picked pixel to the clipboard
+    pub fn pick_pix_color(&self) -> Result<()> {
+        let cmd_str = r#"grim -g "$(slurp -p)" -t ppm - | convert - -format '%[pixel:p{0,0}]' txt:- | tail -n 1 | cut -d ' ' -f 4 | wl-copy"#;
+        self.runner.stdout(&Self::shell(cmd_str))?;
+        Ok(())
+    }
+
+    fn load_image_from_path(path: &std::path::Path) -> Result<egui::ColorImage> {
+        let image = image::io::Reader::open(path)
+            .map_err(|source| Error::File {
+                path: path.to_path_buf(),
+                source,
+            })?
+            .decode()?;
         let size = [image.width() as _, image.height() as _];
         let image_buffer = image.to_rgba8();
         let pixels = image_buffer.as_flat_samples();
@@ -122,60 +145,60 @@ impl Tools {
         ))
     }
 
-    fn shotcut(&mut self, ui: &egui::Ui) {
+    /// shotcut of a selected region, returns path of the picture
+    pub fn take_shotcut(&self) -> Result<String> {
         let pic = format!("/tmp/{}.png", Self::get_now_secs());
         let cmd_str = format!(r#"grim -g "$(slurp)" {}"#, &pic);
-        Command::new("zsh")
-            .env("XCURSOR_SIZE", "48")
-            .arg("-c")
-            .arg(cmd_str.as_str())
-            .output()
-            .unwrap();
-        let img = Self::load_image_from_path(Path::new(pic.as_str())).unwrap();
+        self.runner.stdout(&Self::shell(&cmd_str))?;
+        Ok(pic)
+    }
+
+    fn shotcut(&mut self, ui: &egui::Ui) -> Result<()> {
+        let pic = self.take_shotcut()?;
+        let img = Self::load_image_from_path(Path::new(pic.as_str()))?;
         let texture = ui.ctx().load_texture("shotcut", img);
         self.shotcut_pics = Some(texture);
+        Ok(())
     }
 
-    fn record_gif(&self) {
+    fn record_gif(&self) -> Result<()> {
         let cmd_str = format!(
             r#"wf-recorder -f ~/temp/{}.gif -g "$(slurp)" -c gif"#,
             Self::get_now_secs()
         );
         let cond = self.recordgif_cond.clone();
+        let mut child = self.runner.start(&Self::shell(&cmd_str))?;
         thread::spawn(move || {
-            let mut cmd = Command::new("zsh");
-            cmd.env("XCURSOR_SIZE", "48")
-                .arg("-c")
-                .arg(cmd_str.as_str());
-            let mut child = cmd.spawn().unwrap();
             let guard = Arc::new(Mutex::new(1));
-            let mut guard = guard.lock().unwrap();
-            guard = cond.wait(guard).unwrap();
-            child.kill().expect("Can't kill record gif");
+            let guard = guard.lock().unwrap();
+            let _guard = cond.wait(guard).unwrap();
+            if let Err(e) = child.kill() {
+                eprintln!("Can't kill record gif: {}", e);
+            }
         });
+        Ok(())
     }
 
     fn stop_record_gif(&self) {
         self.recordgif_cond.notify_one();
     }
 
-    fn screencast(&self) {
+    fn screencast(&self) -> Result<()> {
         let cmd_str = format!(
             r#"wf-recorder -f ~/temp/{}.mkv -c libx264rgb"#,
             Self::get_now_secs()
         );
         let cond = self.screencast_cond.clone();
+        let mut child = self.runner.start(&Self::shell(&cmd_str))?;
         thread::spawn(move || {
-            let mut cmd = Command::new("zsh");
-            cmd.env("XCURSOR_SIZE", "48")
-                .arg("-c")
-                .arg(cmd_str.as_str());
-            let mut child = cmd.spawn().unwrap();
             let guard = Arc::new(Mutex::new(1));
             let guard = guard.lock().unwrap();
-            cond.wait(guard).unwrap();
-            child.kill().expect("Can't kill screencast");
+            let _guard = cond.wait(guard).unwrap();
+            if let Err(e) = child.kill() {
+                eprintln!("Can't kill screencast: {}", e);
+            }
         });
+        Ok(())
     }
 
     fn stop_screencast(&self) {